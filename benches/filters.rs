@@ -0,0 +1,212 @@
+//! Throughput of inserting and looking up keys in every filter, for each combination of hasher,
+//! number of hashes (`k`), number of keys (`n`) and key size, reported in operations per second.
+//!
+//! The whole suite takes a while, so pass a filter to run part of it:
+//! ```text
+//! cargo bench --bench filters -- BloomFilter_insert/murmur3
+//! cargo bench --bench filters -- threaded
+//! ```
+
+use bloom_filter::AtomicBloomFilter;
+use bloom_filter::AtomicCountingBloomFilter;
+use bloom_filter::BloomFilter;
+use bloom_filter::CountingBloomFilter;
+use bloom_filter::CountingWLockBloomFilter;
+use bloom_filter::Filter;
+use bloom_filter::ReHasher;
+use bloom_filter::ShardedBloomFilter;
+use bloom_filter::WLockBloomFilter;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use hashers::fnv::FNV1aHasher64;
+use murmur3::murmur3_32::MurmurHasher;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::time::Duration;
+
+/// The false positive rate every filter is sized for, along with its `n` and `k`.
+const RATE: f64 = 0.001;
+const KS: [usize; 3] = [1, 4, 7];
+const NS: [usize; 2] = [1_000, 100_000];
+/// The sizes of the keys in bytes.
+const KEY_SIZES: [usize; 2] = [8, 256];
+const SHARDS: usize = 64;
+
+/// Makes `count` distinct keys of `size` bytes, numbered from `start`.
+fn keys(start: usize, count: usize, size: usize) -> Vec<Vec<u8>> {
+    (start..start + count)
+        .map(|i| {
+            let mut key = vec![0xa5; size];
+            key[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            key
+        })
+        .collect()
+}
+
+/// Benchmarks inserting `n` keys into a new filter made by `new`, and looking up `n` keys,
+/// half of which were inserted, since lookups of keys that weren't inserted can stop at the first unset bit.
+fn bench_filter<F, H>(
+    c: &mut Criterion,
+    filter: &str,
+    hasher: &str,
+    new: fn(usize, ReHasher<H>) -> F,
+) where
+    F: Filter<Vec<u8>>,
+    H: Hasher + Default,
+{
+    for &k in &KS {
+        for &n in &NS {
+            for &size in &KEY_SIZES {
+                let id = BenchmarkId::new(hasher, format!("k{}_n{}_{}B", k, n, size));
+                let inserted = keys(0, n, size);
+                let probes = keys(n / 2, n, size);
+
+                let mut group = c.benchmark_group(format!("{}_insert", filter));
+                group.throughput(Throughput::Elements(n as u64));
+                group.bench_function(id.clone(), |b| {
+                    b.iter_batched(
+                        || new(n, ReHasher::new(k)),
+                        |mut bf| {
+                            inserted.iter().for_each(|key| bf.insert(key));
+                            // Returned so the filter is dropped outside of the measurement.
+                            bf
+                        },
+                        BatchSize::LargeInput,
+                    )
+                });
+                group.finish();
+
+                let mut bf = new(n, ReHasher::new(k));
+                inserted.iter().for_each(|key| bf.insert(key));
+                let mut group = c.benchmark_group(format!("{}_contains", filter));
+                group.throughput(Throughput::Elements(n as u64));
+                group.bench_function(id, |b| {
+                    b.iter(|| probes.iter().filter(|key| bf.contains(key)).count())
+                });
+                group.finish();
+            }
+        }
+    }
+}
+
+/// Benchmarks every filter with hashers built from `H`.
+fn bench_hasher<H: Hasher + Default>(c: &mut Criterion, hasher: &str) {
+    bench_filter::<BloomFilter<Vec<u8>, ReHasher<H>>, H>(c, "BloomFilter", hasher, |n, hashers| {
+        BloomFilter::with_rate(n, RATE, hashers)
+    });
+    bench_filter::<CountingBloomFilter<Vec<u8>, ReHasher<H>>, H>(
+        c,
+        "CountingBloomFilter",
+        hasher,
+        |n, hashers| CountingBloomFilter::with_rate(n, RATE, hashers),
+    );
+    bench_filter::<WLockBloomFilter<Vec<u8>, ReHasher<H>>, H>(
+        c,
+        "WLockBloomFilter",
+        hasher,
+        |n, hashers| WLockBloomFilter::with_rate(n, RATE, hashers),
+    );
+    bench_filter::<CountingWLockBloomFilter<Vec<u8>, ReHasher<H>>, H>(
+        c,
+        "CountingWLockBloomFilter",
+        hasher,
+        |n, hashers| CountingWLockBloomFilter::with_rate(n, RATE, hashers),
+    );
+    bench_filter::<AtomicBloomFilter<Vec<u8>, ReHasher<H>>, H>(
+        c,
+        "AtomicBloomFilter",
+        hasher,
+        |n, hashers| AtomicBloomFilter::with_rate(n, RATE, hashers),
+    );
+    bench_filter::<AtomicCountingBloomFilter<Vec<u8>, ReHasher<H>>, H>(
+        c,
+        "AtomicCountingBloomFilter",
+        hasher,
+        |n, hashers| AtomicCountingBloomFilter::with_rate(n, RATE, hashers),
+    );
+    bench_filter::<ShardedBloomFilter<Vec<u8>, ReHasher<H>>, H>(
+        c,
+        "ShardedBloomFilter",
+        hasher,
+        |n, hashers| ShardedBloomFilter::with_rate(n, RATE, hashers, SHARDS),
+    );
+}
+
+fn single_threaded(c: &mut Criterion) {
+    bench_hasher::<MurmurHasher>(c, "murmur3");
+    bench_hasher::<FNV1aHasher64>(c, "fnv1a64");
+    bench_hasher::<DefaultHasher>(c, "sip");
+}
+
+/// Inserts are spread across a number of threads, each inserting its own 1000 keys,
+/// to measure how each concurrent filter behaves under contention.
+fn threaded(c: &mut Criterion) {
+    fn insert_from_threads<F: Sync>(
+        c: &mut Criterion,
+        filter: &str,
+        new: fn(usize) -> F,
+        insert: fn(&F, &u64),
+    ) {
+        let mut group = c.benchmark_group(format!("threaded_{}_insert", filter));
+        for &threads in &[1u64, 4, 16, 32] {
+            group.throughput(Throughput::Elements(threads * 1000));
+            group.bench_function(BenchmarkId::from_parameter(format!("t{}", threads)), |b| {
+                b.iter_batched(
+                    || new(threads as usize * 1000),
+                    |bf| {
+                        std::thread::scope(|scope| {
+                            for t in 0..threads {
+                                let bf = &bf;
+                                scope.spawn(move || {
+                                    (t * 1000..(t + 1) * 1000).for_each(|n| insert(bf, &n))
+                                });
+                            }
+                        });
+                        bf
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+
+    type Hashers = ReHasher<MurmurHasher>;
+    insert_from_threads(
+        c,
+        "WLockBloomFilter",
+        |n| WLockBloomFilter::<u64, Hashers>::with_rate(n, RATE, ReHasher::new(4)),
+        |bf, n| bf.insert(n),
+    );
+    insert_from_threads(
+        c,
+        "ShardedBloomFilter",
+        |n| ShardedBloomFilter::<u64, Hashers>::with_rate(n, RATE, ReHasher::new(4), SHARDS),
+        |bf, n| bf.insert(n),
+    );
+    insert_from_threads(
+        c,
+        "AtomicBloomFilter",
+        |n| AtomicBloomFilter::<u64, Hashers>::with_rate(n, RATE, ReHasher::new(4)),
+        |bf, n| bf.insert(n),
+    );
+}
+
+fn config() -> Criterion {
+    // The grid is large, so each benchmark is measured for less time than criterion's default.
+    Criterion::default()
+        .warm_up_time(Duration::from_millis(500))
+        .measurement_time(Duration::from_secs(2))
+        .sample_size(20)
+}
+
+criterion_group! {
+    name = benches;
+    config = config();
+    targets = single_threaded, threaded
+}
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+fn main() {
+    #[cfg(feature = "capi")]
+    capi();
+}
+
+/// Generates the header of the C API into `OUT_DIR`,
+/// where a test in `src/capi.rs` checks that the checked in `capi/bloom_filter.h` matches it.
+#[cfg(feature = "capi")]
+fn capi() {
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=capi/cbindgen.toml");
+    let config =
+        cbindgen::Config::from_file("capi/cbindgen.toml").expect("capi/cbindgen.toml is invalid");
+    let out_dir = std::env::var("OUT_DIR").expect("cargo sets OUT_DIR for build scripts");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/capi.rs")
+        .generate()
+        .expect("the C API couldn't be turned into a header")
+        .write_to_file(std::path::Path::new(&out_dir).join("bloom_filter.h"));
+}
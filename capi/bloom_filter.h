@@ -0,0 +1,117 @@
+#ifndef BLOOM_FILTER_H
+#define BLOOM_FILTER_H
+
+/* Generated by cbindgen from src/capi.rs. Don't edit this file by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The outcome of a call.
+ */
+typedef enum BloomStatus {
+  /**
+   * The call succeeded.
+   */
+  BLOOM_STATUS_OK = 0,
+  /**
+   * A pointer that must not be null was null.
+   */
+  BLOOM_STATUS_NULL_POINTER = 1,
+  /**
+   * The number of expected elements was 0, or the false positive rate was not between 0 and 1 exclusive.
+   */
+  BLOOM_STATUS_INVALID_PARAMETERS = 2,
+  /**
+   * The bytes passed to `bloom_deserialize` aren't a serialized filter.
+   */
+  BLOOM_STATUS_INVALID_FORMAT = 3,
+  /**
+   * The buffer passed to `bloom_serialize` is too small to hold the filter.
+   */
+  BLOOM_STATUS_BUFFER_TOO_SMALL = 4,
+} BloomStatus;
+
+/**
+ * An opaque handle to a filter of byte strings.
+ */
+typedef struct Bloom Bloom;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a filter sized for `n` elements with a false positive rate of `p`, and writes its handle to `out`.
+ *
+ * # Safety
+ * `out` must be valid for writes.
+ */
+enum BloomStatus bloom_new(size_t n,
+                           double p,
+                           struct Bloom **out);
+
+/**
+ * Inserts the `len` bytes at `bytes` into the filter.
+ *
+ * # Safety
+ * `filter` must be a handle that hasn't been freed, and `bytes` must be valid for reads of `len` bytes.
+ */
+enum BloomStatus bloom_insert_bytes(struct Bloom *filter,
+                                    const uint8_t *bytes,
+                                    size_t len);
+
+/**
+ * Tests to see if the `len` bytes at `bytes` are in the filter, and writes the answer to `out`.
+ * This will return false positives, but never false negatives.
+ *
+ * # Safety
+ * `filter` must be a handle that hasn't been freed, `bytes` must be valid for reads of `len` bytes,
+ * and `out` must be valid for writes.
+ */
+enum BloomStatus bloom_contains_bytes(const struct Bloom *filter,
+                                      const uint8_t *bytes,
+                                      size_t len,
+                                      bool *out);
+
+/**
+ * Serializes the filter in the format described in the `format` module into the `capacity` bytes at `buf`,
+ * and writes the number of bytes it takes to `len`.
+ *
+ * If `buf` is too small, nothing is written to it, `len` is still set, and `BloomStatus::BufferTooSmall`
+ * is returned, so `buf` may be null to find out how large it needs to be.
+ *
+ * # Safety
+ * `filter` must be a handle that hasn't been freed, `buf` must be valid for writes of `capacity` bytes,
+ * and `len` must be valid for writes.
+ */
+enum BloomStatus bloom_serialize(const struct Bloom *filter,
+                                 uint8_t *buf,
+                                 size_t capacity,
+                                 size_t *len);
+
+/**
+ * Deserializes the `len` bytes at `bytes`, written by `bloom_serialize` or `BloomFilter::to_bytes`,
+ * and writes the handle of the filter to `out`.
+ *
+ * # Safety
+ * `bytes` must be valid for reads of `len` bytes, and `out` must be valid for writes.
+ */
+enum BloomStatus bloom_deserialize(const uint8_t *bytes, size_t len, struct Bloom **out);
+
+/**
+ * Releases a filter. Null handles are ignored.
+ *
+ * # Safety
+ * `filter` must be null, or a handle that hasn't already been freed.
+ */
+void bloom_free(struct Bloom *filter);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BLOOM_FILTER_H */
@@ -0,0 +1,73 @@
+/*
+ * Exercises the C API the way a C program would.
+ * `tests/capi.rs` compiles this against the static library, runs it, and checks that it exits with 0.
+ */
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "bloom_filter.h"
+
+#define CHECK(condition) \
+    if (!(condition)) {  \
+        return __LINE__; \
+    }
+
+/* Returns 0 if every check passed, or the line of the first one that failed. */
+static int run(void) {
+    Bloom *filter = NULL;
+    CHECK(bloom_new(1000, 0.01, &filter) == BLOOM_STATUS_OK);
+    CHECK(filter != NULL);
+
+    const char *keys[] = {"hello", "there", "general"};
+    for (size_t i = 0; i < 3; i++) {
+        CHECK(bloom_insert_bytes(filter, (const uint8_t *)keys[i], strlen(keys[i])) == BLOOM_STATUS_OK);
+    }
+    bool found = false;
+    CHECK(bloom_contains_bytes(filter, (const uint8_t *)"hello", 5, &found) == BLOOM_STATUS_OK);
+    CHECK(found);
+    CHECK(bloom_contains_bytes(filter, (const uint8_t *)"kenobi", 6, &found) == BLOOM_STATUS_OK);
+    CHECK(!found);
+
+    /* Asks for the size first, then serializes into a buffer of that size. */
+    size_t len = 0;
+    CHECK(bloom_serialize(filter, NULL, 0, &len) == BLOOM_STATUS_BUFFER_TOO_SMALL);
+    CHECK(len > 32);
+    uint8_t *bytes = malloc(len);
+    CHECK(bytes != NULL);
+    size_t written = 0;
+    CHECK(bloom_serialize(filter, bytes, len, &written) == BLOOM_STATUS_OK);
+    CHECK(written == len);
+    CHECK(memcmp(bytes, "BLMF", 4) == 0);
+    bloom_free(filter);
+
+    Bloom *copy = NULL;
+    CHECK(bloom_deserialize(bytes, len, &copy) == BLOOM_STATUS_OK);
+    for (size_t i = 0; i < 3; i++) {
+        CHECK(bloom_contains_bytes(copy, (const uint8_t *)keys[i], strlen(keys[i]), &found) == BLOOM_STATUS_OK);
+        CHECK(found);
+    }
+    bytes[0] = 'X';
+    Bloom *invalid = NULL;
+    CHECK(bloom_deserialize(bytes, len, &invalid) == BLOOM_STATUS_INVALID_FORMAT);
+    CHECK(invalid == NULL);
+    free(bytes);
+    bloom_free(copy);
+
+    CHECK(bloom_new(0, 0.01, &filter) == BLOOM_STATUS_INVALID_PARAMETERS);
+    CHECK(bloom_new(1000, 1.5, &filter) == BLOOM_STATUS_INVALID_PARAMETERS);
+    CHECK(bloom_insert_bytes(NULL, (const uint8_t *)"hello", 5) == BLOOM_STATUS_NULL_POINTER);
+    bloom_free(NULL);
+    return 0;
+}
+
+int main(void) {
+    int line = run();
+    if (line != 0) {
+        fprintf(stderr, "the check on line %d failed\n", line);
+        return 1;
+    }
+    return 0;
+}
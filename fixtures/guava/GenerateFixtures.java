@@ -0,0 +1,78 @@
+import com.google.common.hash.BloomFilter;
+import com.google.common.hash.Funnel;
+import com.google.common.hash.Funnels;
+import java.io.FileOutputStream;
+import java.io.IOException;
+import java.io.OutputStream;
+import java.lang.reflect.Field;
+import java.lang.reflect.Method;
+import java.nio.charset.StandardCharsets;
+import java.nio.file.Files;
+import java.nio.file.Paths;
+
+/**
+ * Writes the fixtures used by the tests in `src/guava.rs`, with Guava's own `BloomFilter.writeTo`.
+ *
+ * <p>Written against Guava 33.3.1-jre. From this directory, run:
+ *
+ * <pre>
+ * curl -LO https://repo1.maven.org/maven2/com/google/guava/guava/33.3.1-jre/guava-33.3.1-jre.jar
+ * java -cp guava-33.3.1-jre.jar GenerateFixtures.java
+ * </pre>
+ *
+ * <p>The Guava jar the fixtures were written with is recorded in `VERSION`.
+ */
+public class GenerateFixtures {
+  public static void main(String[] args) throws Exception {
+    BloomFilter<CharSequence> strings =
+        BloomFilter.create(Funnels.stringFunnel(StandardCharsets.UTF_8), 1000, 0.01);
+    for (int i = 0; i < 100; i++) {
+      strings.put("item" + i);
+    }
+    write(strings, "strings_mitz64.bin");
+
+    BloomFilter<Long> longs = BloomFilter.create(Funnels.longFunnel(), 500, 0.03);
+    for (long i = 0; i < 200; i++) {
+      longs.put(i * 1_000_003L - 7);
+    }
+    write(longs, "longs_mitz64.bin");
+
+    BloomFilter<CharSequence> legacy =
+        createWithStrategy(Funnels.stringFunnel(StandardCharsets.UTF_8), 1000, 0.01, "MURMUR128_MITZ_32");
+    for (int i = 0; i < 100; i++) {
+      legacy.put("item" + i);
+    }
+    write(legacy, "strings_mitz32.bin");
+
+    String jar = Paths.get(BloomFilter.class.getProtectionDomain().getCodeSource().getLocation().toURI())
+        .getFileName()
+        .toString();
+    Files.write(Paths.get("VERSION"), (jar + "\n").getBytes(StandardCharsets.UTF_8));
+  }
+
+  /**
+   * `BloomFilter.create` always uses `MURMUR128_MITZ_64`, so filters written by Guava before it was added
+   * are made through the package private overload that takes a strategy.
+   */
+  @SuppressWarnings("unchecked")
+  static <T> BloomFilter<T> createWithStrategy(
+      Funnel<? super T> funnel, long expectedInsertions, double fpp, String strategyName)
+      throws Exception {
+    Class<?> strategies = Class.forName("com.google.common.hash.BloomFilterStrategies");
+    Field field = strategies.getDeclaredField(strategyName);
+    field.setAccessible(true);
+    Object strategy = field.get(null);
+    Class<?> strategyType = Class.forName("com.google.common.hash.BloomFilter$Strategy");
+    Method create =
+        BloomFilter.class.getDeclaredMethod(
+            "create", Funnel.class, long.class, double.class, strategyType);
+    create.setAccessible(true);
+    return (BloomFilter<T>) create.invoke(null, funnel, expectedInsertions, fpp, strategy);
+  }
+
+  static void write(BloomFilter<?> filter, String path) throws IOException {
+    try (OutputStream out = new FileOutputStream(path)) {
+      filter.writeTo(out);
+    }
+  }
+}
@@ -0,0 +1,66 @@
+#!/usr/bin/env python3
+"""Captures the `BF.SCANDUMP` fixtures used by the tests in `src/redis_bloom.rs` from a running RedisBloom.
+
+Start a server with RedisBloom loaded, for example with
+
+    docker run --rm -p 6379:6379 redis/redis-stack-server:7.4.0-v1
+
+then run this with redis-py installed (`pip install redis`), from any directory:
+
+    python3 capture.py [host] [port]
+
+Each fixture starts with `#` lines recording the server's version and the commands that built the filter,
+followed by a line per `BF.SCANDUMP` reply, holding its iterator and its hex encoded data.
+"""
+
+import os
+import sys
+
+import redis
+
+
+def module_versions(r):
+    versions = {}
+    for module in r.execute_command("MODULE", "LIST"):
+        fields = dict(zip(module[::2], module[1::2]))
+        versions[fields[b"name"].decode()] = fields[b"ver"]
+    return versions
+
+
+def capture(r, name, capacity, error, items):
+    key = "fixture:" + name
+    r.delete(key)
+    r.execute_command("BF.RESERVE", key, error, capacity)
+    for i in range(items):
+        r.execute_command("BF.ADD", key, "item%d" % i)
+    replies = []
+    it = 0
+    while True:
+        it, data = r.execute_command("BF.SCANDUMP", key, it)
+        if it == 0:
+            break
+        replies.append((it, data))
+    r.delete(key)
+
+    redis_version = r.info("server")["redis_version"]
+    bloom_version = module_versions(r)["bf"]
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), name)
+    with open(path, "w") as f:
+        f.write("# Captured from RedisBloom %d on Redis %s by capture.py\n" % (bloom_version, redis_version))
+        f.write("# BF.RESERVE key %s %d\n" % (error, capacity))
+        f.write("# BF.ADD key item<i> for i in 0..%d\n" % items)
+        f.write("# BF.SCANDUMP key <iterator> until the iterator is 0\n")
+        for it, data in replies:
+            f.write("%d %s\n" % (it, data.hex()))
+
+
+def main():
+    host = sys.argv[1] if len(sys.argv) > 1 else "localhost"
+    port = int(sys.argv[2]) if len(sys.argv) > 2 else 6379
+    r = redis.Redis(host=host, port=port)
+    capture(r, "single.txt", 1000, 0.01, 100)
+    capture(r, "scaled.txt", 100, 0.01, 250)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Builds the Python module into a venv under `target/`, and runs the tests in `python/tests` against it.
+# Only the standard library is used, so this works without network access.
+set -eu
+cd "$(dirname "$0")/.."
+venv=target/python-venv
+[ -d "$venv" ] || python3 -m venv "$venv"
+cargo rustc --lib --features python,pyo3/extension-module --crate-type cdylib
+site=$("$venv/bin/python" -c 'import sysconfig; print(sysconfig.get_paths()["platlib"])')
+case "$(uname)" in
+    Darwin) library=target/debug/libbloom_filter.dylib ;;
+    *) library=target/debug/libbloom_filter.so ;;
+esac
+cp "$library" "$site/bloom_filter.so"
+"$venv/bin/python" -m unittest discover -s python/tests -v
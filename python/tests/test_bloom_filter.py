@@ -0,0 +1,115 @@
+import os
+import unittest
+
+from bloom_filter import BloomFilter, CountingBloomFilter
+
+FIXTURES = os.path.join(os.path.dirname(__file__), "..", "..", "fixtures", "python")
+
+
+class BloomFilterTest(unittest.TestCase):
+    def test_add_and_contains(self):
+        bf = BloomFilter(1000, 0.01)
+        bf.add("hello")
+        bf.add(b"there")
+        bf.add(42)
+        self.assertIn("hello", bf)
+        self.assertIn(b"there", bf)
+        self.assertIn(42, bf)
+        self.assertNotIn("not here", bf)
+        # A str and its encoding are different items.
+        self.assertNotIn(b"hello", bf)
+
+    def test_constructors(self):
+        self.assertEqual(BloomFilter.optimal_new(1000, 0.01).k, 7)
+        bf = BloomFilter.with_rate(1000, 0.01, 3)
+        self.assertEqual(bf.k, 3)
+        # Fewer hashes than optimal need more bits for the same rate.
+        self.assertGreater(bf.num_bits, BloomFilter(1000, 0.01).num_bits)
+        with self.assertRaises(ValueError):
+            BloomFilter(0, 0.01)
+        with self.assertRaises(ValueError):
+            BloomFilter(1000, 1.5)
+
+    def test_unsupported_items(self):
+        with self.assertRaises(TypeError):
+            BloomFilter(10, 0.01).add(1.5)
+
+    def test_len_is_estimated(self):
+        bf = BloomFilter(10000, 0.01)
+        self.assertEqual(len(bf), 0)
+        for i in range(1000):
+            bf.add(i)
+            bf.add(i)
+        self.assertAlmostEqual(len(bf), 1000, delta=50)
+
+    def test_union_and_intersection(self):
+        a = BloomFilter(1000, 0.01)
+        b = BloomFilter(1000, 0.01)
+        a.add("hello")
+        a.add("both")
+        b.add("there")
+        b.add("both")
+        union = a | b
+        self.assertTrue(all(item in union for item in ["hello", "there", "both"]))
+        intersection = a & b
+        self.assertIn("both", intersection)
+        self.assertNotIn("hello", intersection)
+        with self.assertRaises(ValueError):
+            a | BloomFilter(10, 0.01)
+
+    def test_bytes_round_trip(self):
+        bf = BloomFilter(1000, 0.01)
+        bf.add("hello")
+        data = bf.to_bytes()
+        self.assertEqual(data[:4], b"BLMF")
+        self.assertIn("hello", BloomFilter.from_bytes(data))
+        self.assertEqual(BloomFilter.from_bytes(data).to_bytes(), data)
+        with self.assertRaises(ValueError):
+            BloomFilter.from_bytes(b"not a filter")
+
+    def test_reads_rust_filters(self):
+        with open(os.path.join(FIXTURES, "rust_strings.bf"), "rb") as f:
+            data = f.read()
+        bf = BloomFilter.from_bytes(data)
+        self.assertTrue(all("item%d" % i in bf for i in range(100)))
+        built = BloomFilter(1000, 0.01)
+        for i in range(100):
+            built.add("item%d" % i)
+        self.assertEqual(built.to_bytes(), data)
+
+
+class CountingBloomFilterTest(unittest.TestCase):
+    def test_counts(self):
+        bf = CountingBloomFilter(1000, 0.01)
+        self.assertEqual(bf.false_positive_chance(), 0.0)
+        bf.add("hello")
+        bf.add("hello")
+        self.assertEqual(bf.count, 2)
+        self.assertEqual(len(bf), 1)
+        self.assertIn("hello", bf)
+        self.assertGreater(bf.false_positive_chance(), 0.0)
+
+    def test_union_and_intersection(self):
+        a = CountingBloomFilter.with_rate(1000, 0.01, 4)
+        b = CountingBloomFilter.with_rate(1000, 0.01, 4)
+        a.add("hello")
+        b.add("there")
+        b.add("both")
+        self.assertEqual((a | b).count, 3)
+        self.assertEqual((a & b).count, 1)
+        self.assertIn("there", a | b)
+
+    def test_bytes_round_trip(self):
+        bf = CountingBloomFilter(1000, 0.01)
+        bf.add(b"hello")
+        copy = CountingBloomFilter.from_bytes(bf.to_bytes())
+        self.assertEqual(copy.count, 1)
+        self.assertIn(b"hello", copy)
+        # Plain filters don't record a count.
+        self.assertIn(b"hello", BloomFilter.from_bytes(bf.to_bytes()))
+        with self.assertRaises(ValueError):
+            CountingBloomFilter.from_bytes(BloomFilter(1000, 0.01).to_bytes())
+
+
+if __name__ == "__main__":
+    unittest.main()
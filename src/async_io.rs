@@ -0,0 +1,227 @@
+//! Reading and writing serialized filters through tokio's `AsyncRead` and `AsyncWrite`,
+//! so async tasks can save and load filters without blocking the executor thread on IO.
+//!
+//! The bytes are the format described in the `format` module, so files written by `to_bytes`
+//! can be loaded with `load_from`, and files saved with `save_to` read by `from_bytes`.
+//! Filters shared between tasks should be `AtomicBloomFilter`s, whose `insert` and `contains` never block.
+
+use crate::atomic_bloom_filter::AtomicBloomFilter;
+use crate::bit_store::BitStore;
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::format::HEADER_LEN;
+use crate::hash_to_indicies::K;
+use std::io;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+fn invalid_data(e: BloomError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Reads the bytes of one serialized filter.
+/// Raw bits are read up to the end of the filter, leaving whatever follows it in the reader,
+/// but Rice coded bits don't record their length, so they are read up to the end of the reader.
+async fn read_filter<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; HEADER_LEN];
+    reader.read_exact(&mut bytes).await?;
+    match crate::format::raw_bits_len(&bytes).map_err(invalid_data)? {
+        Some(len) => {
+            // Read through `take`, so a corrupt header can't make this allocate more than the reader holds.
+            let read = reader.take(len as u64).read_to_end(&mut bytes).await?;
+            if read != len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the filter's bits were cut short",
+                ));
+            }
+        }
+        None => {
+            reader.read_to_end(&mut bytes).await?;
+        }
+    }
+    Ok(bytes)
+}
+
+impl<T: ?Sized, U: K, S: BitStore> BloomFilter<T, U, S> {
+    /// Writes the filter to `writer`, serialized like `to_bytes`.
+    ///
+    /// # Errors
+    /// If the bytes can't be written.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let mut saved = Vec::new();
+    /// bf.save_to(&mut saved).await.unwrap();
+    /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::load_from(&mut &saved[..], ReHasher::new(2)).await.unwrap();
+    /// assert!(bf.contains(&"hello"));
+    /// # });
+    /// ```
+    pub async fn save_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes()).await
+    }
+}
+
+impl<T: ?Sized, U: K> BloomFilter<T, U> {
+    /// Reads a filter from `reader`, serialized like `to_bytes` or `to_compact_bytes`.
+    /// The hashers must be the same as the ones the filter was written with, or lookups will be wrong.
+    ///
+    /// A filter with raw bits is read up to its end, so more can follow it in the reader,
+    /// but one with Rice coded bits is read up to the end of the reader.
+    ///
+    /// # Errors
+    /// If the bytes can't be read, or the reader ends before the filter does,
+    /// or with `io::ErrorKind::InvalidData` wrapping a `BloomError` if the bytes aren't a serialized filter,
+    /// or were written by a filter with hashers that perform a different number of hashes.
+    pub async fn load_from<R: AsyncRead + Unpin>(reader: &mut R, hashers: U) -> io::Result<Self> {
+        let bytes = read_filter(reader).await?;
+        Self::from_bytes(&bytes, hashers).map_err(invalid_data)
+    }
+}
+
+impl<T, U: K> AtomicBloomFilter<T, U> {
+    /// Writes the filter to `writer`, serialized like `to_bytes`.
+    /// The bits are copied before anything is written, so inserts can carry on while the write is pending,
+    /// but any that run during the copy may be partially saved.
+    ///
+    /// # Errors
+    /// If the bytes can't be written.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let mut saved = Vec::new();
+    /// bf.save_to(&mut saved).await.unwrap();
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::load_from(&mut &saved[..], ReHasher::new(2)).await.unwrap();
+    /// assert!(bf.contains(&"hello"));
+    /// # });
+    /// ```
+    pub async fn save_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        let bytes = self.to_bytes();
+        writer.write_all(&bytes).await
+    }
+
+    /// Reads a filter from `reader`, like `BloomFilter::load_from`.
+    ///
+    /// # Errors
+    /// If the bytes can't be read, or the reader ends before the filter does,
+    /// or with `io::ErrorKind::InvalidData` wrapping a `BloomError` if the bytes aren't a serialized filter,
+    /// or were written by a filter with hashers that perform a different number of hashes.
+    pub async fn load_from<R: AsyncRead + Unpin>(reader: &mut R, hashers: U) -> io::Result<Self> {
+        let bytes = read_filter(reader).await?;
+        Self::from_bytes(&bytes, hashers).map_err(invalid_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rehasher::ReHasher;
+    use murmur3::murmur3_32::MurmurHasher;
+    use std::sync::Arc;
+
+    type Shared = AtomicBloomFilter<u32, ReHasher<MurmurHasher>>;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(4)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn tasks_share_a_filter() {
+        runtime().block_on(async {
+            let bf = Arc::new(Shared::optimal_new(4000, 0.001));
+            let tasks: Vec<_> = (0..8)
+                .map(|t| {
+                    let bf = bf.clone();
+                    tokio::spawn(async move {
+                        for n in t * 500..(t + 1) * 500 {
+                            bf.insert(&n);
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                })
+                .collect();
+            for task in tasks {
+                task.await.unwrap();
+            }
+            (0..4000).for_each(|n| assert!(bf.contains(&n)));
+        });
+    }
+
+    #[test]
+    fn saves_and_loads_through_a_pipe() {
+        runtime().block_on(async {
+            let bf = Shared::optimal_new(1000, 0.01);
+            (0..500).for_each(|n| bf.insert(&n));
+            let (mut writer, mut reader) = tokio::io::duplex(64);
+            let expected = bf.to_bytes();
+            let saving = tokio::spawn(async move {
+                bf.save_to(&mut writer).await.unwrap();
+                // Something else follows the filter in the stream.
+                writer.write_all(b"after").await.unwrap();
+            });
+            let loaded = Shared::load_from(&mut reader, ReHasher::new(7))
+                .await
+                .unwrap();
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).await.unwrap();
+            saving.await.unwrap();
+            assert_eq!(loaded.to_bytes(), expected);
+            assert_eq!(rest, b"after");
+        });
+    }
+
+    #[test]
+    fn loads_compact_bytes() {
+        runtime().block_on(async {
+            let mut bf = BloomFilter::<u32, ReHasher<MurmurHasher>>::new(10_000, ReHasher::new(3));
+            (0..10).for_each(|n| bf.insert(&n));
+            let compact = bf.to_compact_bytes();
+            let loaded = BloomFilter::<u32, ReHasher<MurmurHasher>>::load_from(
+                &mut &compact[..],
+                ReHasher::new(3),
+            )
+            .await
+            .unwrap();
+            assert_eq!(loaded.to_bytes(), bf.to_bytes());
+        });
+    }
+
+    #[test]
+    fn reports_bad_input() {
+        runtime().block_on(async {
+            let bytes = Shared::new(1000, ReHasher::new(2)).to_bytes();
+            let short = Shared::load_from(&mut &bytes[..bytes.len() - 1], ReHasher::new(2))
+                .await
+                .map(|_| ())
+                .unwrap_err();
+            assert_eq!(short.kind(), io::ErrorKind::UnexpectedEof);
+            let wrong_k = Shared::load_from(&mut &bytes[..], ReHasher::new(3))
+                .await
+                .map(|_| ())
+                .unwrap_err();
+            assert_eq!(wrong_k.kind(), io::ErrorKind::InvalidData);
+            let not_a_filter = Shared::load_from(&mut &[0u8; 64][..], ReHasher::new(2))
+                .await
+                .map(|_| ())
+                .unwrap_err();
+            assert_eq!(not_a_filter.kind(), io::ErrorKind::InvalidData);
+        });
+    }
+}
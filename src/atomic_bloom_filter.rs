@@ -0,0 +1,477 @@
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use crate::rehasher::ReHasher;
+use crate::sync::AtomicU32;
+use crate::sync::Ordering;
+use bit_vec::BitVec;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+/// A bloom filter that can be shared across threads, which sets its bits with atomic operations
+/// instead of taking a lock, so neither `insert` nor `contains` ever blocks or spins.
+/// This makes it safe to use from async tasks, which must not stall the executor thread they run on.
+///
+/// # Notes
+/// The bits are packed into atomic words, and each bit is set with a single `fetch_or`.
+/// The memory ordering contract is:
+/// 1. If an `insert` of a value happens-before a `contains` of that value, the `contains` will return true.
+/// 2. Bits are set with `Release`, and the loads made by `contains` are `Acquire`.
+///    A `contains` that returns true has synchronized with the inserts that set its bits,
+///    so anything written by those threads before inserting is visible after it returns.
+/// 3. An `insert` that runs concurrently with a `contains` of the same value may have only some of its
+///    bits observed, so the `contains` may return false until the `insert` returns.
+///    This is not a data race, just a read of some of the bits,
+///    so unlike with `WLockBloomFilter`, lookups never wait for inserts.
+#[derive(Debug)]
+pub struct AtomicBloomFilter<T, K> {
+    /// The backing words, with bit `i` in bit `i % 32` of word `i / 32`, like a `BitVec`'s blocks.
+    words: Vec<AtomicU32>,
+    /// The number of bits, which may be fewer than the words hold.
+    num_bits: usize,
+    /// The type information of what the filter will accept as input.
+    type_info: PhantomData<T>,
+    /// The generic hashing structure.
+    pub(crate) k: K,
+}
+
+impl<T, H> AtomicBloomFilter<T, ReHasher<H>> {
+    /// Constructs a new AtomicBloomFilter with an optimal ratio of m and k,
+    /// derived from n and p inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001);
+    /// ```
+    pub fn optimal_new(n: usize, p: f64) -> Self {
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new AtomicBloomFilter with an optimal ratio of m and k,
+    /// returning an error instead of panicking if the parameters are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(0, 0.001);
+    /// assert_eq!(bf.err(), Some(BloomError::NoElements));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        let k = crate::optimal_k(n, m)?;
+        Ok(Self::from_parts(m, ReHasher::new(k)))
+    }
+}
+
+impl<T, H> AtomicBloomFilter<T, H>
+where
+    H: HashToIndices + K,
+{
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    /// * `hashers` - Hashing to indices structure.
+    ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(4));
+    /// ```
+    pub fn with_rate(n: usize, p: f64, hashers: H) -> Self {
+        Self::try_with_rate(n, p, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if the parameters are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    pub fn try_with_rate(n: usize, p: f64, hashers: H) -> Result<Self, BloomError> {
+        let m = crate::m_from_knp(hashers.k(), n, p)?;
+        Ok(Self::from_parts(m, hashers))
+    }
+}
+
+impl<T, K> AtomicBloomFilter<T, K> {
+    fn from_parts(m: usize, hashers: K) -> Self {
+        AtomicBloomFilter {
+            words: (0..m.div_ceil(32)).map(|_| AtomicU32::new(0)).collect(),
+            num_bits: m,
+            type_info: PhantomData,
+            k: hashers,
+        }
+    }
+
+    /// Gets the number of bits used in the bloom filter.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Copies the bits into a bitvec. Words are read one at a time,
+    /// so concurrent inserts may be partially copied.
+    fn bit_vec(&self) -> BitVec {
+        let bytes: Vec<u8> = self
+            .words
+            .iter()
+            .flat_map(|w| w.load(Ordering::Acquire).to_le_bytes())
+            .take(self.num_bits.div_ceil(8))
+            .collect();
+        crate::format::bits_to_bit_vec(&bytes, self.num_bits)
+    }
+
+    /// Creates a filter holding the bits of a `BloomFilter`, which can then be shared across threads.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(1000, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let bf = AtomicBloomFilter::from_snapshot(bf);
+    /// assert!(bf.contains(&"hello"));
+    /// ```
+    pub fn from_snapshot(snapshot: BloomFilter<T, K>) -> Self {
+        AtomicBloomFilter {
+            words: snapshot
+                .bits
+                .storage()
+                .iter()
+                .map(|&w| AtomicU32::new(w))
+                .collect(),
+            num_bits: snapshot.bits.len(),
+            type_info: PhantomData,
+            k: snapshot.k,
+        }
+    }
+}
+
+impl<T, K: Clone> AtomicBloomFilter<T, K> {
+    /// Copies the filter into an owned `BloomFilter`.
+    ///
+    /// # Notes
+    /// No lock is taken, so an insert that runs at the same time as the copy may be only partially in the snapshot.
+    /// Every insert that happens-before the call is entirely in it.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// let snapshot = bf.snapshot();
+    /// bf.insert(&"there");
+    /// assert!(snapshot.contains(&"hello"));
+    /// assert!(!snapshot.contains(&"there"));
+    /// ```
+    pub fn snapshot(&self) -> BloomFilter<T, K> {
+        BloomFilter::from_parts(self.bit_vec(), self.k.clone())
+    }
+}
+
+impl<T, U: K> AtomicBloomFilter<T, U> {
+    /// Serializes the filter in the format described in the `format` module, like `BloomFilter::to_bytes`.
+    /// Words are read one at a time, so concurrent inserts may be partially serialized.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let bytes = bf.to_bytes();
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::from_bytes(&bytes, ReHasher::new(2)).unwrap();
+    /// assert!(bf.contains(&"hello"));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::format::encode(self.k.k(), &self.bit_vec(), None)
+    }
+
+    /// Deserializes a filter written by `to_bytes`, or by any other filter's `to_bytes` or `to_compact_bytes`.
+    /// The hashers must be the same as the ones the filter was written with, or lookups will be wrong.
+    ///
+    /// # Errors
+    /// If the bytes aren't a serialized filter,
+    /// or if they were written by a filter with hashers that perform a different number of hashes.
+    pub fn from_bytes(bytes: &[u8], hashers: U) -> Result<Self, BloomError> {
+        BloomFilter::from_bytes(bytes, hashers).map(Self::from_snapshot)
+    }
+}
+
+impl<T, K> AtomicBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+{
+    /// Creates the bloom filter with a given number of bits
+    /// and with a multiple-hashing-to-index function.
+    ///
+    /// # Arguments
+    /// * `m` - Number of bits for the filter.
+    /// * `hashers` - Hashing to indices structure.
+    ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// ```
+    pub fn new(m: usize, hashers: K) -> Self {
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::error::check_m(m)?;
+        crate::error::check_k(hashers.k())?;
+        Ok(Self::from_parts(m, hashers))
+    }
+
+    /// Takes multiple hashes of the provided value, takes the hashes modulo the number of bits
+    /// (converting them to indexes) and sets the bits at those indexes.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// ```
+    pub fn insert(&self, value: &T) {
+        self.k
+            .hash_to_indices(value, self.num_bits)
+            .into_iter()
+            .for_each(|i| {
+                self.words[i / 32].fetch_or(1 << (i % 32), Ordering::Release);
+            });
+    }
+
+    /// Tests to see if the provided value is in the bloom filter.
+    /// This will return false positives if the bits that are the result of hashing the value are already set.
+    /// Likelihood of false positives will increase as the filter fills up.
+    /// This can be mitigated by allocating more bits to the bloom filter, and by increasing the number of hash functions used ('k').
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///   These indices will be used to see if the element has been added.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"there");
+    /// assert!(bf.contains(&"hello"));
+    /// assert!(bf.contains(&"there"));
+    /// assert!(!bf.contains(&"not here"));
+    /// ```
+    pub fn contains(&self, value: &T) -> bool {
+        self.k
+            .hash_to_indices(value, self.num_bits)
+            .into_iter()
+            .all(|i| self.words[i / 32].load(Ordering::Acquire) >> (i % 32) & 1 == 1)
+    }
+
+    /// Gets the number of bits that are set.
+    /// Words are read one at a time, so concurrent inserts may be partially counted.
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|w| w.load(Ordering::Relaxed).count_ones() as usize)
+            .sum()
+    }
+
+    /// Gets the fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.num_bits as f64
+    }
+
+    /// Checks if no bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| w.load(Ordering::Relaxed) == 0)
+    }
+
+    /// Checks if at least `threshold` of the bits are set.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.fill_ratio() >= threshold
+    }
+
+    /// Unsets every bit in place, so the filter can be reused without replacing it wherever it is shared.
+    ///
+    /// # Notes
+    /// Words are cleared one at a time, so an insert that runs at the same time may be partially cleared.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// ```
+    pub fn clear(&self) {
+        self.words
+            .iter()
+            .for_each(|w| w.store(0, Ordering::Release));
+    }
+
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of bits that are set.
+    pub fn current_false_positive_rate(&self) -> f64 {
+        crate::sizing::false_positive_rate_from_fill(self.fill_ratio(), self.k.k())
+    }
+}
+
+impl<T, U: K> K for AtomicBloomFilter<T, U> {
+    fn k(&self) -> usize {
+        self.k.k()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use murmur3::murmur3_32::MurmurHasher;
+    use std::sync::Arc;
+
+    #[test]
+    fn optimal_constructor() {
+        let bf: AtomicBloomFilter<&str, ReHasher<MurmurHasher>> =
+            AtomicBloomFilter::optimal_new(1000, 0.01);
+        assert_eq!(bf.num_bits(), 9586);
+        assert_eq!(bf.k(), 7)
+    }
+
+    #[test]
+    fn serializes_like_bloom_filter() {
+        let bf: AtomicBloomFilter<u32, ReHasher<MurmurHasher>> =
+            AtomicBloomFilter::new(1001, ReHasher::new(3));
+        let mut expected = BloomFilter::<u32, ReHasher<MurmurHasher>>::new(1001, ReHasher::new(3));
+        (0..100).for_each(|n| {
+            bf.insert(&n);
+            expected.insert(&n);
+        });
+        assert_eq!(bf.to_bytes(), expected.to_bytes());
+        assert_eq!(bf.count_ones(), expected.count_ones());
+        let bf = AtomicBloomFilter::from_snapshot(expected.clone());
+        assert_eq!(bf.snapshot().to_bytes(), expected.to_bytes());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn concurrent_inserts() {
+        let bf: Arc<AtomicBloomFilter<i32, ReHasher<MurmurHasher>>> =
+            Arc::new(AtomicBloomFilter::optimal_new(4000, 0.001));
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let bf = bf.clone();
+                std::thread::spawn(move || (t * 1000..(t + 1) * 1000).for_each(|n| bf.insert(&n)))
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        (0..4000).for_each(|n| assert!(bf.contains(&n)));
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    #[test]
+    fn insert_happens_before_contains() {
+        loom::model(|| {
+            let bf: Arc<AtomicBloomFilter<&str, ReHasher<MurmurHasher>>> =
+                Arc::new(AtomicBloomFilter::new(64, ReHasher::new(2)));
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"a"))
+            };
+            writer.join().unwrap();
+            assert!(bf.contains(&"a"));
+        });
+    }
+
+    #[test]
+    fn concurrent_inserts_into_the_same_word() {
+        loom::model(|| {
+            let bf: Arc<AtomicBloomFilter<&str, ReHasher<MurmurHasher>>> =
+                Arc::new(AtomicBloomFilter::new(32, ReHasher::new(2)));
+            let writers: Vec<_> = ["a", "b"]
+                .iter()
+                .map(|value| {
+                    let bf = bf.clone();
+                    thread::spawn(move || bf.insert(value))
+                })
+                .collect();
+            writers.into_iter().for_each(|w| w.join().unwrap());
+            assert!(bf.contains(&"a"));
+            assert!(bf.contains(&"b"));
+        });
+    }
+}
@@ -0,0 +1,513 @@
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use crate::rehasher::ReHasher;
+use crate::sync::AtomicU8;
+use crate::sync::AtomicUsize;
+use crate::sync::Ordering;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+/// A counting bloom filter that can be shared across threads, which keeps an atomic counter per slot
+/// instead of a single bit, allowing elements to be removed.
+///
+/// # Notes
+/// No locks are taken, every slot is updated on its own with atomic read-modify-write operations.
+/// The memory ordering contract is:
+/// 1. If an `insert` of a value happens-before a `contains` of that value, the `contains` will return true.
+/// 2. Slot updates are `Release`, and the slot loads made by `contains` are `Acquire`.
+///    A `contains` that returns true has synchronized with the inserts that set its slots,
+///    so anything written by those threads before inserting is visible after it returns.
+/// 3. An `insert` that runs concurrently with a `contains` of the same value may have only some of its
+///    slots observed, so the `contains` may return false until the `insert` returns.
+/// 4. A `remove` that happens-before a `contains` will have all of its decrements observed by it.
+/// 5. The element count is only an approximation while operations are in flight,
+///    because it is updated separately from the slots. Once every `insert`/`remove` has returned,
+///    it is exact.
+///
+/// # Warning
+/// Each slot saturates at `u8::MAX`.
+/// A saturated slot is never decremented, because how many elements hash to it is no longer known.
+/// This keeps `remove` from introducing false negatives, at the cost of that slot never being freed.
+///
+/// Removing a value that was never inserted will decrement slots belonging to other values,
+/// which can cause false negatives for them. Only remove values that are known to be present.
+#[derive(Debug)]
+pub struct AtomicCountingBloomFilter<T, K> {
+    /// The backing counters, one per slot.
+    counters: Vec<AtomicU8>,
+    /// The counter that keeps track of the number of elements inserted and not yet removed.
+    count: AtomicUsize,
+    /// The type information of what the filter will accept as input.
+    type_info: PhantomData<T>,
+    /// The generic hashing structure.
+    pub(crate) k: K,
+}
+
+impl<T, H> AtomicCountingBloomFilter<T, ReHasher<H>> {
+    /// Constructs a new AtomicCountingBloomFilter with an optimal ratio of m and k,
+    /// derived from n and p inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001);
+    /// ```
+    pub fn optimal_new(n: usize, p: f64) -> Self {
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new AtomicCountingBloomFilter with an optimal ratio of m and k, derived from n and p inputs,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(0, 0.001);
+    /// assert_eq!(bf.err(), Some(BloomError::NoElements));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        let k = crate::optimal_k(n, m)?;
+        Ok(Self::from_parts(m, ReHasher::new(k)))
+    }
+}
+
+impl<T, H> AtomicCountingBloomFilter<T, H>
+where
+    H: HashToIndices + K,
+{
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    /// * `hashers` - Hashing to indicies struct. `k` can be acquired from this.
+    ///
+    /// # Panics
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(1));
+    /// ```
+    pub fn with_rate(n: usize, p: f64, hashers: H) -> Self {
+        Self::try_with_rate(n, p, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_rate(10000, 1.5, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::InvalidFalsePositiveRate(1.5)));
+    /// ```
+    pub fn try_with_rate(n: usize, p: f64, hashers: H) -> Result<Self, BloomError> {
+        let m = crate::m_from_knp(hashers.k(), n, p)?;
+        Ok(Self::from_parts(m, hashers))
+    }
+}
+
+impl<T, K> AtomicCountingBloomFilter<T, K> {
+    fn from_parts(m: usize, hashers: K) -> Self {
+        AtomicCountingBloomFilter {
+            counters: (0..m).map(|_| AtomicU8::new(0)).collect(),
+            count: AtomicUsize::new(0),
+            type_info: PhantomData,
+            k: hashers,
+        }
+    }
+}
+
+impl<T, K> AtomicCountingBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+{
+    /// Creates the bloom filter with a given number of slots
+    /// and with a multiple-hashing-to-index function.
+    ///
+    /// # Arguments
+    /// * `m` - Number of slots for the filter. Each slot takes up a byte.
+    /// * `hashers` - Hashing to indices structure.
+    ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// ```
+    pub fn new(m: usize, hashers: K) -> Self {
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::error::check_m(m)?;
+        crate::error::check_k(hashers.k())?;
+        Ok(Self::from_parts(m, hashers))
+    }
+
+    /// Gets the number of slots used in the bloom filter.
+    pub fn num_bits(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Takes multiple hashes of the provided value, takes the hashes modulo the number of slots
+    /// (converting them to indexes) and increments the counters in those slots.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// ```
+    pub fn insert(&self, value: &T) {
+        self.k
+            .hash_to_indices(value, self.num_bits())
+            .into_iter()
+            .for_each(|i| {
+                // Saturated counters stay saturated, so the failed update is ignored.
+                let _ = self.counters[i]
+                    .fetch_update(Ordering::Release, Ordering::Relaxed, |c| c.checked_add(1));
+            });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Removes a previously inserted value from the filter by decrementing the counters in its slots.
+    ///
+    /// # Warning
+    /// Removing a value that was never inserted can cause false negatives for other values.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(3));
+    /// bf.insert(&"hello");
+    /// assert!(bf.contains(&"hello"));
+    /// bf.remove(&"hello");
+    /// assert!(!bf.contains(&"hello"));
+    /// ```
+    pub fn remove(&self, value: &T) {
+        self.k
+            .hash_to_indices(value, self.num_bits())
+            .into_iter()
+            .for_each(|i| {
+                // Saturated and empty counters are left alone.
+                let _ =
+                    self.counters[i].fetch_update(
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        |c| match c {
+                            0 | u8::MAX => None,
+                            c => Some(c - 1),
+                        },
+                    );
+            });
+        // The count is never allowed to wrap if a value that wasn't inserted is removed.
+        let _ = self
+            .count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |c| c.checked_sub(1));
+    }
+
+    /// Tests to see if the provided value is in the bloom filter.
+    /// This will return false positives if the slots that are the result of hashing the value are already occupied.
+    /// Likelihood of false positives will increase as the filter fills up.
+    /// This can be mitigated by allocating more slots to the bloom filter, and by increasing the number of hash functions used ('k').
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///   These indices will be used to see if the element has been added.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"there");
+    /// assert!(bf.contains(&"hello"));
+    /// assert!(bf.contains(&"there"));
+    /// assert!(!bf.contains(&"not here"));
+    /// ```
+    pub fn contains(&self, value: &T) -> bool {
+        self.k
+            .hash_to_indices(value, self.num_bits())
+            .into_iter()
+            .all(|i| self.counters[i].load(Ordering::Acquire) > 0)
+    }
+
+    /// Gets the number of elements that have been inserted and not removed.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of slots that are occupied.
+    /// Slots are read one at a time, so concurrent inserts and removes may be partially counted.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.count_ones(), 1);
+    /// bf.remove(&"hello");
+    /// bf.remove(&"hello");
+    /// assert!(bf.is_empty());
+    /// ```
+    pub fn count_ones(&self) -> usize {
+        self.counters
+            .iter()
+            .filter(|c| c.load(Ordering::Relaxed) > 0)
+            .count()
+    }
+
+    /// Gets the fraction of slots that are occupied.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.num_bits() as f64
+    }
+
+    /// Checks if no slots are occupied.
+    pub fn is_empty(&self) -> bool {
+        self.counters.iter().all(|c| c.load(Ordering::Relaxed) == 0)
+    }
+
+    /// Checks if at least `threshold` of the slots are occupied.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.fill_ratio() >= threshold
+    }
+
+    /// Zeroes every counter and the count in place, so the filter can be reused without replacing it wherever it is shared.
+    ///
+    /// # Notes
+    /// Counters are zeroed one at a time, so an insert that runs at the same time may be partially cleared,
+    /// and a later `remove` of that value can leave other values' counters one short.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// assert_eq!(bf.count(), 0);
+    /// ```
+    pub fn clear(&self) {
+        self.counters
+            .iter()
+            .for_each(|c| c.store(0, Ordering::Release));
+        self.count.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of slots that are occupied rather than from the count like `false_positive_chance`.
+    pub fn current_false_positive_rate(&self) -> f64 {
+        crate::sizing::false_positive_rate_from_fill(self.fill_ratio(), self.k.k())
+    }
+
+    /// Returns the current chance that any given lookup will return a false positive.
+    ///
+    /// # Note
+    /// The accuracy of the false positive chance is correlated with how evenly distributed the chosen hashing method is.
+    /// As it stands, most users will choose a fast hashing method that may not necessarily have a perfectly distributed hash output.
+    /// Because of this, the actual incidence of false positives may be higher than indicated here.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::AtomicCountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = AtomicCountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// assert_eq!(bf.false_positive_chance(), 0.0);
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.false_positive_chance(), 0.009950166250831893);
+    /// ```
+    pub fn false_positive_chance(&self) -> f64 {
+        use crate::false_positive_rate as fpr;
+        fpr(self.k.k(), self.count(), self.num_bits())
+    }
+}
+
+impl<T, U: K> K for AtomicCountingBloomFilter<T, U> {
+    fn k(&self) -> usize {
+        self.k.k()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::hash_numbers::One;
+    use murmur3::murmur3_32::MurmurHasher;
+    use std::sync::Arc;
+
+    #[test]
+    fn optimal_constructor() {
+        let bf: AtomicCountingBloomFilter<&str, ReHasher<MurmurHasher>> =
+            AtomicCountingBloomFilter::optimal_new(1000, 0.01);
+        assert_eq!(bf.num_bits(), 9586);
+        assert_eq!(bf.k(), 7)
+    }
+
+    #[test]
+    fn remove_keeps_colliding_values() {
+        let bf: AtomicCountingBloomFilter<&str, One<MurmurHasher>> =
+            AtomicCountingBloomFilter::new(5, One::default());
+        bf.insert(&"a");
+        bf.insert(&"l");
+        bf.remove(&"a");
+        assert!(
+            bf.contains(&"l"),
+            "With a murmur hasher, a and l should resolve to the same index"
+        );
+        bf.remove(&"l");
+        assert!(!bf.contains(&"l"));
+        assert_eq!(bf.count(), 0);
+    }
+
+    #[test]
+    fn saturated_slots_are_not_removed() {
+        let bf: AtomicCountingBloomFilter<&str, One<MurmurHasher>> =
+            AtomicCountingBloomFilter::new(5, One::default());
+        (0..300).for_each(|_| bf.insert(&"a"));
+        (0..300).for_each(|_| bf.remove(&"a"));
+        assert!(bf.contains(&"a"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn concurrent_inserts_and_removes() {
+        let bf: Arc<AtomicCountingBloomFilter<i32, ReHasher<MurmurHasher>>> =
+            Arc::new(AtomicCountingBloomFilter::optimal_new(4000, 0.001));
+        (0..2000).for_each(|n| bf.insert(&n));
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let bf = bf.clone();
+                std::thread::spawn(move || {
+                    (2000 + t * 500..2000 + (t + 1) * 500).for_each(|n| bf.insert(&n));
+                    (t * 500..(t + 1) * 500).for_each(|n| bf.remove(&n));
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(bf.count(), 2000);
+        (2000..4000).for_each(|n| assert!(bf.contains(&n)));
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use crate::hash_numbers::One;
+    use loom::sync::Arc;
+    use loom::thread;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    #[test]
+    fn insert_happens_before_contains() {
+        loom::model(|| {
+            let bf: Arc<AtomicCountingBloomFilter<&str, ReHasher<MurmurHasher>>> =
+                Arc::new(AtomicCountingBloomFilter::new(4, ReHasher::new(2)));
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"a"))
+            };
+            writer.join().unwrap();
+            assert!(bf.contains(&"a"));
+        });
+    }
+
+    #[test]
+    fn concurrent_insert_and_remove_of_colliding_values() {
+        loom::model(|| {
+            let bf: Arc<AtomicCountingBloomFilter<&str, One<MurmurHasher>>> =
+                Arc::new(AtomicCountingBloomFilter::new(5, One::default()));
+            bf.insert(&"a");
+            let inserter = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"l"))
+            };
+            let remover = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.remove(&"a"))
+            };
+            inserter.join().unwrap();
+            remover.join().unwrap();
+            assert!(bf.contains(&"l"));
+            assert_eq!(bf.count(), 1);
+        });
+    }
+}
@@ -0,0 +1,838 @@
+//! A server that speaks the Redis protocol (RESP), and implements the `BF.*` commands of RedisBloom
+//! on named filters, so they can be used with `redis-cli` and Redis client libraries.
+//!
+//! ```text
+//! bloom-server --addr 127.0.0.1:6379 --dir filters --save-interval 60
+//! redis-cli BF.RESERVE seen 0.001 1000000
+//! redis-cli BF.ADD seen alice
+//! redis-cli BF.EXISTS seen alice
+//! ```
+//!
+//! The commands are `BF.RESERVE`, `BF.ADD`, `BF.MADD`, `BF.EXISTS`, `BF.MEXISTS` and `BF.INFO`, along with
+//! `PING` and `SAVE`. Unlike RedisBloom's, filters don't grow once they are full, so `BF.RESERVE` only
+//! accepts the `NONSCALING` option, and `BF.ADD` creates a missing filter sized for 100 items with a false
+//! positive rate of 0.01, like RedisBloom does.
+//!
+//! Items are hashed by `ReHasher<MurmurHasher>` like `&[u8]` and `Vec<u8>` are, like in the C API.
+//! With `--dir`, every filter is kept in the directory as a file named by the hex of its key,
+//! in the format of `CountingBloomFilter::to_bytes`. Filters are loaded from it on startup,
+//! and the ones that changed are written back on `SAVE`, every `--save-interval` seconds, and on Ctrl-C.
+//! The capacity a filter was reserved with isn't part of the format, so `BF.INFO` reports it as nil for
+//! filters loaded from the directory.
+//!
+//! A filter that would need more than `--max-filter-bytes` bytes is refused with an error, rather than
+//! letting a client make the server allocate as much as it asks for.
+
+use bloom_filter::sizing::bytes_needed;
+use bloom_filter::BloomError;
+use bloom_filter::CountingBloomFilter;
+use bloom_filter::ReHasher;
+use clap::Parser;
+use murmur3::murmur3_32::MurmurHasher;
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+
+type Filter = CountingBloomFilter<Vec<u8>, ReHasher<MurmurHasher>>;
+
+/// The size of the filter `BF.ADD` and `BF.MADD` create, like RedisBloom's default.
+const DEFAULT_CAPACITY: usize = 100;
+/// The false positive rate of the filter `BF.ADD` and `BF.MADD` create, like RedisBloom's default.
+const DEFAULT_ERROR_RATE: f64 = 0.01;
+/// The longest line of a request, which holds at most a length, or an inline command.
+const MAX_LINE: u64 = 64 * 1024;
+/// The most arguments a command may have.
+const MAX_ARGS: usize = 1024 * 1024;
+/// The longest argument, like Redis' default `proto-max-bulk-len`.
+const MAX_BULK: usize = 512 * 1024 * 1024;
+/// The default for the most bytes a filter may need.
+const DEFAULT_MAX_FILTER_BYTES: usize = 512 * 1024 * 1024;
+
+#[derive(Parser)]
+#[command(
+    name = "bloom-server",
+    about = "Serves bloom filters over the Redis protocol"
+)]
+struct Cli {
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:6379")]
+    addr: SocketAddr,
+    /// The directory filters are loaded from and saved to. Without it, filters are only kept in memory
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    /// Saves the filters that changed every this many seconds, as well as on SAVE and on Ctrl-C
+    #[arg(long, requires = "dir")]
+    save_interval: Option<u64>,
+    /// The most bytes a filter created by BF.RESERVE or BF.ADD may need
+    #[arg(long, default_value_t = DEFAULT_MAX_FILTER_BYTES)]
+    max_filter_bytes: usize,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("bloom-server: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let Cli {
+        addr,
+        dir,
+        save_interval,
+        max_filter_bytes,
+    } = cli;
+    let server = Arc::new(Server::open(dir, max_filter_bytes)?);
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = TcpListener::bind(addr).await?;
+        eprintln!("bloom-server: listening on {}", listener.local_addr()?);
+        tokio::spawn(server.clone().serve(listener));
+        if let Some(seconds) = save_interval {
+            let server = server.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(seconds));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = server.save().await {
+                        eprintln!("bloom-server: saving failed: {}", e);
+                    }
+                }
+            });
+        }
+        tokio::signal::ctrl_c().await?;
+        server.save().await?;
+        Ok(())
+    })
+}
+
+/// A reply to a command.
+#[derive(Debug, Clone, PartialEq)]
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Nil,
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    fn error(message: impl Into<String>) -> Reply {
+        Reply::Error(message.into())
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Reply::Status(status) => out.extend_from_slice(format!("+{}\r\n", status).as_bytes()),
+            Reply::Error(message) => out.extend_from_slice(format!("-{}\r\n", message).as_bytes()),
+            Reply::Integer(n) => out.extend_from_slice(format!(":{}\r\n", n).as_bytes()),
+            Reply::Bulk(bytes) => {
+                out.extend_from_slice(format!("${}\r\n", bytes.len()).as_bytes());
+                out.extend_from_slice(bytes);
+                out.extend_from_slice(b"\r\n");
+            }
+            Reply::Nil => out.extend_from_slice(b"$-1\r\n"),
+            Reply::Array(replies) => {
+                out.extend_from_slice(format!("*{}\r\n", replies.len()).as_bytes());
+                replies.iter().for_each(|reply| reply.encode(out));
+            }
+        }
+    }
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Protocol error: {}", message),
+    )
+}
+
+/// Reads a line, without its `\r\n`, or `None` if the reader has ended.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    reader.take(MAX_LINE).read_until(b'\n', &mut line).await?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if !line.ends_with(b"\r\n") {
+        return Err(protocol_error("a line is too long, or doesn't end in CRLF"));
+    }
+    line.truncate(line.len() - 2);
+    Ok(Some(line))
+}
+
+/// Parses the length after the type byte of a line.
+fn parse_len(line: &[u8], max: usize) -> io::Result<usize> {
+    std::str::from_utf8(&line[1..])
+        .ok()
+        .and_then(|len| len.parse().ok())
+        .filter(|&len| len <= max)
+        .ok_or_else(|| protocol_error("invalid length"))
+}
+
+/// Reads a command, as an array of bulk strings like clients send, or inline like typed into telnet,
+/// or `None` if the reader has ended.
+async fn read_command<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<Vec<u8>>>> {
+    let line = match read_line(reader).await? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    if line.first() != Some(&b'*') {
+        let args = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect();
+        return Ok(Some(args));
+    }
+    let n = parse_len(&line, MAX_ARGS)?;
+    let mut args = Vec::with_capacity(n.min(1024));
+    for _ in 0..n {
+        let header = read_line(reader)
+            .await?
+            .ok_or_else(|| protocol_error("the command was cut short"))?;
+        if header.first() != Some(&b'$') {
+            return Err(protocol_error("expected a bulk string"));
+        }
+        let len = parse_len(&header, MAX_BULK)?;
+        let mut arg = Vec::new();
+        reader.take(len as u64 + 2).read_to_end(&mut arg).await?;
+        if arg.len() != len + 2 || !arg.ends_with(b"\r\n") {
+            return Err(protocol_error("a bulk string was cut short"));
+        }
+        arg.truncate(len);
+        args.push(arg);
+    }
+    Ok(Some(args))
+}
+
+/// A named filter.
+struct Entry {
+    filter: Filter,
+    /// The number of items the filter was reserved for, if it was reserved since the server started.
+    capacity: Option<usize>,
+    /// The number of times the filter has changed, so a save knows whether it is up to date.
+    changes: u64,
+    /// The number of changes that have been written to the directory.
+    saved: u64,
+}
+
+impl Entry {
+    /// Creates a filter for `capacity` items, unless it would need more than `max_bytes` bytes.
+    fn new(capacity: usize, error_rate: f64, max_bytes: usize) -> Result<Entry, String> {
+        let error = |e: BloomError| format!("ERR {}", e);
+        let needed = bytes_needed(bloom_filter::optimal_m(capacity, error_rate).map_err(error)?);
+        if needed > max_bytes {
+            return Err(error(BloomError::ExceedsMaxBytes {
+                needed,
+                max: max_bytes,
+            }));
+        }
+        let filter = Filter::try_optimal_new(capacity, error_rate).map_err(error)?;
+        Ok(Entry {
+            filter,
+            capacity: Some(capacity),
+            changes: 1,
+            saved: 0,
+        })
+    }
+
+    /// Adds an item, returning whether the filter didn't already contain it.
+    /// Only new items are inserted, so the filter's count is the number of items that were added.
+    fn add(&mut self, item: &Vec<u8>) -> bool {
+        let new = !self.filter.contains(item);
+        if new {
+            self.filter.insert(item);
+            self.changes += 1;
+        }
+        new
+    }
+}
+
+struct Server {
+    filters: Mutex<HashMap<Vec<u8>, Entry>>,
+    dir: Option<PathBuf>,
+    /// Held while saving, so the `--save-interval` task, `SAVE` and Ctrl-C don't write the same
+    /// temporary files at once, or move an older save over a newer one.
+    saving: tokio::sync::Mutex<()>,
+    /// The most bytes a filter created by a command may need.
+    max_filter_bytes: usize,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn filter_path(dir: &Path, key: &[u8]) -> PathBuf {
+    dir.join(format!("{}.bf", hex(key)))
+}
+
+/// Reads a filter written by `Filter::to_bytes`.
+fn read_filter(bytes: &[u8]) -> Result<Filter, Box<dyn Error>> {
+    let k = bloom_filter::format::hashes(bytes)?;
+    Ok(Filter::from_bytes(bytes, ReHasher::new(k))?)
+}
+
+/// Gets the integer reply for a bool.
+fn flag(b: bool) -> Reply {
+    Reply::Integer(b as i64)
+}
+
+impl Server {
+    /// Creates a server, loading the filters in `dir`, which is created if it doesn't exist.
+    /// Loaded filters aren't limited to `max_filter_bytes`, only the ones created by commands.
+    fn open(dir: Option<PathBuf>, max_filter_bytes: usize) -> Result<Server, Box<dyn Error>> {
+        let mut filters = HashMap::new();
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir)?;
+            for file in std::fs::read_dir(dir)? {
+                let path = file?.path();
+                let key = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".bf"))
+                    .and_then(unhex);
+                if let Some(key) = key {
+                    let filter = read_filter(&std::fs::read(&path)?)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    let entry = Entry {
+                        filter,
+                        capacity: None,
+                        changes: 0,
+                        saved: 0,
+                    };
+                    filters.insert(key, entry);
+                }
+            }
+        }
+        Ok(Server {
+            filters: Mutex::new(filters),
+            dir,
+            saving: tokio::sync::Mutex::new(()),
+            max_filter_bytes,
+        })
+    }
+
+    /// Locks the filters. Nothing panics partway through changing a filter,
+    /// so the filters behind a poisoned lock are still usable.
+    fn filters(&self) -> MutexGuard<'_, HashMap<Vec<u8>, Entry>> {
+        self.filters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Accepts connections until the listener fails, serving each on its own task.
+    async fn serve(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let server = self.clone();
+                    tokio::spawn(async move {
+                        // The connection is dropped on any error, and the client will see it closed.
+                        let _ = server.serve_connection(stream).await;
+                    });
+                }
+                Err(e) => eprintln!("bloom-server: accepting a connection failed: {}", e),
+            }
+        }
+    }
+
+    /// Answers the commands sent on a connection until it is closed.
+    async fn serve_connection(&self, mut stream: TcpStream) -> io::Result<()> {
+        let (read, mut write) = stream.split();
+        let mut reader = BufReader::new(read);
+        loop {
+            let reply = match read_command(&mut reader).await {
+                Ok(Some(args)) if args.is_empty() => continue,
+                Ok(Some(args)) => self.execute(&args).await,
+                Ok(None) => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    // Like Redis, the error is replied to before the connection is closed.
+                    let mut out = Vec::new();
+                    Reply::error(format!("ERR {}", e)).encode(&mut out);
+                    write.write_all(&out).await?;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            };
+            let mut out = Vec::new();
+            reply.encode(&mut out);
+            write.write_all(&out).await?;
+        }
+    }
+
+    /// Runs a command, which has at least its name.
+    async fn execute(&self, args: &[Vec<u8>]) -> Reply {
+        let name = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+        let args = &args[1..];
+        let arity = |ok: bool| -> Result<(), Reply> {
+            if ok {
+                Ok(())
+            } else {
+                Err(Reply::error(format!(
+                    "ERR wrong number of arguments for '{}' command",
+                    name.to_ascii_lowercase()
+                )))
+            }
+        };
+        let reply = match name.as_str() {
+            "PING" => arity(args.len() <= 1).map(|_| match args.first() {
+                Some(message) => Reply::Bulk(message.clone()),
+                None => Reply::Status("PONG"),
+            }),
+            "SAVE" => match arity(args.is_empty()) {
+                Ok(()) => match self.save().await {
+                    Ok(_) => Ok(Reply::Status("OK")),
+                    Err(e) => Ok(Reply::error(format!("ERR {}", e))),
+                },
+                Err(reply) => Err(reply),
+            },
+            "BF.RESERVE" => arity(args.len() >= 3).map(|_| self.reserve(args)),
+            "BF.ADD" => arity(args.len() == 2).map(|_| self.add(&args[0], &args[1..], false)),
+            "BF.MADD" => arity(args.len() >= 2).map(|_| self.add(&args[0], &args[1..], true)),
+            "BF.EXISTS" => arity(args.len() == 2).map(|_| self.exists(&args[0], &args[1..], false)),
+            "BF.MEXISTS" => arity(args.len() >= 2).map(|_| self.exists(&args[0], &args[1..], true)),
+            "BF.INFO" => arity(args.len() == 1).map(|_| self.info(&args[0])),
+            _ => Err(Reply::error(format!("ERR unknown command '{}'", name))),
+        };
+        reply.unwrap_or_else(|error| error)
+    }
+
+    /// `BF.RESERVE key error_rate capacity [NONSCALING]`
+    fn reserve(&self, args: &[Vec<u8>]) -> Reply {
+        let error_rate = std::str::from_utf8(&args[1])
+            .ok()
+            .and_then(|p| p.parse::<f64>().ok())
+            .filter(|p| *p > 0.0 && *p < 1.0);
+        let error_rate = match error_rate {
+            Some(p) => p,
+            None => return Reply::error("ERR (0 < error rate range < 1)"),
+        };
+        let capacity = std::str::from_utf8(&args[2])
+            .ok()
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0);
+        let capacity = match capacity {
+            Some(n) => n,
+            None => return Reply::error("ERR (capacity should be larger than 0)"),
+        };
+        for option in &args[3..] {
+            match option.to_ascii_uppercase().as_slice() {
+                b"NONSCALING" => {}
+                b"EXPANSION" => {
+                    return Reply::error("ERR filters don't scale, so EXPANSION isn't supported")
+                }
+                _ => return Reply::error("ERR syntax error"),
+            }
+        }
+        if self.filters().contains_key(&args[0]) {
+            return Reply::error("ERR item exists");
+        }
+        // The filter is allocated without holding the lock, so other commands aren't held up by it,
+        // and it is checked again that no other client reserved the key meanwhile.
+        let entry = match Entry::new(capacity, error_rate, self.max_filter_bytes) {
+            Ok(entry) => entry,
+            Err(message) => return Reply::Error(message),
+        };
+        match self.filters().entry(args[0].clone()) {
+            hash_map::Entry::Occupied(_) => Reply::error("ERR item exists"),
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+                Reply::Status("OK")
+            }
+        }
+    }
+
+    /// `BF.ADD key item` and `BF.MADD key item [item ...]`, which create the filter if it doesn't exist.
+    fn add(&self, key: &[u8], items: &[Vec<u8>], multi: bool) -> Reply {
+        let mut filters = self.filters();
+        if !filters.contains_key(key) {
+            // The default filter is small enough to allocate while holding the lock.
+            match Entry::new(DEFAULT_CAPACITY, DEFAULT_ERROR_RATE, self.max_filter_bytes) {
+                Ok(entry) => filters.insert(key.to_vec(), entry),
+                Err(message) => return Reply::Error(message),
+            };
+        }
+        let entry = filters.get_mut(key).expect("the filter was just created");
+        let mut added = items.iter().map(|item| flag(entry.add(item)));
+        if multi {
+            Reply::Array(added.collect())
+        } else {
+            added.next().expect("BF.ADD has one item")
+        }
+    }
+
+    /// `BF.EXISTS key item` and `BF.MEXISTS key item [item ...]`. Nothing exists in a missing filter.
+    fn exists(&self, key: &[u8], items: &[Vec<u8>], multi: bool) -> Reply {
+        let filters = self.filters();
+        let filter = filters.get(key).map(|entry| &entry.filter);
+        let mut found = items
+            .iter()
+            .map(|item| flag(filter.is_some_and(|filter| filter.contains(item))));
+        if multi {
+            Reply::Array(found.collect())
+        } else {
+            found.next().expect("BF.EXISTS has one item")
+        }
+    }
+
+    /// `BF.INFO key`
+    fn info(&self, key: &[u8]) -> Reply {
+        let filters = self.filters();
+        let entry = match filters.get(key) {
+            Some(entry) => entry,
+            None => return Reply::error("ERR not found"),
+        };
+        let capacity = entry
+            .capacity
+            .map(|n| Reply::Integer(n as i64))
+            .unwrap_or(Reply::Nil);
+        let field = |name: &str| Reply::Bulk(name.as_bytes().to_vec());
+        Reply::Array(vec![
+            field("Capacity"),
+            capacity,
+            field("Size"),
+            Reply::Integer(entry.filter.num_bits().div_ceil(8) as i64),
+            field("Number of filters"),
+            Reply::Integer(1),
+            field("Number of items inserted"),
+            Reply::Integer(entry.filter.count() as i64),
+            field("Expansion rate"),
+            Reply::Nil,
+        ])
+    }
+
+    /// Writes the filters that changed since they were last saved to the directory, if there is one,
+    /// and returns how many were written.
+    /// Each is written to a temporary file first, and moved over the old one, so a crash can't leave it partly written.
+    async fn save(&self) -> io::Result<usize> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => {
+                return Err(io::Error::other(
+                    "persistence isn't enabled, start the server with --dir",
+                ))
+            }
+        };
+        let _saving = self.saving.lock().await;
+        // The bytes are copied out, so the lock isn't held while they are written.
+        let changed: Vec<(Vec<u8>, u64, Vec<u8>)> = self
+            .filters()
+            .iter()
+            .filter(|(_, entry)| entry.changes != entry.saved)
+            .map(|(key, entry)| (key.clone(), entry.changes, entry.filter.to_bytes()))
+            .collect();
+        for (key, changes, bytes) in &changed {
+            let path = filter_path(dir, key);
+            let temporary = path.with_extension("bf.tmp");
+            tokio::fs::write(&temporary, bytes).await?;
+            tokio::fs::rename(&temporary, &path).await?;
+            if let Some(entry) = self.filters().get_mut(key) {
+                entry.saved = entry.saved.max(*changes);
+            }
+        }
+        Ok(changed.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::io::Read;
+    use std::io::Write;
+
+    /// Starts a server on a localhost port, on a runtime that is dropped along with it.
+    fn start(dir: Option<PathBuf>) -> (tokio::runtime::Runtime, SocketAddr) {
+        start_limited(dir, DEFAULT_MAX_FILTER_BYTES)
+    }
+
+    /// Starts a server whose filters may need at most `max_filter_bytes` bytes.
+    fn start_limited(
+        dir: Option<PathBuf>,
+        max_filter_bytes: usize,
+    ) -> (tokio::runtime::Runtime, SocketAddr) {
+        let server = Arc::new(Server::open(dir, max_filter_bytes).unwrap());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+        runtime.spawn(server.serve(listener));
+        (runtime, addr)
+    }
+
+    /// A minimal RESP client.
+    struct Client(std::io::BufReader<std::net::TcpStream>);
+
+    impl Client {
+        fn connect(addr: SocketAddr) -> Client {
+            Client(std::io::BufReader::new(
+                std::net::TcpStream::connect(addr).unwrap(),
+            ))
+        }
+
+        fn call(&mut self, args: &[&str]) -> Reply {
+            let mut request = format!("*{}\r\n", args.len());
+            for arg in args {
+                request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+            }
+            self.send(request.as_bytes())
+        }
+
+        fn send(&mut self, bytes: &[u8]) -> Reply {
+            self.0.get_mut().write_all(bytes).unwrap();
+            self.read_reply()
+        }
+
+        fn read_reply(&mut self) -> Reply {
+            let mut line = String::new();
+            self.0.read_line(&mut line).unwrap();
+            let (kind, rest) = line.trim_end().split_at(1);
+            match kind {
+                "+" => Reply::Status(Box::leak(rest.to_string().into_boxed_str())),
+                "-" => Reply::Error(rest.to_string()),
+                ":" => Reply::Integer(rest.parse().unwrap()),
+                "$" if rest == "-1" => Reply::Nil,
+                "$" => {
+                    let mut bytes = vec![0; rest.parse::<usize>().unwrap() + 2];
+                    self.0.read_exact(&mut bytes).unwrap();
+                    bytes.truncate(bytes.len() - 2);
+                    Reply::Bulk(bytes)
+                }
+                "*" => Reply::Array(
+                    (0..rest.parse().unwrap())
+                        .map(|_| self.read_reply())
+                        .collect(),
+                ),
+                _ => panic!("unexpected reply {:?}", line),
+            }
+        }
+    }
+
+    fn ints(ns: &[i64]) -> Reply {
+        Reply::Array(ns.iter().map(|&n| Reply::Integer(n)).collect())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bloom-server-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn bf_commands() {
+        let (_runtime, addr) = start(None);
+        let mut client = Client::connect(addr);
+        assert_eq!(
+            client.call(&["BF.RESERVE", "seen", "0.001", "1000"]),
+            Reply::Status("OK")
+        );
+        assert_eq!(
+            client.call(&["bf.reserve", "seen", "0.001", "1000"]),
+            Reply::error("ERR item exists")
+        );
+        assert_eq!(client.call(&["BF.ADD", "seen", "alice"]), Reply::Integer(1));
+        assert_eq!(client.call(&["BF.ADD", "seen", "alice"]), Reply::Integer(0));
+        assert_eq!(
+            client.call(&["BF.MADD", "seen", "bob", "alice", "carol"]),
+            ints(&[1, 0, 1])
+        );
+        assert_eq!(
+            client.call(&["BF.EXISTS", "seen", "bob"]),
+            Reply::Integer(1)
+        );
+        assert_eq!(
+            client.call(&["BF.MEXISTS", "seen", "carol", "dave"]),
+            ints(&[1, 0])
+        );
+        assert_eq!(
+            client.call(&["BF.EXISTS", "missing", "bob"]),
+            Reply::Integer(0)
+        );
+        match client.call(&["BF.INFO", "seen"]) {
+            Reply::Array(fields) => {
+                assert_eq!(fields[1], Reply::Integer(1000));
+                assert_eq!(fields[7], Reply::Integer(3));
+            }
+            reply => panic!("{:?}", reply),
+        }
+        assert_eq!(
+            client.call(&["BF.INFO", "missing"]),
+            Reply::error("ERR not found")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (_runtime, addr) = start(None);
+        let mut client = Client::connect(addr);
+        assert_eq!(
+            client.call(&["BF.ADD", "seen"]),
+            Reply::error("ERR wrong number of arguments for 'bf.add' command")
+        );
+        assert_eq!(
+            client.call(&["BF.RESERVE", "seen", "1.5", "100"]),
+            Reply::error("ERR (0 < error rate range < 1)")
+        );
+        assert_eq!(
+            client.call(&["BF.RESERVE", "seen", "0.01", "100", "EXPANSION", "2"]),
+            Reply::error("ERR filters don't scale, so EXPANSION isn't supported")
+        );
+        assert_eq!(
+            client.call(&["SET", "a", "b"]),
+            Reply::error("ERR unknown command 'SET'")
+        );
+        assert_eq!(
+            client.call(&["SAVE"]),
+            Reply::error("ERR persistence isn't enabled, start the server with --dir")
+        );
+        // Malformed requests are answered with an error, and the connection is closed.
+        match client.send(b"*1\r\n+PING\r\n") {
+            Reply::Error(message) => assert!(message.starts_with("ERR Protocol error")),
+            reply => panic!("{:?}", reply),
+        }
+    }
+
+    #[test]
+    fn add_creates_a_default_filter() {
+        let (_runtime, addr) = start(None);
+        let mut client = Client::connect(addr);
+        assert_eq!(client.call(&["BF.ADD", "new", "x"]), Reply::Integer(1));
+        match client.call(&["BF.INFO", "new"]) {
+            Reply::Array(fields) => assert_eq!(fields[1], Reply::Integer(100)),
+            reply => panic!("{:?}", reply),
+        }
+    }
+
+    #[test]
+    fn large_filters_are_refused() {
+        let (_runtime, addr) = start_limited(None, 1024);
+        let mut client = Client::connect(addr);
+        assert_eq!(
+            client.call(&["BF.RESERVE", "big", "0.01", "1000"]),
+            Reply::error("ERR the filter would need 1199 bytes, but is limited to 1024")
+        );
+        assert_eq!(
+            client.call(&["BF.RESERVE", "huge", "0.000001", "18446744073709551615"]),
+            Reply::error(format!("ERR {}", BloomError::TooManyBits))
+        );
+        assert_eq!(
+            client.call(&["BF.INFO", "big"]),
+            Reply::error("ERR not found")
+        );
+        assert_eq!(
+            client.call(&["BF.RESERVE", "small", "0.01", "100"]),
+            Reply::Status("OK")
+        );
+
+        let (_runtime, addr) = start_limited(None, 64);
+        let mut client = Client::connect(addr);
+        assert_eq!(
+            client.call(&["BF.ADD", "new", "x"]),
+            Reply::error("ERR the filter would need 120 bytes, but is limited to 64")
+        );
+    }
+
+    #[test]
+    fn inline_commands() {
+        let (_runtime, addr) = start(None);
+        let mut client = Client::connect(addr);
+        assert_eq!(client.send(b"PING\r\n"), Reply::Status("PONG"));
+        assert_eq!(client.send(b"BF.ADD seen alice\r\n"), Reply::Integer(1));
+        assert_eq!(client.send(b"BF.EXISTS seen alice\r\n"), Reply::Integer(1));
+    }
+
+    #[test]
+    fn persists_filters() {
+        let dir = temp_dir("persists");
+        {
+            let (_runtime, addr) = start(Some(dir.clone()));
+            let mut client = Client::connect(addr);
+            client.call(&["BF.RESERVE", "seen ids", "0.001", "1000"]);
+            client.call(&["BF.MADD", "seen ids", "alice", "bob"]);
+            assert_eq!(client.call(&["SAVE"]), Reply::Status("OK"));
+        }
+        let path = filter_path(&dir, b"seen ids");
+        let filter = read_filter(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(filter.contains(&b"alice".to_vec()));
+
+        let (_runtime, addr) = start(Some(dir.clone()));
+        let mut client = Client::connect(addr);
+        assert_eq!(
+            client.call(&["BF.MEXISTS", "seen ids", "alice", "bob", "carol"]),
+            ints(&[1, 1, 0])
+        );
+        match client.call(&["BF.INFO", "seen ids"]) {
+            Reply::Array(fields) => {
+                assert_eq!(fields[1], Reply::Nil);
+                assert_eq!(fields[7], Reply::Integer(2));
+            }
+            reply => panic!("{:?}", reply),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_saves_keep_every_change() {
+        let dir = temp_dir("concurrent");
+        let server = Arc::new(Server::open(Some(dir.clone()), DEFAULT_MAX_FILTER_BYTES).unwrap());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let saves: Vec<_> = (0..100)
+                .map(|i| {
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        server.add(b"seen", &[i.to_string().into_bytes()], false);
+                        server.save().await.unwrap();
+                    })
+                })
+                .collect();
+            for save in saves {
+                save.await.unwrap();
+            }
+        });
+        // Every save finished after the change it followed, so nothing is left to save.
+        assert_eq!(runtime.block_on(server.save()).unwrap(), 0);
+        let filter = read_filter(&std::fs::read(filter_path(&dir, b"seen")).unwrap()).unwrap();
+        (0..100).for_each(|i| assert!(filter.contains(&i.to_string().into_bytes())));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hex_names() {
+        assert_eq!(hex(b"seen ids/\xff"), "7365656e206964732fff");
+        assert_eq!(
+            unhex("7365656e206964732fff"),
+            Some(b"seen ids/\xff".to_vec())
+        );
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("zz"), None);
+    }
+}
@@ -0,0 +1,254 @@
+//! Builds, queries, inspects and merges filters serialized with `BloomFilter::to_bytes`.
+//!
+//! Keys are read one per line, and are inserted as `String`s, so a filter built here can be read by
+//! `BloomFilter::<&str, ReHasher<MurmurHasher>>::from_bytes`, and the other way around.
+//!
+//! ```text
+//! bloom build --n 1000000 --p 0.01 --hasher murmur3 < keys.txt > filter.bf
+//! bloom query filter.bf < probes.txt
+//! bloom info filter.bf
+//! bloom merge a.bf b.bf > both.bf
+//! ```
+
+use bloom_filter::hash_to_indicies::K;
+use bloom_filter::BloomFilter;
+use bloom_filter::ReHasher;
+use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
+use murmur3::murmur3_32::MurmurHasher;
+use std::error::Error;
+use std::io::BufRead;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+
+type KeyFilter<H> = BloomFilter<String, ReHasher<H>>;
+
+#[derive(Parser)]
+#[command(name = "bloom", about = "Builds and inspects serialized bloom filters")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Builds a filter from the keys on stdin, one per line, and writes it to stdout
+    Build {
+        /// The number of keys the filter is sized for
+        #[arg(long)]
+        n: usize,
+        /// The false positive rate once `n` keys are inserted
+        #[arg(long)]
+        p: f64,
+        #[arg(long, value_enum, default_value_t = Hasher::Murmur3)]
+        hasher: Hasher,
+        /// Rice codes the bits if that makes the filter smaller, at the cost of it not being readable in place
+        #[arg(long)]
+        compact: bool,
+    },
+    /// Prints the keys on stdin, one per line, that the filter contains
+    Query {
+        filter: PathBuf,
+        #[arg(long, value_enum, default_value_t = Hasher::Murmur3)]
+        hasher: Hasher,
+    },
+    /// Prints the number of bits, number of hashes, fill ratio and estimated number of keys of a filter
+    Info { filter: PathBuf },
+    /// Writes the union of filters built with the same `n`, `p` and hasher to stdout
+    Merge {
+        #[arg(required = true, num_args = 2..)]
+        filters: Vec<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Hasher::Murmur3)]
+        hasher: Hasher,
+        /// Rice codes the bits if that makes the filter smaller, at the cost of it not being readable in place
+        #[arg(long)]
+        compact: bool,
+    },
+}
+
+/// The hashers a filter is populated with.
+/// These aren't recorded in the serialized format, so the same one has to be given to every command.
+#[derive(Clone, Copy, ValueEnum)]
+enum Hasher {
+    /// `ReHasher<MurmurHasher>`, with the 32 bit murmur3 hasher of the `murmur3` crate
+    Murmur3,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if let Err(e) = run(cli, stdin.lock(), &mut out).and_then(|_| Ok(out.flush()?)) {
+        eprintln!("bloom: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli, input: impl BufRead, out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Build {
+            n,
+            p,
+            hasher,
+            compact,
+        } => match hasher {
+            Hasher::Murmur3 => build::<MurmurHasher>(n, p, compact, input, out),
+        },
+        Command::Query { filter, hasher } => {
+            let filter = std::fs::read(filter)?;
+            match hasher {
+                Hasher::Murmur3 => query::<MurmurHasher>(&filter, input, out),
+            }
+        }
+        Command::Info { filter } => info(&std::fs::read(filter)?, out),
+        Command::Merge {
+            filters,
+            hasher,
+            compact,
+        } => {
+            let filters = filters
+                .iter()
+                .map(std::fs::read)
+                .collect::<Result<Vec<_>, _>>()?;
+            match hasher {
+                Hasher::Murmur3 => merge::<MurmurHasher>(&filters, compact, out),
+            }
+        }
+    }
+}
+
+/// Deserializes a filter, with hashers that perform as many hashes as it was written with.
+fn read<H>(bytes: &[u8]) -> Result<KeyFilter<H>, Box<dyn Error>> {
+    let k = bloom_filter::format::hashes(bytes)?;
+    Ok(KeyFilter::from_bytes(bytes, ReHasher::new(k))?)
+}
+
+fn write<H>(
+    filter: &KeyFilter<H>,
+    compact: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let bytes = if compact {
+        filter.to_compact_bytes()
+    } else {
+        filter.to_bytes()
+    };
+    Ok(out.write_all(&bytes)?)
+}
+
+fn build<H: std::hash::Hasher + Default>(
+    n: usize,
+    p: f64,
+    compact: bool,
+    keys: impl BufRead,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut filter = KeyFilter::<H>::try_optimal_new(n, p)?;
+    for key in keys.lines() {
+        filter.insert(&key?);
+    }
+    write(&filter, compact, out)
+}
+
+fn query<H: std::hash::Hasher + Default>(
+    filter: &[u8],
+    keys: impl BufRead,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let filter = read::<H>(filter)?;
+    for key in keys.lines() {
+        let key = key?;
+        if filter.contains(&key) {
+            writeln!(out, "{}", key)?;
+        }
+    }
+    Ok(())
+}
+
+fn info(filter: &[u8], out: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    // Nothing is hashed, so any hashers will do.
+    let filter = read::<MurmurHasher>(filter)?;
+    writeln!(out, "bits (m): {}", filter.num_bits())?;
+    writeln!(out, "hashes (k): {}", filter.k())?;
+    writeln!(out, "fill ratio: {:.4}", filter.fill_ratio())?;
+    writeln!(out, "estimated count: {:.0}", filter.estimated_count())?;
+    Ok(())
+}
+
+fn merge<H: std::hash::Hasher + Default>(
+    filters: &[Vec<u8>],
+    compact: bool,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut merged = read::<H>(&filters[0])?;
+    for filter in &filters[1..] {
+        merged.union(&read::<H>(filter)?)?;
+    }
+    write(&merged, compact, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_from(keys: &str, compact: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        build::<MurmurHasher>(1000, 0.01, compact, keys.as_bytes(), &mut out).unwrap();
+        out
+    }
+
+    fn query_with(filter: &[u8], keys: &str) -> String {
+        let mut out = Vec::new();
+        query::<MurmurHasher>(filter, keys.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn build_and_query() {
+        for &compact in &[false, true] {
+            let filter = build_from("hello\nthere\n", compact);
+            assert_eq!(
+                query_with(&filter, "hello\nnot here\nthere"),
+                "hello\nthere\n"
+            );
+        }
+    }
+
+    #[test]
+    fn readable_by_the_library() {
+        let filter = build_from("hello\n", false);
+        let k = bloom_filter::format::hashes(&filter).unwrap();
+        let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::from_bytes(&filter, ReHasher::new(k))
+            .unwrap();
+        assert!(bf.contains(&"hello"));
+    }
+
+    #[test]
+    fn info_of_a_filter() {
+        let keys: String = (0..500).map(|i| format!("key{}\n", i)).collect();
+        let mut out = Vec::new();
+        info(&build_from(&keys, false), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "bits (m): 9586");
+        assert_eq!(lines[1], "hashes (k): 7");
+        let count: f64 = lines[3]["estimated count: ".len()..].parse().unwrap();
+        assert!((475.0..525.0).contains(&count), "{}", count);
+    }
+
+    #[test]
+    fn merge_unions() {
+        let filters = vec![build_from("hello\n", false), build_from("there\n", true)];
+        let mut out = Vec::new();
+        merge::<MurmurHasher>(&filters, false, &mut out).unwrap();
+        assert_eq!(query_with(&out, "hello\nthere\nnot here"), "hello\nthere\n");
+
+        let mut smaller = Vec::new();
+        build::<MurmurHasher>(10, 0.01, false, "hello\n".as_bytes(), &mut smaller).unwrap();
+        let e = merge::<MurmurHasher>(&[out, smaller], false, &mut Vec::new()).unwrap_err();
+        assert!(e.to_string().contains("can't be combined"), "{}", e);
+    }
+}
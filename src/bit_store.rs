@@ -0,0 +1,287 @@
+//! Storage for the bits of a filter.
+//!
+//! Bit `i` of every store lives in bit `i % w` of word `i / w`, where `w` is the width of the store's words,
+//! so the little endian bytes of any store are the bits in the order of the serialized format.
+
+use bit_vec::BitVec;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use std::borrow::Cow;
+
+/// The bits backing a filter.
+///
+/// The bits past `len` in the last word are always unset.
+#[allow(clippy::len_without_is_empty)]
+pub trait BitStore {
+    /// Gets the number of bits.
+    fn len(&self) -> usize;
+
+    /// Gets bit `i`.
+    fn get(&self, i: usize) -> bool;
+
+    /// Sets bit `i` to `value`.
+    fn set(&mut self, i: usize, value: bool);
+
+    /// Gets the number of bits that are set.
+    fn count_ones(&self) -> usize;
+
+    /// Gets the bits packed into `ceil(len / 8)` bytes, with bit `i` being `bytes[i / 8] >> (i % 8) & 1`.
+    /// Stores that already hold their bits this way borrow them instead of copying.
+    fn as_bytes(&self) -> Cow<'_, [u8]>;
+
+    /// Unsets every bit.
+    fn clear(&mut self) {
+        (0..self.len()).for_each(|i| self.set(i, false))
+    }
+}
+
+/// Views words as their little endian bytes, truncated to `len` bits.
+fn words_as_bytes<W, const N: usize>(
+    words: &[W],
+    len: usize,
+    to_le_bytes: impl Fn(&W) -> [u8; N],
+) -> Cow<'_, [u8]> {
+    let num_bytes = len.div_ceil(8);
+    if cfg!(target_endian = "little") {
+        // Safety: the words are plain integers, every byte of which is initialized,
+        // and on a little endian target their bytes in memory are already in order.
+        let bytes = unsafe {
+            core::slice::from_raw_parts(words.as_ptr() as *const u8, core::mem::size_of_val(words))
+        };
+        Cow::Borrowed(&bytes[..num_bytes])
+    } else {
+        let mut bytes: Vec<u8> = words.iter().flat_map(to_le_bytes).collect();
+        bytes.truncate(num_bytes);
+        Cow::Owned(bytes)
+    }
+}
+
+impl BitStore for BitVec {
+    fn len(&self) -> usize {
+        BitVec::len(self)
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self[i]
+    }
+
+    fn set(&mut self, i: usize, value: bool) {
+        BitVec::set(self, i, value)
+    }
+
+    fn count_ones(&self) -> usize {
+        // The bits past the end of the last block are always kept unset.
+        self.blocks().map(|block| block.count_ones() as usize).sum()
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        words_as_bytes(self.storage(), BitVec::len(self), |w| w.to_le_bytes())
+    }
+
+    fn clear(&mut self) {
+        BitVec::clear(self)
+    }
+}
+
+impl BitStore for [u64] {
+    fn len(&self) -> usize {
+        <[u64]>::len(self) * 64
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    fn set(&mut self, i: usize, value: bool) {
+        if value {
+            self[i / 64] |= 1 << (i % 64);
+        } else {
+            self[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    fn count_ones(&self) -> usize {
+        self.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        words_as_bytes(self, BitStore::len(self), |w| w.to_le_bytes())
+    }
+
+    fn clear(&mut self) {
+        self.iter_mut().for_each(|w| *w = 0)
+    }
+}
+
+/// Delegates to the implementation for the words a store derefs to.
+macro_rules! deref_bit_store {
+    ($($store:ty => $words:ty),* $(,)?) => {
+        $(
+            impl BitStore for $store {
+                fn len(&self) -> usize {
+                    <$words as BitStore>::len(self)
+                }
+
+                fn get(&self, i: usize) -> bool {
+                    <$words as BitStore>::get(self, i)
+                }
+
+                fn set(&mut self, i: usize, value: bool) {
+                    <$words as BitStore>::set(self, i, value)
+                }
+
+                fn count_ones(&self) -> usize {
+                    <$words as BitStore>::count_ones(self)
+                }
+
+                fn as_bytes(&self) -> Cow<'_, [u8]> {
+                    <$words as BitStore>::as_bytes(self)
+                }
+
+                fn clear(&mut self) {
+                    <$words as BitStore>::clear(self)
+                }
+            }
+        )*
+    };
+}
+
+deref_bit_store!(Vec<u64> => [u64], Box<[u64]> => [u64], &mut [u8] => [u8]);
+
+impl<const N: usize> BitStore for [u64; N] {
+    fn len(&self) -> usize {
+        N * 64
+    }
+
+    fn get(&self, i: usize) -> bool {
+        <[u64] as BitStore>::get(self, i)
+    }
+
+    fn set(&mut self, i: usize, value: bool) {
+        <[u64] as BitStore>::set(self, i, value)
+    }
+
+    fn count_ones(&self) -> usize {
+        <[u64] as BitStore>::count_ones(self)
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        <[u64] as BitStore>::as_bytes(self)
+    }
+
+    fn clear(&mut self) {
+        <[u64] as BitStore>::clear(self)
+    }
+}
+
+/// Bytes, such as a memory mapped file, holding bits in the serialized order.
+impl BitStore for [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self) * 8
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self[i / 8] >> (i % 8) & 1 == 1
+    }
+
+    fn set(&mut self, i: usize, value: bool) {
+        if value {
+            self[i / 8] |= 1 << (i % 8);
+        } else {
+            self[i / 8] &= !(1 << (i % 8));
+        }
+    }
+
+    fn count_ones(&self) -> usize {
+        self.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self)
+    }
+
+    fn clear(&mut self) {
+        self.iter_mut().for_each(|b| *b = 0)
+    }
+}
+
+/// Atomic words, so the bits can be shared with code that reads them atomically.
+impl BitStore for [AtomicU64] {
+    fn len(&self) -> usize {
+        <[AtomicU64]>::len(self) * 64
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self[i / 64].load(Ordering::Acquire) >> (i % 64) & 1 == 1
+    }
+
+    fn set(&mut self, i: usize, value: bool) {
+        let word = self[i / 64].get_mut();
+        if value {
+            *word |= 1 << (i % 64);
+        } else {
+            *word &= !(1 << (i % 64));
+        }
+    }
+
+    fn count_ones(&self) -> usize {
+        self.iter()
+            .map(|w| w.load(Ordering::Relaxed).count_ones() as usize)
+            .sum()
+    }
+
+    fn as_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(
+            self.iter()
+                .flat_map(|w| w.load(Ordering::Relaxed).to_le_bytes())
+                .collect(),
+        )
+    }
+
+    fn clear(&mut self) {
+        self.iter_mut().for_each(|w| *w.get_mut() = 0)
+    }
+}
+
+deref_bit_store!(Vec<AtomicU64> => [AtomicU64], Box<[AtomicU64]> => [AtomicU64]);
+
+#[cfg(feature = "memmap")]
+deref_bit_store!(memmap2::MmapMut => [u8]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sets the same bits in a store and a bitvec, and checks that they agree.
+    fn agrees_with_bit_vec<S: BitStore + ?Sized>(store: &mut S) {
+        let mut bit_vec = BitVec::from_elem(store.len(), false);
+        (0..store.len())
+            .filter(|i| i % 7 == 0 || i % 11 == 3)
+            .for_each(|i| {
+                store.set(i, true);
+                bit_vec.set(i, true);
+            });
+        store.set(7, false);
+        bit_vec.set(7, false);
+        (0..store.len()).for_each(|i| assert_eq!(store.get(i), bit_vec[i], "bit {}", i));
+        assert_eq!(store.count_ones(), BitStore::count_ones(&bit_vec));
+        assert_eq!(store.as_bytes(), BitStore::as_bytes(&bit_vec));
+        store.clear();
+        assert_eq!(store.count_ones(), 0);
+    }
+
+    #[test]
+    fn stores_agree() {
+        agrees_with_bit_vec(&mut vec![0u64; 5]);
+        agrees_with_bit_vec(&mut [0u64; 3]);
+        agrees_with_bit_vec(&mut vec![0u8; 37][..]);
+        agrees_with_bit_vec(&mut (0..4).map(|_| AtomicU64::new(0)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn bit_vec_bytes_are_truncated() {
+        let mut bit_vec = BitVec::from_elem(10, false);
+        bit_vec.set(9, true);
+        assert_eq!(BitStore::as_bytes(&bit_vec), &[0, 0b10][..]);
+    }
+}
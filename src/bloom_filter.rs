@@ -1,3 +1,5 @@
+use crate::bit_store::BitStore;
+use crate::error::BloomError;
 use crate::hash_to_indicies::HashToIndices;
 use crate::hash_to_indicies::K;
 use crate::rehasher::ReHasher;
@@ -11,16 +13,25 @@ use core::marker::PhantomData;
 /// added to itself, but allows false positives when determining if an element has been added.
 /// This false positive rate is influenced by the number of hash functions used and the size of the backing bit vector,
 /// as well as the number of entries that have been recorded.
-#[derive(Debug, Clone)]
-pub struct BloomFilter<T, K> {
-    /// The backing bit vector.
-    pub(crate) bit_vec: BitVec,
+///
+/// The bits are stored in a `BitVec` unless another `BitStore` is provided with `with_store`.
+#[derive(Debug)]
+pub struct BloomFilter<T: ?Sized, K, S = BitVec> {
+    /// The backing bits.
+    pub(crate) bits: S,
     /// The type information of what the bitvector will accept as input.
     type_info: PhantomData<T>,
     /// The generic hashing structure.
     pub(crate) k: K,
 }
 
+// Not derived, as that would require the elements to be `Clone`.
+impl<T: ?Sized, K: Clone, S: Clone> Clone for BloomFilter<T, K, S> {
+    fn clone(&self) -> Self {
+        BloomFilter::from_parts(self.bits.clone(), self.k.clone())
+    }
+}
+
 //impl<T, K> Debug for BloomFilter<T, K> {
 //    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
 //        let s: &[u8] = self.bit_vec
@@ -33,7 +44,7 @@ pub struct BloomFilter<T, K> {
 //    }
 //}
 
-impl<T, H> BloomFilter<T, ReHasher<H>> {
+impl<T: ?Sized, H> BloomFilter<T, ReHasher<H>> {
     /// Constructs a new BloomFilter with an optimal ratio of m and k,
     /// derived from n and p inputs.
     ///
@@ -46,6 +57,9 @@ impl<T, H> BloomFilter<T, ReHasher<H>> {
     /// For practical use, optimal isn't really optimal with respect to the speed of the bloom filter.
     /// Manually choosing a lower `k` typically results in up to a 3x speedup.
     ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::BloomFilter;
@@ -54,17 +68,35 @@ impl<T, H> BloomFilter<T, ReHasher<H>> {
     /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001);
     /// ```
     pub fn optimal_new(n: usize, p: f64) -> Self {
-        let m = crate::optimal_m(n, p);
-        let k = crate::optimal_k(n, m);
-        BloomFilter {
-            bit_vec: BitVec::from_elem(m, false),
-            type_info: PhantomData,
-            k: ReHasher::new(k),
-        }
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new BloomFilter with an optimal ratio of m and k, derived from n and p inputs,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(0, 0.001);
+    /// assert_eq!(bf.err(), Some(BloomError::NoElements));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        let k = crate::optimal_k(n, m)?;
+        Ok(Self::from_parts(
+            BitVec::from_elem(m, false),
+            ReHasher::new(k),
+        ))
     }
 }
 
-impl<T, H> BloomFilter<T, H>
+impl<T: ?Sized, H> BloomFilter<T, H>
 where
     H: HashToIndices + K,
 {
@@ -85,6 +117,10 @@ where
     /// Because the insert and checking time scales with `k`, not with `m`,
     /// `m` can be increased to trade space efficiency for speed.
     ///
+    /// # Panics
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::BloomFilter;
@@ -93,19 +129,261 @@ where
     /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(1));
     /// ```
     pub fn with_rate(n: usize, p: f64, hashers: H) -> Self {
-        let m = crate::m_from_knp(hashers.k(), n, p);
+        Self::try_with_rate(n, p, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_rate(10000, 1.5, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::InvalidFalsePositiveRate(1.5)));
+    /// ```
+    pub fn try_with_rate(n: usize, p: f64, hashers: H) -> Result<Self, BloomError> {
+        let m = crate::m_from_knp(hashers.k(), n, p)?;
+        Ok(Self::from_parts(BitVec::from_elem(m, false), hashers))
+    }
+}
+
+impl<T: ?Sized, K, S> BloomFilter<T, K, S> {
+    /// Assembles a filter from already populated bits and the hashers that populated them.
+    pub(crate) fn from_parts(bits: S, k: K) -> Self {
         BloomFilter {
-            bit_vec: BitVec::from_elem(m, false),
+            bits,
             type_info: PhantomData,
-            k: hashers,
+            k,
+        }
+    }
+}
+
+impl<T: ?Sized, K, S: BitStore> BloomFilter<T, K, S> {
+    /// Gets the number of bits that are set.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// assert_eq!(bf.count_ones(), 0);
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.count_ones(), 1);
+    /// ```
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    /// Gets the fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bits.len() as f64
+    }
+
+    /// Checks if no bits are set, which means nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.count_ones() == 0
+    }
+
+    /// Checks if at least `threshold` of the bits are set.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.fill_ratio() >= threshold
+    }
+
+    /// Unsets every bit, keeping the allocation, so the filter can be reused.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// assert!(bf.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.bits.clear()
+    }
+}
+
+impl<T: ?Sized, U: K, S: BitStore> BloomFilter<T, U, S> {
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of bits that are set.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// assert_eq!(bf.current_false_positive_rate(), 0.0);
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.current_false_positive_rate(), 0.01);
+    /// ```
+    pub fn current_false_positive_rate(&self) -> f64 {
+        crate::sizing::false_positive_rate_from_fill(self.fill_ratio(), self.k.k())
+    }
+
+    /// Estimates the number of distinct elements that have been inserted, from the fraction of bits that are set.
+    /// The estimate becomes less accurate as the filter saturates, and is infinite once every bit is set.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<usize, ReHasher<MurmurHasher>>::optimal_new(1000, 0.01);
+    /// (0..500).for_each(|i| bf.insert(&i));
+    /// let count = bf.estimated_count();
+    /// assert!(count > 475.0 && count < 525.0);
+    /// ```
+    pub fn estimated_count(&self) -> f64 {
+        crate::sizing::items_from_fill(self.fill_ratio(), self.bits.len(), self.k.k())
+    }
+
+    /// Serializes the filter in the format described in the `format` module.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let bytes = bf.to_bytes();
+    /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::from_bytes(&bytes, ReHasher::new(2)).unwrap();
+    /// assert!(bf.contains(&"hello"));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::format::encode(self.k.k(), &self.bits, None)
+    }
+
+    /// Serializes the filter like `to_bytes`, but Rice codes the bits if that makes them smaller,
+    /// which it does for filters that are less than around a quarter full.
+    /// The bytes can be read by `from_bytes`, but not by `BloomFilterRef` or `MmapBloomFilter`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(10000, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let bytes = bf.to_compact_bytes();
+    /// assert!(bytes.len() < bf.to_bytes().len() / 10);
+    /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::from_bytes(&bytes, ReHasher::new(2)).unwrap();
+    /// assert!(bf.contains(&"hello"));
+    /// ```
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        crate::format::encode_compact(self.k.k(), &self.bits, None)
+    }
+}
+
+impl<T: ?Sized, U: K> BloomFilter<T, U> {
+    /// Deserializes a filter written by `to_bytes` or `to_compact_bytes`.
+    ///
+    /// # Arguments
+    /// * `bytes` - The serialized filter.
+    /// * `hashers` - The same hashers the filter was populated with.
+    ///
+    /// # Errors
+    /// If the bytes aren't a serialized filter,
+    /// or if they were written by a filter with hashers that perform a different number of hashes.
+    pub fn from_bytes(bytes: &[u8], hashers: U) -> Result<Self, BloomError> {
+        let (bit_vec, _) = crate::format::decode(bytes, hashers.k())?;
+        Ok(Self::from_parts(bit_vec, hashers))
+    }
+}
+
+impl<T: ?Sized, U: K> BloomFilter<T, U> {
+    /// Sets every bit that is set in `other`, so that the filter contains everything either filter contained.
+    /// The filters must have been populated with the same hashers.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut a = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// let mut b = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// a.insert(&"hello");
+    /// b.insert(&"there");
+    /// a.union(&b).unwrap();
+    /// assert!(a.contains(&"hello"));
+    /// assert!(a.contains(&"there"));
+    /// ```
+    ///
+    /// # Errors
+    /// If the filters have different numbers of bits, or hashers that perform different numbers of hashes.
+    pub fn union(&mut self, other: &Self) -> Result<(), BloomError> {
+        self.check_combinable(other)?;
+        self.bits.union(&other.bits);
+        Ok(())
+    }
+
+    /// Unsets every bit that is unset in `other`, so that the filter contains what both filters contained.
+    /// Lookups of elements that were only inserted into one of the filters may still return true,
+    /// so the false positive rate is at least that of either filter.
+    /// The filters must have been populated with the same hashers.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut a = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// let mut b = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// a.insert(&"hello");
+    /// a.insert(&"there");
+    /// b.insert(&"there");
+    /// a.intersect(&b).unwrap();
+    /// assert!(!a.contains(&"hello"));
+    /// assert!(a.contains(&"there"));
+    /// ```
+    ///
+    /// # Errors
+    /// If the filters have different numbers of bits, or hashers that perform different numbers of hashes.
+    pub fn intersect(&mut self, other: &Self) -> Result<(), BloomError> {
+        self.check_combinable(other)?;
+        self.bits.intersect(&other.bits);
+        Ok(())
+    }
+
+    fn check_combinable(&self, other: &Self) -> Result<(), BloomError> {
+        if self.k.k() != other.k.k() {
+            return Err(BloomError::ConflictingHashes {
+                requested: other.k.k(),
+                hashers: self.k.k(),
+            });
+        }
+        if self.bits.len() != other.bits.len() {
+            return Err(BloomError::MismatchedBits {
+                bits: self.bits.len(),
+                other: other.bits.len(),
+            });
         }
+        Ok(())
     }
 }
 
-impl<T, K> BloomFilter<T, K>
+impl<T: ?Sized, K> BloomFilter<T, K>
 where
     T: Hash,
-    K: HashToIndices,
+    K: HashToIndices + crate::hash_to_indicies::K,
 {
     /// Creates the bloom filter with a given number of bits
     /// and with a multiple-hashing-to-index function.
@@ -114,6 +392,9 @@ where
     /// * `m` - Number of bits for the BloomFilter.
     /// * `hashers` - Hashing to indices structure.
     ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::BloomFilter;
@@ -122,16 +403,83 @@ where
     /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
     /// ```
     pub fn new(m: usize, hashers: K) -> Self {
-        BloomFilter {
-            bit_vec: BitVec::from_elem(m, false),
-            type_info: PhantomData,
-            k: hashers,
-        }
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::error::check_m(m)?;
+        crate::error::check_k(hashers.k())?;
+        Ok(Self::from_parts(BitVec::from_elem(m, false), hashers))
+    }
+}
+
+impl<T: ?Sized, K, S> BloomFilter<T, K, S>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+    S: BitStore,
+{
+    /// Creates the bloom filter backed by the provided bits, which are usually all unset,
+    /// and with a multiple-hashing-to-index function.
+    /// The number of bits in the filter is the length of the store.
+    ///
+    /// # Arguments
+    /// * `store` - The bits backing the filter.
+    /// * `hashers` - Hashing to indices structure.
+    ///
+    /// # Panics
+    /// If the store holds no bits, or if the `k` of the hashers is 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, _, _>::with_store([0u64; 16], ReHasher::<MurmurHasher>::new(2));
+    /// bf.insert(&"hello");
+    /// assert!(bf.contains(&"hello"));
+    /// assert_eq!(bf.num_bits(), 1024);
+    /// ```
+    pub fn with_store(store: S, hashers: K) -> Self {
+        Self::try_with_store(store, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter backed by the provided bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if the store holds no bits, or if `k` is 0.
+    ///
+    /// # Errors
+    /// If the store holds no bits, or if the `k` of the hashers is 0.
+    pub fn try_with_store(store: S, hashers: K) -> Result<Self, BloomError> {
+        crate::error::check_m(store.len())?;
+        crate::error::check_k(hashers.k())?;
+        Ok(Self::from_parts(store, hashers))
     }
+}
 
+impl<T: ?Sized, K, S> BloomFilter<T, K, S>
+where
+    T: Hash,
+    K: HashToIndices,
+    S: BitStore,
+{
     /// Gets the number of bits in the used in the bloom filter.
     pub fn num_bits(&self) -> usize {
-        self.bit_vec.len()
+        self.bits.len()
     }
 
     /// Takes multiple hashes of the provided value, takes the hashes modulo the number of bits
@@ -158,7 +506,7 @@ where
         self.k
             .hash_to_indices(value, self.num_bits())
             .into_iter()
-            .for_each(|i| self.bit_vec.set(i, true));
+            .for_each(|i| self.bits.set(i, true));
     }
 
     /// Tests to see if the provided value is in the bloom filter.
@@ -169,7 +517,7 @@ where
     /// # Arguments
     ///
     /// * `value` - The value to be hashed to create indices into the bloom filter.
-    /// These indices will be used to see if the element has been added.
+    ///   These indices will be used to see if the element has been added.
     ///
     /// # Examples
     /// ```
@@ -187,11 +535,11 @@ where
         self.k
             .hash_to_indices(value, self.num_bits())
             .into_iter()
-            .all(|i| self.bit_vec[i])
+            .all(|i| self.bits.get(i))
     }
 }
 
-impl<T, U: K> K for BloomFilter<T, U> {
+impl<T: ?Sized, U: K, S> K for BloomFilter<T, U, S> {
     fn k(&self) -> usize {
         self.k.k()
     }
@@ -268,6 +616,60 @@ mod tests {
     fn with_rate_constructor() {
         let bf: BloomFilter<&str, ReHasher<MurmurHasher>> =
             BloomFilter::with_rate(1000, 0.0001, ReHasher::new(4));
-        assert_eq!(bf.num_bits(), 37_964)
+        assert_eq!(bf.num_bits(), 37_965)
+    }
+
+    #[test]
+    fn fills_up() {
+        let mut bf: BloomFilter<usize, ReHasher<MurmurHasher>> =
+            BloomFilter::new(1000, ReHasher::new(2));
+        assert!(bf.is_empty());
+        assert!(!bf.is_saturated(0.5));
+        (0..2000).for_each(|n| bf.insert(&n));
+        assert!(!bf.is_empty());
+        assert!(bf.is_saturated(0.5));
+        assert!(bf.fill_ratio() > 0.9);
+        // Not exactly equal, since `powi` may round differently, as it does under Miri.
+        assert!(
+            (bf.current_false_positive_rate() - bf.fill_ratio() * bf.fill_ratio()).abs() < 1e-12
+        );
+    }
+
+    #[test]
+    fn clear_keeps_size() {
+        let mut bf: BloomFilter<usize, ReHasher<MurmurHasher>> =
+            BloomFilter::new(1000, ReHasher::new(2));
+        (0..2000).for_each(|n| bf.insert(&n));
+        bf.clear();
+        assert!(bf.is_empty());
+        assert_eq!(bf.num_bits(), 1000);
+        bf.insert(&1);
+        assert!(bf.contains(&1));
+    }
+
+    #[test]
+    fn no_hashes() {
+        let bf = BloomFilter::<usize, ReHasher<MurmurHasher>>::try_new(1000, ReHasher::new(0));
+        assert_eq!(bf.err(), Some(BloomError::NoHashes));
+        let bf = BloomFilter::<usize, ReHasher<MurmurHasher>, Vec<u64>>::try_with_store(
+            vec![0; 16],
+            ReHasher::new(0),
+        );
+        assert_eq!(bf.err(), Some(BloomError::NoHashes));
+    }
+
+    #[test]
+    fn stores_agree() {
+        let mut bit_vec: BloomFilter<usize, ReHasher<MurmurHasher>> =
+            BloomFilter::new(1024, ReHasher::new(3));
+        let mut words: BloomFilter<usize, ReHasher<MurmurHasher>, Vec<u64>> =
+            BloomFilter::with_store(vec![0; 16], ReHasher::new(3));
+        (0..100).for_each(|n| {
+            bit_vec.insert(&n);
+            words.insert(&n);
+        });
+        assert_eq!(words.count_ones(), bit_vec.count_ones());
+        assert_eq!(words.to_bytes(), bit_vec.to_bytes());
+        (0..1000).for_each(|n| assert_eq!(words.contains(&n), bit_vec.contains(&n)));
     }
 }
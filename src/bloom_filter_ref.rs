@@ -0,0 +1,129 @@
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::format;
+use crate::format::Header;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+/// A read only bloom filter that borrows its bits from a filter serialized by `to_bytes`,
+/// so that lookups can be made without copying them.
+#[derive(Debug)]
+pub struct BloomFilterRef<'a, T, K> {
+    bits: &'a [u8],
+    num_bits: usize,
+    count: Option<usize>,
+    type_info: PhantomData<T>,
+    k: K,
+}
+
+impl<'a, T, U: K> BloomFilterRef<'a, T, U> {
+    /// Borrows a filter serialized by `to_bytes`.
+    ///
+    /// # Arguments
+    /// * `bytes` - The serialized filter.
+    /// * `hashers` - The same hashers the filter was populated with.
+    ///
+    /// # Errors
+    /// If the bytes aren't a serialized filter,
+    /// or if they were written by a filter with hashers that perform a different number of hashes.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::BloomFilterRef;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let bytes = bf.to_bytes();
+    /// let bf_ref = BloomFilterRef::<&str, ReHasher<MurmurHasher>>::from_bytes(&bytes, ReHasher::new(2)).unwrap();
+    /// assert!(bf_ref.contains(&"hello"));
+    /// assert!(!bf_ref.contains(&"not here"));
+    /// ```
+    pub fn from_bytes(bytes: &'a [u8], hashers: U) -> Result<Self, BloomError> {
+        let (header, bits) = Header::read(bytes, hashers.k())?;
+        Ok(BloomFilterRef {
+            bits,
+            num_bits: header.m,
+            count: header.count,
+            type_info: PhantomData,
+            k: hashers,
+        })
+    }
+}
+
+impl<'a, T, K> BloomFilterRef<'a, T, K> {
+    /// Gets the number of bits in the used in the bloom filter.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Gets the number of elements that were inserted, if the filter that was serialized counted them.
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    /// Gets the number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        self.bits.iter().map(|byte| byte.count_ones() as usize).sum()
+    }
+}
+
+impl<'a, T, K> BloomFilterRef<'a, T, K>
+where
+    T: Hash,
+    K: HashToIndices,
+{
+    /// Tests to see if the provided value is in the bloom filter.
+    /// This will return false positives if the bits that are the result of hashing the value are already set.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///   These indices will be used to see if the element has been added.
+    pub fn contains(&self, value: &T) -> bool {
+        self.k
+            .hash_to_indices(value, self.num_bits)
+            .into_iter()
+            .all(|i| format::get(self.bits, i))
+    }
+}
+
+impl<'a, T, K: Clone> BloomFilterRef<'a, T, K> {
+    /// Copies the bits into an owned `BloomFilter` that can be inserted into.
+    pub fn to_owned_filter(&self) -> BloomFilter<T, K> {
+        let bit_vec = format::bits_to_bit_vec(self.bits, self.num_bits);
+        BloomFilter::from_parts(bit_vec, self.k.clone())
+    }
+}
+
+impl<'a, T, U: K> K for BloomFilterRef<'a, T, U> {
+    fn k(&self) -> usize {
+        self.k.k()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counting_bloom_filter::CountingBloomFilter;
+    use crate::rehasher::ReHasher;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    #[test]
+    fn agrees_with_the_owned_filter() {
+        let mut bf: CountingBloomFilter<usize, ReHasher<MurmurHasher>> =
+            CountingBloomFilter::new(1000, ReHasher::new(3));
+        (0..100).for_each(|n| bf.insert(&n));
+        let bytes = bf.to_bytes();
+        let bf_ref: BloomFilterRef<usize, ReHasher<MurmurHasher>> =
+            BloomFilterRef::from_bytes(&bytes, ReHasher::new(3)).unwrap();
+        assert_eq!(bf_ref.count(), Some(100));
+        assert_eq!(bf_ref.count_ones(), bf.count_ones());
+        (0..1000).for_each(|n| assert_eq!(bf_ref.contains(&n), bf.contains(&n)));
+        let owned = bf_ref.to_owned_filter();
+        (0..1000).for_each(|n| assert_eq!(owned.contains(&n), bf.contains(&n)));
+    }
+}
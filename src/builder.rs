@@ -0,0 +1,369 @@
+use crate::atomic_counting_bloom_filter::AtomicCountingBloomFilter;
+use crate::bloom_filter::BloomFilter;
+use crate::counting_bloom_filter::CountingBloomFilter;
+use crate::counting_w_lock_bloom_filter::CountingWLockBloomFilter;
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use crate::rehasher::ReHasher;
+#[cfg(feature = "std")]
+use crate::sharded_bloom_filter::ShardedBloomFilter;
+use crate::w_lock_bloom_filter::WLockBloomFilter;
+use core::hash::Hash;
+
+/// Where the builder gets its hashers from.
+enum Hashers<K> {
+    /// Hashers that can be made to perform any number of hashes, so `k` can be solved for.
+    ForK(fn(usize) -> K),
+    /// Hashers that perform a fixed number of hashes.
+    Fixed(K),
+}
+
+/// Configures and constructs any of the filters in this crate.
+///
+/// Any combination of the expected number of elements (`n`), the false positive rate (`p`),
+/// the number of bits (`m`), the number of hashes (`k`) and a memory limit can be provided,
+/// and whatever is left unspecified is solved for when the filter is built:
+/// * `m` is taken from `bits`, otherwise it is solved from `n` and `p` (using `k` if it is known),
+///   otherwise the whole byte limit is used.
+/// * `k` is taken from `hashes` or from fixed hashers, otherwise it is the optimal `k` for `n` and `m`,
+///   or if `n` isn't known, the optimal `k` for `p`.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::BloomFilter;
+/// use bloom_filter::BloomFilterBuilder;
+/// use bloom_filter::CountingWLockBloomFilter;
+/// use bloom_filter::ReHasher;
+/// use murmur3::murmur3_32::MurmurHasher;
+/// let bf: BloomFilter<&str, ReHasher<MurmurHasher>> = BloomFilterBuilder::new()
+///     .expected_items(1000)
+///     .false_positive_rate(0.01)
+///     .build()
+///     .unwrap();
+/// assert_eq!(bf.num_bits(), 9586);
+///
+/// let bf: CountingWLockBloomFilter<&str, ReHasher<MurmurHasher>> = BloomFilterBuilder::new()
+///     .expected_items(1000)
+///     .max_bytes(512)
+///     .build()
+///     .unwrap();
+/// assert_eq!(bf.num_bits(), 4096);
+/// ```
+pub struct BloomFilterBuilder<K> {
+    n: Option<usize>,
+    p: Option<f64>,
+    m: Option<usize>,
+    k: Option<usize>,
+    max_bytes: Option<usize>,
+    hashers: Hashers<K>,
+}
+
+impl<H> BloomFilterBuilder<ReHasher<H>> {
+    /// Creates a builder for filters that use a `ReHasher`, so `k` can be solved for.
+    pub fn new() -> Self {
+        Self::from_hashers(Hashers::ForK(ReHasher::new))
+    }
+}
+
+impl<H> Default for BloomFilterBuilder<ReHasher<H>> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> BloomFilterBuilder<K> {
+    /// Creates a builder for filters that use the provided hashers.
+    /// `k` is fixed to the number of hashes they perform.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::BloomFilterBuilder;
+    /// use bloom_filter::hash_numbers::Two;
+    /// use hashers::fnv::FNV1aHasher32;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf: BloomFilter<&str, Two<MurmurHasher, FNV1aHasher32>> =
+    ///     BloomFilterBuilder::with_hasher(Two::default())
+    ///         .expected_items(1000)
+    ///         .false_positive_rate(0.01)
+    ///         .build()
+    ///         .unwrap();
+    /// ```
+    pub fn with_hasher(hashers: K) -> Self {
+        Self::from_hashers(Hashers::Fixed(hashers))
+    }
+
+    fn from_hashers(hashers: Hashers<K>) -> Self {
+        BloomFilterBuilder {
+            n: None,
+            p: None,
+            m: None,
+            k: None,
+            max_bytes: None,
+            hashers,
+        }
+    }
+
+    /// Sets the number of elements that are expected to be inserted (`n`).
+    pub fn expected_items(mut self, n: usize) -> Self {
+        self.n = Some(n);
+        self
+    }
+
+    /// Sets the false positive rate the filter should have once `n` elements have been inserted (`p`).
+    pub fn false_positive_rate(mut self, p: f64) -> Self {
+        self.p = Some(p);
+        self
+    }
+
+    /// Sets the number of bits in the filter (`m`).
+    pub fn bits(mut self, m: usize) -> Self {
+        self.m = Some(m);
+        self
+    }
+
+    /// Sets the number of hashes performed for every element (`k`).
+    pub fn hashes(mut self, k: usize) -> Self {
+        self.k = Some(k);
+        self
+    }
+
+    /// Limits the number of bytes the bits of the filter may take up.
+    /// If `m` can't be otherwise determined, the filter will use all of them.
+    pub fn max_bytes(mut self, limit: usize) -> Self {
+        self.max_bytes = Some(limit);
+        self
+    }
+
+    /// Replaces the hashers the filter will use.
+    /// `k` is fixed to the number of hashes they perform.
+    pub fn hasher<U>(self, hashers: U) -> BloomFilterBuilder<U> {
+        BloomFilterBuilder {
+            n: self.n,
+            p: self.p,
+            m: self.m,
+            k: self.k,
+            max_bytes: self.max_bytes,
+            hashers: Hashers::Fixed(hashers),
+        }
+    }
+}
+
+impl<U: K> BloomFilterBuilder<U> {
+    /// Works out `m` and `k` from the provided parameters.
+    fn solve(&self) -> Result<(usize, usize), BloomError> {
+        let fixed_k = match (&self.hashers, self.k) {
+            (Hashers::Fixed(hashers), Some(requested)) if hashers.k() != requested => {
+                return Err(BloomError::ConflictingHashes {
+                    requested,
+                    hashers: hashers.k(),
+                })
+            }
+            (Hashers::Fixed(hashers), _) => Some(hashers.k()),
+            (Hashers::ForK(_), k) => k,
+        };
+        let fixed_k = fixed_k.map(crate::error::check_k).transpose()?;
+
+        let m = match (self.m, self.n, self.p, fixed_k, self.max_bytes) {
+            (Some(m), ..) => m,
+            (None, Some(n), Some(p), Some(k), _) => crate::m_from_knp(k, n, p)?,
+            (None, Some(n), Some(p), None, _) => crate::optimal_m(n, p)?,
+            (None, _, _, _, Some(max_bytes)) => {
+                max_bytes.checked_mul(8).ok_or(BloomError::TooManyBits)?
+            }
+            _ => return Err(BloomError::Underdetermined),
+        };
+        let m = crate::error::check_m(m)?;
+        if let Some(max) = self.max_bytes {
+            let needed = m.div_ceil(8);
+            if needed > max {
+                return Err(BloomError::ExceedsMaxBytes { needed, max });
+            }
+        }
+
+        let k = match (fixed_k, self.n, self.p) {
+            (Some(k), ..) => k,
+            (None, Some(n), _) => crate::optimal_k(n, m)?,
+            // Without `n`, the optimal `k` only depends on `p`: k = -log2(p)
+            (None, None, Some(p)) => {
+                core::cmp::max((-crate::error::check_p(p)?.log2()).ceil() as usize, 1)
+            }
+            _ => return Err(BloomError::Underdetermined),
+        };
+        Ok((m, k))
+    }
+
+    /// Solves for the parameters that were left unspecified and constructs the filter.
+    ///
+    /// # Errors
+    /// If too few parameters were provided to determine `m` and `k`, if any of them are invalid,
+    /// if the filter wouldn't fit within `max_bytes`, or if `hashes` doesn't match the provided hashers.
+    pub fn build<F: FromBuilder<U>>(self) -> Result<F, BloomError> {
+        let (m, k) = self.solve()?;
+        let hashers = match self.hashers {
+            Hashers::ForK(for_k) => for_k(k),
+            Hashers::Fixed(hashers) => hashers,
+        };
+        F::from_builder(m, hashers)
+    }
+}
+
+/// Filters that can be constructed by a `BloomFilterBuilder`.
+pub trait FromBuilder<K>: Sized {
+    /// Constructs the filter with `m` bits and the provided hashers.
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError>;
+}
+
+impl<T: Hash, K: HashToIndices + crate::hash_to_indicies::K> FromBuilder<K> for BloomFilter<T, K> {
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError> {
+        BloomFilter::try_new(m, hashers)
+    }
+}
+
+impl<T: Hash, K: HashToIndices + crate::hash_to_indicies::K> FromBuilder<K>
+    for CountingBloomFilter<T, K>
+{
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError> {
+        CountingBloomFilter::try_new(m, hashers)
+    }
+}
+
+impl<T: Hash, K: HashToIndices + crate::hash_to_indicies::K> FromBuilder<K>
+    for WLockBloomFilter<T, K>
+{
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError> {
+        WLockBloomFilter::try_new(m, hashers)
+    }
+}
+
+impl<T: Hash, K: HashToIndices + crate::hash_to_indicies::K> FromBuilder<K>
+    for CountingWLockBloomFilter<T, K>
+{
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError> {
+        CountingWLockBloomFilter::try_new(m, hashers)
+    }
+}
+
+impl<T: Hash, K: HashToIndices + crate::hash_to_indicies::K> FromBuilder<K>
+    for AtomicCountingBloomFilter<T, K>
+{
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError> {
+        AtomicCountingBloomFilter::try_new(m, hashers)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Hash, K: HashToIndices + crate::hash_to_indicies::K> FromBuilder<K>
+    for ShardedBloomFilter<T, K>
+{
+    fn from_builder(m: usize, hashers: K) -> Result<Self, BloomError> {
+        ShardedBloomFilter::try_new(m, hashers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_numbers::Two;
+    use hashers::fnv::FNV1aHasher32;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    type Builder = BloomFilterBuilder<ReHasher<MurmurHasher>>;
+
+    #[test]
+    fn matches_optimal_new() {
+        let bf: BloomFilter<&str, ReHasher<MurmurHasher>> = Builder::new()
+            .expected_items(1000)
+            .false_positive_rate(0.01)
+            .build()
+            .unwrap();
+        let optimal: BloomFilter<&str, ReHasher<MurmurHasher>> =
+            BloomFilter::optimal_new(1000, 0.01);
+        assert_eq!(bf.num_bits(), optimal.num_bits());
+        assert_eq!(bf.k(), optimal.k());
+    }
+
+    #[test]
+    fn matches_with_rate() {
+        let bf: CountingBloomFilter<&str, ReHasher<MurmurHasher>> = Builder::new()
+            .expected_items(1000)
+            .false_positive_rate(0.0001)
+            .hashes(4)
+            .build()
+            .unwrap();
+        assert_eq!(bf.num_bits(), 37_965);
+        assert_eq!(bf.k(), 4);
+    }
+
+    #[test]
+    fn k_from_bits_and_items() {
+        let bf: WLockBloomFilter<&str, ReHasher<MurmurHasher>> = Builder::new()
+            .expected_items(2000)
+            .bits(28756)
+            .build()
+            .unwrap();
+        assert_eq!(bf.k(), 10);
+    }
+
+    #[test]
+    fn k_from_false_positive_rate_alone() {
+        let bf: BloomFilter<&str, ReHasher<MurmurHasher>> = Builder::new()
+            .bits(10_000)
+            .false_positive_rate(0.01)
+            .build()
+            .unwrap();
+        assert_eq!(bf.k(), 7);
+    }
+
+    #[test]
+    fn fixed_hashers() {
+        let bf: BloomFilter<&str, Two<MurmurHasher, FNV1aHasher32>> = Builder::new()
+            .expected_items(1000)
+            .false_positive_rate(0.01)
+            .hasher(Two::default())
+            .build()
+            .unwrap();
+        assert_eq!(bf.k(), 2);
+        assert_eq!(bf.num_bits(), crate::m_from_knp(2, 1000, 0.01).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let underdetermined: Result<BloomFilter<&str, ReHasher<MurmurHasher>>, _> =
+            Builder::new().expected_items(1000).build();
+        assert_eq!(underdetermined.err(), Some(BloomError::Underdetermined));
+
+        let too_big: Result<BloomFilter<&str, ReHasher<MurmurHasher>>, _> = Builder::new()
+            .expected_items(1000)
+            .false_positive_rate(0.01)
+            .max_bytes(1000)
+            .build();
+        assert_eq!(
+            too_big.err(),
+            Some(BloomError::ExceedsMaxBytes {
+                needed: 1199,
+                max: 1000
+            })
+        );
+
+        let unrepresentable: Result<BloomFilter<&str, ReHasher<MurmurHasher>>, _> = Builder::new()
+            .false_positive_rate(0.01)
+            .max_bytes(usize::MAX)
+            .build();
+        assert_eq!(unrepresentable.err(), Some(BloomError::TooManyBits));
+
+        let conflicting: Result<BloomFilter<&str, Two<MurmurHasher, FNV1aHasher32>>, _> =
+            BloomFilterBuilder::with_hasher(Two::default())
+                .bits(1000)
+                .hashes(3)
+                .build();
+        assert_eq!(
+            conflicting.err(),
+            Some(BloomError::ConflictingHashes {
+                requested: 3,
+                hashers: 2
+            })
+        );
+    }
+}
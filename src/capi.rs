@@ -0,0 +1,248 @@
+//! A C API over `BloomFilter<[u8], ReHasher<MurmurHasher>>`, so programs in other languages can share filters,
+//! and their serialized format, with Rust programs.
+//!
+//! The header, `capi/bloom_filter.h`, is generated by cbindgen when the crate is built with the `capi` feature,
+//! and a test checks that the checked in copy is up to date.
+//! Filters are passed around as opaque `Bloom` handles, which are created by `bloom_new` or `bloom_deserialize`,
+//! and must be released with `bloom_free`.
+//! Every function other than `bloom_free` returns a `BloomStatus`, and writes its result through a pointer.
+//!
+//! Keys are hashed like `&[u8]` and `Vec<u8>` are in Rust, so a filter serialized by
+//! `BloomFilter::<Vec<u8>, ReHasher<MurmurHasher>>::to_bytes` answers lookups for the same keys here.
+
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::rehasher::ReHasher;
+use murmur3::murmur3_32::MurmurHasher;
+
+/// An opaque handle to a filter of byte strings.
+pub struct Bloom(BloomFilter<[u8], ReHasher<MurmurHasher>>);
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BloomStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A pointer that must not be null was null.
+    NullPointer = 1,
+    /// The number of expected elements was 0, or the false positive rate was not between 0 and 1 exclusive.
+    InvalidParameters = 2,
+    /// The bytes passed to `bloom_deserialize` aren't a serialized filter.
+    InvalidFormat = 3,
+    /// The buffer passed to `bloom_serialize` is too small to hold the filter.
+    BufferTooSmall = 4,
+}
+
+impl From<BloomError> for BloomStatus {
+    fn from(e: BloomError) -> Self {
+        match e {
+            BloomError::InvalidFormat(_) | BloomError::ConflictingHashes { .. } => {
+                BloomStatus::InvalidFormat
+            }
+            _ => BloomStatus::InvalidParameters,
+        }
+    }
+}
+
+/// Gets the bytes at `bytes`, allowing a null pointer if `len` is 0.
+unsafe fn slice<'a>(bytes: *const u8, len: usize) -> Option<&'a [u8]> {
+    if bytes.is_null() {
+        if len == 0 {
+            Some(&[])
+        } else {
+            None
+        }
+    } else {
+        Some(core::slice::from_raw_parts(bytes, len))
+    }
+}
+
+/// Creates a filter sized for `n` elements with a false positive rate of `p`, and writes its handle to `out`.
+///
+/// # Safety
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bloom_new(n: usize, p: f64, out: *mut *mut Bloom) -> BloomStatus {
+    if out.is_null() {
+        return BloomStatus::NullPointer;
+    }
+    match BloomFilter::try_optimal_new(n, p) {
+        Ok(filter) => {
+            *out = Box::into_raw(Box::new(Bloom(filter)));
+            BloomStatus::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Inserts the `len` bytes at `bytes` into the filter.
+///
+/// # Safety
+/// `filter` must be a handle that hasn't been freed, and `bytes` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bloom_insert_bytes(
+    filter: *mut Bloom,
+    bytes: *const u8,
+    len: usize,
+) -> BloomStatus {
+    match (filter.as_mut(), slice(bytes, len)) {
+        (Some(filter), Some(bytes)) => {
+            filter.0.insert(bytes);
+            BloomStatus::Ok
+        }
+        _ => BloomStatus::NullPointer,
+    }
+}
+
+/// Tests to see if the `len` bytes at `bytes` are in the filter, and writes the answer to `out`.
+/// This will return false positives, but never false negatives.
+///
+/// # Safety
+/// `filter` must be a handle that hasn't been freed, `bytes` must be valid for reads of `len` bytes,
+/// and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bloom_contains_bytes(
+    filter: *const Bloom,
+    bytes: *const u8,
+    len: usize,
+    out: *mut bool,
+) -> BloomStatus {
+    match (filter.as_ref(), slice(bytes, len)) {
+        (Some(filter), Some(bytes)) if !out.is_null() => {
+            *out = filter.0.contains(bytes);
+            BloomStatus::Ok
+        }
+        _ => BloomStatus::NullPointer,
+    }
+}
+
+/// Serializes the filter in the format described in the `format` module into the `capacity` bytes at `buf`,
+/// and writes the number of bytes it takes to `len`.
+///
+/// If `buf` is too small, nothing is written to it, `len` is still set, and `BloomStatus::BufferTooSmall`
+/// is returned, so `buf` may be null to find out how large it needs to be.
+///
+/// # Safety
+/// `filter` must be a handle that hasn't been freed, `buf` must be valid for writes of `capacity` bytes,
+/// and `len` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bloom_serialize(
+    filter: *const Bloom,
+    buf: *mut u8,
+    capacity: usize,
+    len: *mut usize,
+) -> BloomStatus {
+    let filter = match filter.as_ref() {
+        Some(filter) if !len.is_null() => filter,
+        _ => return BloomStatus::NullPointer,
+    };
+    let bytes = filter.0.to_bytes();
+    *len = bytes.len();
+    if buf.is_null() || capacity < bytes.len() {
+        return BloomStatus::BufferTooSmall;
+    }
+    core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+    BloomStatus::Ok
+}
+
+/// Deserializes the `len` bytes at `bytes`, written by `bloom_serialize` or `BloomFilter::to_bytes`,
+/// and writes the handle of the filter to `out`.
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bloom_deserialize(
+    bytes: *const u8,
+    len: usize,
+    out: *mut *mut Bloom,
+) -> BloomStatus {
+    let bytes = match slice(bytes, len) {
+        Some(bytes) if !out.is_null() => bytes,
+        _ => return BloomStatus::NullPointer,
+    };
+    let filter =
+        crate::format::hashes(bytes).and_then(|k| BloomFilter::from_bytes(bytes, ReHasher::new(k)));
+    match filter {
+        Ok(filter) => {
+            *out = Box::into_raw(Box::new(Bloom(filter)));
+            BloomStatus::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Releases a filter. Null handles are ignored.
+///
+/// # Safety
+/// `filter` must be null, or a handle that hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bloom_free(filter: *mut Bloom) {
+    if !filter.is_null() {
+        drop(Box::from_raw(filter));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn header_is_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/bloom_filter.h"));
+        assert!(
+            generated == include_str!("../capi/bloom_filter.h"),
+            "capi/bloom_filter.h is out of date, copy it from {}/bloom_filter.h",
+            env!("OUT_DIR")
+        );
+    }
+
+    #[test]
+    fn shares_the_format_with_rust() {
+        let mut bf = BloomFilter::<Vec<u8>, ReHasher<MurmurHasher>>::optimal_new(100, 0.01);
+        bf.insert(&b"hello".to_vec());
+        let bytes = bf.to_bytes();
+        unsafe {
+            let mut filter = ptr::null_mut();
+            assert_eq!(
+                bloom_deserialize(bytes.as_ptr(), bytes.len(), &mut filter),
+                BloomStatus::Ok
+            );
+            let mut found = false;
+            assert_eq!(
+                bloom_contains_bytes(filter, b"hello".as_ptr(), 5, &mut found),
+                BloomStatus::Ok
+            );
+            assert!(found);
+            bloom_free(filter);
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        unsafe {
+            let mut filter = ptr::null_mut();
+            assert_eq!(
+                bloom_new(0, 0.01, &mut filter),
+                BloomStatus::InvalidParameters
+            );
+            assert_eq!(
+                bloom_deserialize(b"nope".as_ptr(), 4, &mut filter),
+                BloomStatus::InvalidFormat
+            );
+            let mut huge_k =
+                BloomFilter::<[u8], ReHasher<MurmurHasher>>::new(64, ReHasher::new(1)).to_bytes();
+            huge_k[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+            assert_eq!(
+                bloom_deserialize(huge_k.as_ptr(), huge_k.len(), &mut filter),
+                BloomStatus::InvalidFormat
+            );
+            assert_eq!(
+                bloom_insert_bytes(ptr::null_mut(), ptr::null(), 0),
+                BloomStatus::NullPointer
+            );
+            assert!(filter.is_null());
+        }
+    }
+}
@@ -1,17 +1,19 @@
+use crate::bit_store::BitStore;
 use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
 use crate::hash_to_indicies::HashToIndices;
-use crate::hash_to_indicies::K as GetK;
 use crate::hash_to_indicies::K;
 use crate::rehasher::ReHasher;
+use bit_vec::BitVec;
 use core::hash::Hash;
 
 /// A bloom filter that counts on each insertion so that it can give a reliable estimate of
 /// a false positive rate at its current occupancy level.
-pub struct CountingBloomFilter<T, K> {
+pub struct CountingBloomFilter<T, K, S = BitVec> {
     /// Backing bloom filter.
-    bloom_filter: BloomFilter<T, K>,
+    pub(crate) bloom_filter: BloomFilter<T, K, S>,
     /// The counter that keeps track of the number of elements inserted.
-    count: usize,
+    pub(crate) count: usize,
 }
 
 impl<T, H> CountingBloomFilter<T, ReHasher<H>> {
@@ -23,6 +25,9 @@ impl<T, H> CountingBloomFilter<T, ReHasher<H>> {
     /// * `n` - Number of expected elements to be inserted into the set.
     /// * `p` - False positive rate.
     ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::CountingBloomFilter;
@@ -31,17 +36,35 @@ impl<T, H> CountingBloomFilter<T, ReHasher<H>> {
     /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001);
     /// ```
     pub fn optimal_new(n: usize, p: f64) -> Self {
-        let bloom_filter = BloomFilter::optimal_new(n, p);
-        CountingBloomFilter {
-            bloom_filter,
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new CountingBloomFilter with an optimal ratio of m and k, derived from n and p inputs,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(0, 0.001);
+    /// assert_eq!(bf.err(), Some(BloomError::NoElements));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        Ok(CountingBloomFilter {
+            bloom_filter: BloomFilter::try_optimal_new(n, p)?,
             count: 0,
-        }
+        })
     }
 }
 
 impl<T, H> CountingBloomFilter<T, H>
 where
-    H: HashToIndices + GetK,
+    H: HashToIndices + K,
 {
     /// Given a fixed size `k`, and an expected number of elements (`n`),
     /// initialize the bloom filter with a computed `m` value to achieve the required error rate.
@@ -60,6 +83,10 @@ where
     /// Because the insert and checking time scales with `k`, not with `m`,
     /// `m` can be increased to trade space efficiency for speed.
     ///
+    /// # Panics
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::CountingBloomFilter;
@@ -68,17 +95,42 @@ where
     /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(1));
     /// ```
     pub fn with_rate(expected_elements: usize, error_rate: f64, k: H) -> Self {
-        CountingBloomFilter {
-            bloom_filter: BloomFilter::with_rate(expected_elements, error_rate, k),
+        Self::try_with_rate(expected_elements, error_rate, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_rate(10000, 1.5, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::InvalidFalsePositiveRate(1.5)));
+    /// ```
+    pub fn try_with_rate(
+        expected_elements: usize,
+        error_rate: f64,
+        k: H,
+    ) -> Result<Self, BloomError> {
+        Ok(CountingBloomFilter {
+            bloom_filter: BloomFilter::try_with_rate(expected_elements, error_rate, k)?,
             count: 0,
-        }
+        })
     }
 }
 
 impl<T, K> CountingBloomFilter<T, K>
 where
     T: Hash,
-    K: HashToIndices + GetK,
+    K: HashToIndices + crate::hash_to_indicies::K,
 {
     /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function.
     ///
@@ -86,6 +138,9 @@ where
     /// * `m` - Number of bits for the BloomFilter.
     /// * `hashers` - Hashing to indices structure.
     ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::CountingBloomFilter;
@@ -94,12 +149,38 @@ where
     /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
     /// ```
     pub fn new(m: usize, hashers: K) -> Self {
-        CountingBloomFilter {
-            bloom_filter: BloomFilter::new(m, hashers),
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        Ok(CountingBloomFilter {
+            bloom_filter: BloomFilter::try_new(m, hashers)?,
             count: 0,
-        }
+        })
     }
+}
 
+impl<T, K, S> CountingBloomFilter<T, K, S>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+    S: BitStore,
+{
     /// Gets the number of bits in the used in the bloom filter.
     pub fn num_bits(&self) -> usize {
         self.bloom_filter.num_bits()
@@ -156,6 +237,129 @@ where
         self.bloom_filter.contains(value)
     }
 
+    /// Gets the number of bits that are set.
+    pub fn count_ones(&self) -> usize {
+        self.bloom_filter.count_ones()
+    }
+
+    /// Gets the fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.bloom_filter.fill_ratio()
+    }
+
+    /// Checks if no bits are set, which means nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.bloom_filter.is_empty()
+    }
+
+    /// Checks if at least `threshold` of the bits are set.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.bloom_filter.is_saturated(threshold)
+    }
+
+    /// Unsets every bit and resets the count, keeping the allocation, so the filter can be reused.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// assert_eq!(bf.false_positive_chance(), 0.0);
+    /// ```
+    pub fn clear(&mut self) {
+        self.bloom_filter.clear();
+        self.count = 0;
+    }
+
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of bits that are set rather than from the number of inserts like `false_positive_chance`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.current_false_positive_rate(), 0.01);
+    /// assert!(bf.false_positive_chance() > 0.01);
+    /// ```
+    pub fn current_false_positive_rate(&self) -> f64 {
+        self.bloom_filter.current_false_positive_rate()
+    }
+
+    /// Serializes the filter and its count in the format described in the `format` module.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// let bytes = bf.to_bytes();
+    /// let bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::from_bytes(&bytes, ReHasher::new(1)).unwrap();
+    /// assert_eq!(bf.false_positive_chance(), 0.009950166250831893);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::format::encode(
+            self.bloom_filter.k.k(),
+            &self.bloom_filter.bits,
+            Some(self.count),
+        )
+    }
+
+    /// Serializes the filter and its count like `to_bytes`, but Rice codes the bits if that makes them smaller.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        crate::format::encode_compact(
+            self.bloom_filter.k.k(),
+            &self.bloom_filter.bits,
+            Some(self.count),
+        )
+    }
+}
+
+impl<T, K> CountingBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+{
+    /// Deserializes a filter written by `to_bytes` or `to_compact_bytes`.
+    ///
+    /// # Arguments
+    /// * `bytes` - The serialized filter.
+    /// * `hashers` - The same hashers the filter was populated with.
+    ///
+    /// # Errors
+    /// If the bytes aren't a serialized filter, if they don't record how many elements were inserted,
+    /// or if they were written by a filter with hashers that perform a different number of hashes.
+    pub fn from_bytes(bytes: &[u8], hashers: K) -> Result<Self, BloomError> {
+        let (bit_vec, count) = crate::format::decode(bytes, hashers.k())?;
+        let count = count.ok_or(BloomError::InvalidFormat(
+            "the number of inserted elements wasn't recorded",
+        ))?;
+        Ok(CountingBloomFilter {
+            bloom_filter: BloomFilter::from_parts(bit_vec, hashers),
+            count,
+        })
+    }
+}
+
+impl<T, K, S> CountingBloomFilter<T, K, S>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+    S: BitStore,
+{
     /// Returns the current chance that any given lookup will return a false positive.
     ///
     /// # Note
@@ -180,9 +384,25 @@ where
             self.bloom_filter.num_bits(),
         )
     }
+
+    /// Gets the number of elements that have been inserted, including duplicates.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.count(), 2);
+    /// ```
+    pub fn count(&self) -> usize {
+        self.count
+    }
 }
 
-impl<T, U: K> K for CountingBloomFilter<T, U> {
+impl<T, U: K, S> K for CountingBloomFilter<T, U, S> {
     fn k(&self) -> usize {
         self.bloom_filter.k.k()
     }
@@ -202,6 +422,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)] // Miri makes `exp` imprecise, so the rate isn't exactly this.
     fn full_false_positive() {
         let mut cbf: CountingBloomFilter<&str, One<MurmurHasher>> =
             CountingBloomFilter::new(10, One::default());
@@ -1,16 +1,20 @@
+use crate::bit_store::BitStore;
+use crate::bloom_filter::BloomFilter;
+use crate::counting_bloom_filter::CountingBloomFilter;
+use crate::error::BloomError;
 use crate::hash_to_indicies::HashToIndices;
-use crate::hash_to_indicies::K as GetK;
 use crate::hash_to_indicies::K;
 use crate::rehasher::ReHasher;
+use crate::sync::AtomicUsize;
+use crate::sync::Ordering;
 use crate::w_lock_bloom_filter::WLockBloomFilter;
+use bit_vec::BitVec;
 use core::hash::Hash;
-use core::sync::atomic::AtomicUsize;
-use core::sync::atomic::Ordering;
 
 /// A bloom filter with a spinlock permitting writes and an atomic counter to allow
 /// assessing the percentage chance of a false positive.
-pub struct CountingWLockBloomFilter<T, K> {
-    bloom_filter: WLockBloomFilter<T, K>,
+pub struct CountingWLockBloomFilter<T, K, S = BitVec> {
+    bloom_filter: WLockBloomFilter<T, K, S>,
     count: AtomicUsize,
 }
 
@@ -23,6 +27,9 @@ impl<T, H> CountingWLockBloomFilter<T, ReHasher<H>> {
     /// * `n` - Number of expected elements to be inserted into the set.
     /// * `p` - False positive rate.
     ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::CountingWLockBloomFilter;
@@ -31,17 +38,35 @@ impl<T, H> CountingWLockBloomFilter<T, ReHasher<H>> {
     /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001);
     /// ```
     pub fn optimal_new(n: usize, p: f64) -> Self {
-        let bloom_filter = WLockBloomFilter::optimal_new(n, p);
-        CountingWLockBloomFilter {
-            bloom_filter,
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new CountingWLockBloomFilter with an optimal ratio of m and k, derived from n and p inputs,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(0, 0.001);
+    /// assert_eq!(bf.err(), Some(BloomError::NoElements));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        Ok(CountingWLockBloomFilter {
+            bloom_filter: WLockBloomFilter::try_optimal_new(n, p)?,
             count: AtomicUsize::new(0),
-        }
+        })
     }
 }
 
 impl<T, H> CountingWLockBloomFilter<T, H>
 where
-    H: HashToIndices + GetK,
+    H: HashToIndices + K,
 {
     /// Given a fixed size `k`, and an expected number of elements (`n`),
     /// initialize the bloom filter with a computed `m` value to achieve the required error rate.
@@ -60,6 +85,10 @@ where
     /// Because the insert and checking time scales with `k`, not with `m`,
     /// `m` can be increased to trade space efficiency for speed.
     ///
+    /// # Panics
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::CountingWLockBloomFilter;
@@ -68,17 +97,38 @@ where
     /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(1));
     /// ```
     pub fn with_rate(n: usize, p: f64, hashers: H) -> Self {
-        CountingWLockBloomFilter {
-            bloom_filter: WLockBloomFilter::with_rate(n, p, hashers),
+        Self::try_with_rate(n, p, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_rate(10000, 1.5, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::InvalidFalsePositiveRate(1.5)));
+    /// ```
+    pub fn try_with_rate(n: usize, p: f64, hashers: H) -> Result<Self, BloomError> {
+        Ok(CountingWLockBloomFilter {
+            bloom_filter: WLockBloomFilter::try_with_rate(n, p, hashers)?,
             count: AtomicUsize::new(0),
-        }
+        })
     }
 }
 
 impl<T, K> CountingWLockBloomFilter<T, K>
 where
     T: Hash,
-    K: HashToIndices + GetK,
+    K: HashToIndices + crate::hash_to_indicies::K,
 {
     /// Creates the bloom filter with a given number of bits
     /// and with a multiple-hashing-to-index function.
@@ -86,6 +136,9 @@ where
     /// * `m` - Number of bits for the BloomFilter.
     /// * `hashers` - Hashing to indices structure.
     ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::CountingWLockBloomFilter;
@@ -94,12 +147,38 @@ where
     /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
     /// ```
     pub fn new(m: usize, hashers: K) -> Self {
-        CountingWLockBloomFilter {
-            bloom_filter: WLockBloomFilter::new(m, hashers),
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        Ok(CountingWLockBloomFilter {
+            bloom_filter: WLockBloomFilter::try_new(m, hashers)?,
             count: AtomicUsize::new(0),
-        }
+        })
     }
+}
 
+impl<T, K, S> CountingWLockBloomFilter<T, K, S>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+    S: BitStore,
+{
     /// Gets the number of bits in the used in the bloom filter.
     pub fn num_bits(&self) -> usize {
         self.bloom_filter.num_bits()
@@ -125,8 +204,15 @@ where
     /// bf.insert(&"hello");
     /// ```
     pub fn insert(&self, value: &T) {
-        self.bloom_filter.insert(value);
-        self.count.fetch_add(1, Ordering::Acquire);
+        let indices = self
+            .bloom_filter
+            .k
+            .hash_to_indices(value, self.bloom_filter.num_bits());
+        // The count is bumped under the same lock as the bits, so a snapshot never sees one without the other.
+        self.bloom_filter.with_lock(|bits| {
+            indices.into_iter().for_each(|i| bits.set(i, true));
+            self.count.fetch_add(1, Ordering::Relaxed);
+        });
     }
 
     /// Tests to see if the provided value is in the bloom filter.
@@ -137,7 +223,7 @@ where
     /// # Arguments
     ///
     /// * `value` - The value to be hashed to create indices into the bloom filter.
-    /// These indices will be used to see if the element has been added.
+    ///   These indices will be used to see if the element has been added.
     ///
     /// # Examples
     /// ```
@@ -155,6 +241,72 @@ where
         self.bloom_filter.contains(value)
     }
 
+    /// Gets the number of bits that are set.
+    /// The write lock is held while they are counted, so concurrent inserts wait for it.
+    pub fn count_ones(&self) -> usize {
+        self.bloom_filter.count_ones()
+    }
+
+    /// Gets the fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.bloom_filter.fill_ratio()
+    }
+
+    /// Checks if no bits are set, which means nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.bloom_filter.is_empty()
+    }
+
+    /// Checks if at least `threshold` of the bits are set.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.bloom_filter.is_saturated(threshold)
+    }
+
+    /// Unsets every bit and resets the count in place, so the filter can be reused without replacing it wherever it is shared.
+    ///
+    /// # Notes
+    /// The count is reset under the same lock as the bits, so a snapshot never sees one cleared without the other.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// assert_eq!(bf.false_positive_chance(), 0.0);
+    /// ```
+    pub fn clear(&self) {
+        self.bloom_filter.with_lock(|bits| {
+            bits.clear();
+            self.count.store(0, Ordering::Relaxed);
+        });
+    }
+
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of bits that are set rather than from the number of inserts like `false_positive_chance`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.current_false_positive_rate(), 0.01);
+    /// assert!(bf.false_positive_chance() > 0.01);
+    /// ```
+    pub fn current_false_positive_rate(&self) -> f64 {
+        self.bloom_filter.current_false_positive_rate()
+    }
+
     /// Returns the current chance that any given lookup will return a false positive.
     ///
     /// # Note
@@ -181,13 +333,66 @@ where
     }
 }
 
-impl<T, U: K> K for CountingWLockBloomFilter<T, U> {
+impl<T, K, S: BitStore> CountingWLockBloomFilter<T, K, S> {
+    /// Creates a counting filter that can be shared across threads from a snapshot.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingBloomFilter;
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut snapshot = CountingBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// snapshot.insert(&"hello");
+    /// let bf = CountingWLockBloomFilter::from_snapshot(snapshot);
+    /// assert!(bf.contains(&"hello"));
+    /// assert_eq!(bf.false_positive_chance(), 0.009950166250831893);
+    /// ```
+    pub fn from_snapshot(snapshot: CountingBloomFilter<T, K, S>) -> Self {
+        CountingWLockBloomFilter {
+            bloom_filter: WLockBloomFilter::from_snapshot(snapshot.bloom_filter),
+            count: AtomicUsize::new(snapshot.count),
+        }
+    }
+}
+
+impl<T, K: Clone, S: BitStore + Clone> CountingWLockBloomFilter<T, K, S> {
+    /// Copies the filter and its count into an owned `CountingBloomFilter`.
+    ///
+    /// # Notes
+    /// The write lock is held while the bits and the count are copied,
+    /// so the count in the snapshot always matches the inserts that made it into the bits.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::CountingWLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = CountingWLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// let snapshot = bf.snapshot();
+    /// bf.insert(&"there");
+    /// assert!(snapshot.contains(&"hello"));
+    /// assert_eq!(snapshot.false_positive_chance(), 0.009950166250831893);
+    /// ```
+    pub fn snapshot(&self) -> CountingBloomFilter<T, K, S> {
+        let (bits, count) = self
+            .bloom_filter
+            .with_lock(|bits| (bits.clone(), self.count.load(Ordering::Relaxed)));
+        CountingBloomFilter {
+            bloom_filter: BloomFilter::from_parts(bits, self.bloom_filter.k.clone()),
+            count,
+        }
+    }
+}
+
+impl<T, U: K, S> K for CountingWLockBloomFilter<T, U, S> {
     fn k(&self) -> usize {
         self.bloom_filter.k.k()
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(loom)))]
 mod tests {
     use super::*;
     use murmur3::murmur3_32::MurmurHasher;
@@ -200,4 +405,57 @@ mod tests {
         assert_eq!(bf.k(), 7)
     }
 
+    #[test]
+    fn snapshot_round_trip() {
+        let bf: CountingWLockBloomFilter<i32, ReHasher<MurmurHasher>> =
+            CountingWLockBloomFilter::optimal_new(1000, 0.01);
+        (0..500).for_each(|n| bf.insert(&n));
+        let snapshot = bf.snapshot();
+        // Counts are compared rather than the false positive chances computed from them,
+        // which Miri doesn't compute to the last bit.
+        assert_eq!(snapshot.count(), 500);
+        let restored = CountingWLockBloomFilter::from_snapshot(snapshot);
+        assert_eq!(restored.snapshot().count(), 500);
+        (0..1000).for_each(|n| assert_eq!(restored.contains(&n), bf.contains(&n)));
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    #[test]
+    fn snapshot_count_matches_bits() {
+        loom::model(|| {
+            let bf: Arc<CountingWLockBloomFilter<&str, ReHasher<MurmurHasher>>> =
+                Arc::new(CountingWLockBloomFilter::new(16, ReHasher::new(2)));
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"hello"))
+            };
+            let snapshot = bf.snapshot();
+            writer.join().unwrap();
+            assert_eq!(snapshot.count == 1, snapshot.contains(&"hello"));
+        });
+    }
+
+    #[test]
+    fn clear_during_insert() {
+        loom::model(|| {
+            let bf: Arc<CountingWLockBloomFilter<&str, ReHasher<MurmurHasher>>> =
+                Arc::new(CountingWLockBloomFilter::new(16, ReHasher::new(2)));
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"hello"))
+            };
+            bf.clear();
+            writer.join().unwrap();
+            let snapshot = bf.snapshot();
+            assert_eq!(snapshot.count == 1, snapshot.contains(&"hello"));
+            assert_eq!(snapshot.count == 0, snapshot.is_empty());
+        });
+    }
 }
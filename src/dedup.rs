@@ -0,0 +1,266 @@
+//! Iterator adapters that drop items a filter has already seen.
+
+use crate::bloom_filter::BloomFilter;
+use crate::filter::Filter;
+use crate::rehasher::ReHasher;
+use core::hash::Hash;
+use std::collections::hash_map::DefaultHasher;
+
+/// The filter `bloom_dedup` and `bloom_dedup_by_key` create, hashing with std's `DefaultHasher`.
+pub type DedupFilter<T> = BloomFilter<T, ReHasher<DefaultHasher>>;
+
+/// How many items a deduplicating iterator has let through and dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DedupStats {
+    /// The items that were yielded, because the filter hadn't seen them.
+    pub yielded: usize,
+    /// The items that were dropped, because the filter had seen them.
+    /// Some of these may be false positives, which were never actually seen.
+    pub dropped: usize,
+}
+
+/// Adds `bloom_dedup` and its variants to every iterator.
+///
+/// # Notes
+/// The filter only ever gives false positives, so no duplicate is ever yielded,
+/// but some items are dropped without having been seen before, at the filter's false positive rate.
+/// The rate climbs as more distinct items than the filter was sized for go through it.
+pub trait BloomDedupExt: Iterator + Sized {
+    /// Yields only the items a `BloomFilter` sized for `n` items with a false positive rate of `p` hasn't seen.
+    ///
+    /// # Arguments
+    /// * `n` - The number of distinct items the filter is sized for.
+    /// * `p` - The false positive rate once `n` distinct items have been seen.
+    ///
+    /// # Panics
+    /// If `n` is 0, or `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomDedupExt;
+    /// let deduped: Vec<_> = vec![1, 2, 1, 3, 2].into_iter().bloom_dedup(100, 0.001).collect();
+    /// assert_eq!(deduped, vec![1, 2, 3]);
+    /// ```
+    fn bloom_dedup(self, n: usize, p: f64) -> BloomDedup<Self, DedupFilter<Self::Item>>
+    where
+        Self::Item: Hash,
+    {
+        self.bloom_dedup_with(DedupFilter::optimal_new(n, p))
+    }
+
+    /// Yields only the items the provided filter hasn't seen, inserting them as they are yielded.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomDedupExt;
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut seen = BloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(100, 0.001);
+    /// seen.insert(&"hello");
+    /// let deduped: Vec<_> = vec!["hello", "there", "there"].into_iter().bloom_dedup_with(seen).collect();
+    /// assert_eq!(deduped, vec!["there"]);
+    /// ```
+    fn bloom_dedup_with<F: Filter<Self::Item>>(self, filter: F) -> BloomDedup<Self, F> {
+        BloomDedup {
+            iter: self,
+            filter,
+            stats: DedupStats::default(),
+        }
+    }
+
+    /// Yields only the items whose keys a `BloomFilter` sized for `n` keys with a false positive rate of `p`
+    /// hasn't seen.
+    ///
+    /// # Arguments
+    /// * `n` - The number of distinct keys the filter is sized for.
+    /// * `p` - The false positive rate once `n` distinct keys have been seen.
+    /// * `key` - Gets the key of an item.
+    ///
+    /// # Panics
+    /// If `n` is 0, or `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomDedupExt;
+    /// let users = vec![(1, "alice"), (2, "bob"), (1, "alice again")];
+    /// let deduped: Vec<_> = users.into_iter().bloom_dedup_by_key(100, 0.001, |user| user.0).collect();
+    /// assert_eq!(deduped, vec![(1, "alice"), (2, "bob")]);
+    /// ```
+    fn bloom_dedup_by_key<K, G>(
+        self,
+        n: usize,
+        p: f64,
+        key: G,
+    ) -> BloomDedupByKey<Self, DedupFilter<K>, G>
+    where
+        K: Hash,
+        G: FnMut(&Self::Item) -> K,
+    {
+        self.bloom_dedup_by_key_with(DedupFilter::optimal_new(n, p), key)
+    }
+
+    /// Yields only the items whose keys the provided filter hasn't seen, inserting the keys as items are yielded.
+    fn bloom_dedup_by_key_with<K, F, G>(self, filter: F, key: G) -> BloomDedupByKey<Self, F, G>
+    where
+        F: Filter<K>,
+        G: FnMut(&Self::Item) -> K,
+    {
+        BloomDedupByKey {
+            iter: self,
+            filter,
+            key,
+            stats: DedupStats::default(),
+        }
+    }
+}
+
+impl<I: Iterator> BloomDedupExt for I {}
+
+/// An iterator that drops the items its filter has already seen. Created by `bloom_dedup` and `bloom_dedup_with`.
+///
+/// The stats can be read as it goes, by iterating over it with `by_ref`.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::BloomDedupExt;
+/// let mut deduped = vec![1, 2, 1, 3, 2].into_iter().bloom_dedup(100, 0.001);
+/// assert_eq!(deduped.by_ref().count(), 3);
+/// assert_eq!(deduped.stats().dropped, 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BloomDedup<I, F> {
+    iter: I,
+    filter: F,
+    stats: DedupStats,
+}
+
+impl<I, F> BloomDedup<I, F> {
+    /// Gets how many items have been yielded and dropped so far.
+    pub fn stats(&self) -> DedupStats {
+        self.stats
+    }
+
+    /// Gets the filter, which holds every item that was yielded, to carry on deduplicating with elsewhere.
+    pub fn into_filter(self) -> F {
+        self.filter
+    }
+}
+
+impl<I: Iterator, F: Filter<I::Item>> Iterator for BloomDedup<I, F> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        for item in &mut self.iter {
+            if self.filter.contains(&item) {
+                self.stats.dropped += 1;
+            } else {
+                self.filter.insert(&item);
+                self.stats.yielded += 1;
+                return Some(item);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// An iterator that drops the items whose keys its filter has already seen.
+/// Created by `bloom_dedup_by_key` and `bloom_dedup_by_key_with`.
+#[derive(Debug, Clone)]
+pub struct BloomDedupByKey<I, F, G> {
+    iter: I,
+    filter: F,
+    key: G,
+    stats: DedupStats,
+}
+
+impl<I, F, G> BloomDedupByKey<I, F, G> {
+    /// Gets how many items have been yielded and dropped so far.
+    pub fn stats(&self) -> DedupStats {
+        self.stats
+    }
+
+    /// Gets the filter, which holds the key of every item that was yielded, to carry on deduplicating with elsewhere.
+    pub fn into_filter(self) -> F {
+        self.filter
+    }
+}
+
+impl<I, F, G, K> Iterator for BloomDedupByKey<I, F, G>
+where
+    I: Iterator,
+    F: Filter<K>,
+    G: FnMut(&I::Item) -> K,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        for item in &mut self.iter {
+            let key = (self.key)(&item);
+            if self.filter.contains(&key) {
+                self.stats.dropped += 1;
+            } else {
+                self.filter.insert(&key);
+                self.stats.yielded += 1;
+                return Some(item);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sharded_bloom_filter::ShardedBloomFilter;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    #[test]
+    fn drops_duplicates() {
+        let items = (0..1000).chain(0..1000).chain(500..1500);
+        let mut deduped = items.bloom_dedup(2000, 0.0001);
+        let kept: Vec<u32> = deduped.by_ref().collect();
+        assert_eq!(kept, (0..1500).collect::<Vec<_>>());
+        assert_eq!(
+            deduped.stats(),
+            DedupStats {
+                yielded: 1500,
+                dropped: 1500
+            }
+        );
+        assert!(deduped.into_filter().contains(&1499));
+    }
+
+    #[test]
+    fn by_key_with_any_filter() {
+        let filter: ShardedBloomFilter<u32, ReHasher<MurmurHasher>> =
+            ShardedBloomFilter::optimal_new(100, 0.001, 4);
+        let words = vec!["apple", "avocado", "banana", "blueberry", "cherry"];
+        let mut deduped = words
+            .into_iter()
+            .bloom_dedup_by_key_with(filter, |word| word.as_bytes()[0] as u32);
+        assert_eq!(
+            deduped.by_ref().collect::<Vec<_>>(),
+            vec!["apple", "banana", "cherry"]
+        );
+        assert_eq!(deduped.stats().dropped, 2);
+    }
+
+    #[test]
+    fn false_positives_are_dropped() {
+        // A filter this small drops some items that were never seen, but never yields a duplicate.
+        let mut deduped = (0..1000u32).chain(0..1000).bloom_dedup(10, 0.1);
+        let kept: Vec<u32> = deduped.by_ref().collect();
+        assert!(kept.len() < 1000);
+        assert!(kept.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(deduped.stats().yielded + deduped.stats().dropped, 2000);
+    }
+}
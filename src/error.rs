@@ -0,0 +1,152 @@
+use core::fmt;
+
+/// The reasons a filter can't be constructed from the provided parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BloomError {
+    /// The number of expected elements (`n`) was 0.
+    NoElements,
+    /// The number of bits (`m`) was, or would have been computed to be, 0.
+    NoBits,
+    /// The number of hashes (`k`) was 0.
+    NoHashes,
+    /// The false positive rate (`p`) was not within the open range `(0, 1)`.
+    InvalidFalsePositiveRate(f64),
+    /// A sharded filter was asked to be split into 0 shards.
+    NoShards,
+    /// Not enough parameters were provided to a `BloomFilterBuilder` to work out `m` and `k`.
+    Underdetermined,
+    /// The number of hashes given to a `BloomFilterBuilder`, or recorded in a serialized filter,
+    /// didn't match the `k` of the hashers.
+    ConflictingHashes {
+        /// The number of hashes that was asked for.
+        requested: usize,
+        /// The number of hashes the hashers perform.
+        hashers: usize,
+    },
+    /// The filter would need more bytes than a `BloomFilterBuilder` was limited to.
+    ExceedsMaxBytes {
+        /// The number of bytes the filter would need.
+        needed: usize,
+        /// The limit.
+        max: usize,
+    },
+    /// The bytes aren't a filter in the serialized format, with the reason why.
+    InvalidFormat(&'static str),
+    /// Filters with different numbers of bits (`m`) were combined.
+    MismatchedBits {
+        /// The number of bits of the filter being combined into.
+        bits: usize,
+        /// The number of bits of the other filter.
+        other: usize,
+    },
+    /// The number of bits (`m`) a filter would need was too large to be represented by a `usize`.
+    TooManyBits,
+    /// A filter that doesn't grow already holds as many elements as it was created for.
+    Full,
+    /// The Golomb-Rice parameter (`P`) of a `GolombCodedSet` was 64 or more.
+    InvalidGolombParameter(u8),
+    /// The range a `GolombCodedSet` hashes its keys below, `N * M`, was 0 or didn't fit in a `u64`.
+    InvalidGolombRange {
+        /// The number of keys.
+        n: u64,
+        /// The inverse of the false positive rate.
+        m: u64,
+    },
+}
+
+impl fmt::Display for BloomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BloomError::NoElements => {
+                write!(f, "the number of expected elements (n) must be at least 1")
+            }
+            BloomError::NoBits => write!(f, "the number of bits (m) must be at least 1"),
+            BloomError::NoHashes => write!(f, "the number of hashes (k) must be at least 1"),
+            BloomError::InvalidFalsePositiveRate(p) => write!(
+                f,
+                "the false positive rate (p) must be between 0 and 1 exclusive, but was {}",
+                p
+            ),
+            BloomError::NoShards => write!(f, "the number of shards must be at least 1"),
+            BloomError::Underdetermined => write!(
+                f,
+                "either the number of bits (m), the number of expected elements (n) and false positive rate (p), \
+                 or a byte limit must be provided"
+            ),
+            BloomError::ConflictingHashes { requested, hashers } => write!(
+                f,
+                "{} hashes (k) were requested, but the hashers perform {}",
+                requested, hashers
+            ),
+            BloomError::ExceedsMaxBytes { needed, max } => write!(
+                f,
+                "the filter would need {} bytes, but is limited to {}",
+                needed, max
+            ),
+            BloomError::InvalidFormat(reason) => write!(f, "invalid serialized filter: {}", reason),
+            BloomError::MismatchedBits { bits, other } => write!(
+                f,
+                "a filter with {} bits can't be combined with one with {}",
+                bits, other
+            ),
+            BloomError::TooManyBits => write!(f, "the number of bits (m) needed doesn't fit in a usize"),
+            BloomError::Full => write!(f, "the filter is full, and can't grow"),
+            BloomError::InvalidGolombParameter(p) => write!(
+                f,
+                "the Golomb-Rice parameter (P) must be below 64, but was {}",
+                p
+            ),
+            BloomError::InvalidGolombRange { n, m } => write!(
+                f,
+                "N * M must be between 1 and u64::MAX, but N was {} and M was {}",
+                n, m
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BloomError {}
+
+pub(crate) fn check_n(n: usize) -> Result<usize, BloomError> {
+    if n == 0 {
+        Err(BloomError::NoElements)
+    } else {
+        Ok(n)
+    }
+}
+
+pub(crate) fn check_m(m: usize) -> Result<usize, BloomError> {
+    if m == 0 {
+        Err(BloomError::NoBits)
+    } else {
+        Ok(m)
+    }
+}
+
+/// Rounds up a number of bits (`m`) that was computed in f64, checking that it is at least 1 and fits in a `usize`.
+pub(crate) fn check_computed_m(m: f64) -> Result<usize, BloomError> {
+    // Written so that NaN fails the check.
+    if m < usize::MAX as f64 {
+        check_m(m.ceil() as usize)
+    } else {
+        Err(BloomError::TooManyBits)
+    }
+}
+
+pub(crate) fn check_k(k: usize) -> Result<usize, BloomError> {
+    if k == 0 {
+        Err(BloomError::NoHashes)
+    } else {
+        Ok(k)
+    }
+}
+
+pub(crate) fn check_p(p: f64) -> Result<f64, BloomError> {
+    // Written so that NaN fails the check.
+    if p > 0.0 && p < 1.0 {
+        Ok(p)
+    } else {
+        Err(BloomError::InvalidFalsePositiveRate(p))
+    }
+}
@@ -0,0 +1,94 @@
+use crate::atomic_bloom_filter::AtomicBloomFilter;
+use crate::atomic_counting_bloom_filter::AtomicCountingBloomFilter;
+use crate::bit_store::BitStore;
+use crate::bloom_filter::BloomFilter;
+use crate::counting_bloom_filter::CountingBloomFilter;
+use crate::counting_w_lock_bloom_filter::CountingWLockBloomFilter;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+#[cfg(feature = "std")]
+use crate::sharded_bloom_filter::ShardedBloomFilter;
+use crate::w_lock_bloom_filter::WLockBloomFilter;
+use core::hash::Hash;
+
+/// The operations every filter in this crate supports,
+/// for code that should work with any of them.
+///
+/// The concurrent filters only need `&self` to insert, but implement this with `&mut self` like the others.
+pub trait Filter<T> {
+    /// Inserts the value into the filter.
+    fn insert(&mut self, value: &T);
+    /// Checks if the value may have been inserted into the filter.
+    fn contains(&self, value: &T) -> bool;
+}
+
+impl<T: Hash, U: HashToIndices, S: BitStore> Filter<T> for BloomFilter<T, U, S> {
+    fn insert(&mut self, value: &T) {
+        BloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        BloomFilter::contains(self, value)
+    }
+}
+
+impl<T: Hash, U: HashToIndices + K, S: BitStore> Filter<T> for CountingBloomFilter<T, U, S> {
+    fn insert(&mut self, value: &T) {
+        CountingBloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        CountingBloomFilter::contains(self, value)
+    }
+}
+
+impl<T: Hash, U: HashToIndices, S: BitStore> Filter<T> for WLockBloomFilter<T, U, S> {
+    fn insert(&mut self, value: &T) {
+        WLockBloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        WLockBloomFilter::contains(self, value)
+    }
+}
+
+impl<T: Hash, U: HashToIndices + K, S: BitStore> Filter<T> for CountingWLockBloomFilter<T, U, S> {
+    fn insert(&mut self, value: &T) {
+        CountingWLockBloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        CountingWLockBloomFilter::contains(self, value)
+    }
+}
+
+impl<T: Hash, U: HashToIndices + K> Filter<T> for AtomicBloomFilter<T, U> {
+    fn insert(&mut self, value: &T) {
+        AtomicBloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        AtomicBloomFilter::contains(self, value)
+    }
+}
+
+impl<T: Hash, U: HashToIndices + K> Filter<T> for AtomicCountingBloomFilter<T, U> {
+    fn insert(&mut self, value: &T) {
+        AtomicCountingBloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        AtomicCountingBloomFilter::contains(self, value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Hash, U: HashToIndices> Filter<T> for ShardedBloomFilter<T, U> {
+    fn insert(&mut self, value: &T) {
+        ShardedBloomFilter::insert(self, value)
+    }
+
+    fn contains(&self, value: &T) -> bool {
+        ShardedBloomFilter::contains(self, value)
+    }
+}
@@ -0,0 +1,588 @@
+//! The serialized format written by `to_bytes`, and read by `from_bytes` and `BloomFilterRef`.
+//!
+//! All integers are little endian.
+//!
+//! | Offset | Size         | Field                                                                  |
+//! |--------|--------------|------------------------------------------------------------------------|
+//! | 0      | 4            | Magic, `b"BLMF"`                                                       |
+//! | 4      | 1            | Version, currently 1                                                   |
+//! | 5      | 1            | Encoding of the bits, 0 for raw or 1 for Rice coded                    |
+//! | 6      | 1            | The Rice parameter (`r`), at most 16, if the bits are Rice coded, or 0 |
+//! | 7      | 1            | Reserved, 0                                                            |
+//! | 8      | 8            | The number of hashes (`k`)                                             |
+//! | 16     | 8            | The number of bits (`m`)                                               |
+//! | 24     | 8            | The number of inserted elements, or `u64::MAX` if it wasn't tracked    |
+//! | 32     |              | The bits, in the encoding given above                                  |
+//!
+//! Raw bits take `ceil(m / 8)` bytes, with bit `i` being `bytes[i / 8] >> (i % 8) & 1`.
+//! Bits past `m` in the last byte are always 0.
+//! The header is a multiple of 8 bytes long, so the bits stay aligned when the format is memory mapped.
+//!
+//! Rice coded bits start with the number of set bits, as a `u64`.
+//! Then, for each set bit, the gap `g` between it and the previous set bit (or the start, for the first)
+//! is written as `g >> r` 1s, a 0, and the low `r` bits of `g`, lowest first.
+//! The gap is the number of unset bits in between.
+//! Last comes the gap between the last set bit and `m`, written the same way,
+//! so the coded bits account for every bit, and untrusted bytes can't claim more bits than they code.
+//! The coded bits are packed like raw bits, and the last byte is padded with 0s.
+//! Lightly filled filters are a fraction of their raw size when Rice coded,
+//! but the bits have to be decoded before lookups can be made.
+
+use crate::bit_store::BitStore;
+use crate::error::BloomError;
+use bit_vec::BitVec;
+use core::convert::TryFrom;
+
+const MAGIC: &[u8; 4] = b"BLMF";
+const VERSION: u8 = 1;
+const RAW: u8 = 0;
+const RICE: u8 = 1;
+const UNTRACKED: u64 = u64::MAX;
+/// The largest Rice parameter, which bounds the bits a coded bit can stand for to `2^17 / 18`,
+/// at the cost of a 1 for every `2^16` bits of filters sparser than that.
+const MAX_RICE_PARAMETER: u8 = 16;
+
+/// The length of the header that comes before the bits.
+pub(crate) const HEADER_LEN: usize = 32;
+
+/// The most hashes (`k`) `hashes` reads from a serialized filter.
+/// Hashers allocate an index per hash on every lookup, so bytes from an untrusted source mustn't be able
+/// to ask for any number of them. Filters with more hashes are still read by `from_bytes`,
+/// with hashers the caller chose.
+pub const MAX_HASHES: usize = 255;
+
+/// How the bits of a serialized filter are encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Raw,
+    /// Rice coded, with the parameter.
+    Rice(u8),
+}
+
+/// The parameters of a serialized filter.
+pub(crate) struct Header {
+    pub(crate) k: usize,
+    pub(crate) m: usize,
+    pub(crate) count: Option<usize>,
+    encoding: Encoding,
+}
+
+impl Header {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        let (encoding, parameter) = match self.encoding {
+            Encoding::Raw => (RAW, 0),
+            Encoding::Rice(r) => (RICE, r),
+        };
+        out.extend_from_slice(&[VERSION, encoding, parameter, 0]);
+        out.extend_from_slice(&(self.k as u64).to_le_bytes());
+        out.extend_from_slice(&(self.m as u64).to_le_bytes());
+        let count = self.count.map(|c| c as u64).unwrap_or(UNTRACKED);
+        out.extend_from_slice(&count.to_le_bytes());
+    }
+
+    /// Reads the header, checks that it describes a filter with raw bits for hashers performing `k` hashes,
+    /// and returns it along with the bits that follow it.
+    pub(crate) fn read(bytes: &[u8], k: usize) -> Result<(Header, &[u8]), BloomError> {
+        let (header, bits) = Header::read_any(bytes, k)?;
+        if header.encoding != Encoding::Raw {
+            return Err(BloomError::InvalidFormat(
+                "compressed bits can't be looked up in place",
+            ));
+        }
+        check_raw(bits, header.m)?;
+        Ok((header, bits))
+    }
+
+    /// Reads the header of a filter with bits in any encoding, checks that it was written with `k` hashes,
+    /// and returns it along with the encoded bits that follow it.
+    fn read_any(bytes: &[u8], k: usize) -> Result<(Header, &[u8]), BloomError> {
+        let (header, bits) = Header::parse(bytes)?;
+        if header.k != k {
+            return Err(BloomError::ConflictingHashes {
+                requested: header.k,
+                hashers: k,
+            });
+        }
+        Ok((header, bits))
+    }
+
+    /// Reads the header of a filter with bits in any encoding, and returns it along with the encoded bits that follow it.
+    fn parse(bytes: &[u8]) -> Result<(Header, &[u8]), BloomError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(BloomError::InvalidFormat("too short to hold a header"));
+        }
+        if &bytes[0..4] != MAGIC {
+            return Err(BloomError::InvalidFormat("not a serialized filter"));
+        }
+        if bytes[4] != VERSION {
+            return Err(BloomError::InvalidFormat("unsupported version"));
+        }
+        let encoding = match (bytes[5], bytes[6]) {
+            (RAW, 0) => Encoding::Raw,
+            (RICE, r) if r <= MAX_RICE_PARAMETER => Encoding::Rice(r),
+            _ => return Err(BloomError::InvalidFormat("unsupported encoding")),
+        };
+        let k = crate::error::check_k(read_usize(&bytes[8..16])?)?;
+        let m = crate::error::check_m(read_usize(&bytes[16..24])?)?;
+        let count = match read_u64(&bytes[24..32]) {
+            UNTRACKED => None,
+            count => Some(to_usize(count)?),
+        };
+        let header = Header {
+            k,
+            m,
+            count,
+            encoding,
+        };
+        Ok((header, &bytes[HEADER_LEN..]))
+    }
+}
+
+/// Reads the number of hashes (`k`) a serialized filter was written with,
+/// so that it can be deserialized with hashers that perform as many.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::BloomFilter;
+/// use bloom_filter::ReHasher;
+/// use murmur3::murmur3_32::MurmurHasher;
+/// let bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(3));
+/// assert_eq!(bloom_filter::format::hashes(&bf.to_bytes()), Ok(3));
+/// ```
+///
+/// # Errors
+/// If the bytes don't start with the header of a serialized filter, or it has more than `MAX_HASHES` hashes.
+pub fn hashes(bytes: &[u8]) -> Result<usize, BloomError> {
+    let (header, _) = Header::parse(bytes)?;
+    if header.k > MAX_HASHES {
+        return Err(BloomError::InvalidFormat("more hashes than MAX_HASHES"));
+    }
+    Ok(header.k)
+}
+
+/// Reads the number of bits (`m`) of a serialized filter, without decoding them.
+pub(crate) fn num_bits(bytes: &[u8]) -> Result<usize, BloomError> {
+    Header::parse(bytes).map(|(header, _)| header.m)
+}
+
+/// Reads the number of bytes of raw bits that follow a header, so a reader knows where the filter ends,
+/// or `None` if the bits are Rice coded, in which case they run to the end of the bytes.
+#[cfg(feature = "async")]
+pub(crate) fn raw_bits_len(bytes: &[u8]) -> Result<Option<usize>, BloomError> {
+    Header::parse(bytes).map(|(header, _)| match header.encoding {
+        Encoding::Raw => Some(header.m.div_ceil(8)),
+        Encoding::Rice(_) => None,
+    })
+}
+
+/// Checks that raw bits hold `m` bits.
+fn check_raw(bits: &[u8], m: usize) -> Result<(), BloomError> {
+    if bits.len() != m.div_ceil(8) {
+        return Err(BloomError::InvalidFormat(
+            "the number of bytes doesn't match the number of bits",
+        ));
+    }
+    if !m.is_multiple_of(8) && bits[bits.len() - 1] >> (m % 8) != 0 {
+        return Err(BloomError::InvalidFormat("bits past the end are set"));
+    }
+    Ok(())
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+fn read_usize(bytes: &[u8]) -> Result<usize, BloomError> {
+    to_usize(read_u64(bytes))
+}
+
+fn to_usize(n: u64) -> Result<usize, BloomError> {
+    usize::try_from(n).map_err(|_| BloomError::InvalidFormat("too large for this platform"))
+}
+
+/// Tests bit `i` of serialized bits.
+pub(crate) fn get(bits: &[u8], i: usize) -> bool {
+    bits[i / 8] >> (i % 8) & 1 == 1
+}
+
+/// Serializes a filter's bits.
+pub(crate) fn encode<S: BitStore + ?Sized>(k: usize, bits: &S, count: Option<usize>) -> Vec<u8> {
+    let m = bits.len();
+    let mut out = Vec::with_capacity(HEADER_LEN + m.div_ceil(8));
+    let header = Header {
+        k,
+        m,
+        count,
+        encoding: Encoding::Raw,
+    };
+    header.write(&mut out);
+    out.extend_from_slice(&bits.as_bytes());
+    out
+}
+
+/// Serializes a filter's bits, Rice coding them if that takes fewer bytes than leaving them raw.
+pub(crate) fn encode_compact<S: BitStore + ?Sized>(
+    k: usize,
+    bits: &S,
+    count: Option<usize>,
+) -> Vec<u8> {
+    let m = bits.len();
+    let ones = bits.count_ones();
+    let r = rice_parameter(m, ones);
+    // Each gap, including the one up to `m`, takes `r + 1` bits besides its 1s, which add up to at most `m >> r`.
+    let estimate = 8 + ((ones + 1) * (r as usize + 1) + (m >> r)).div_ceil(8);
+    if estimate >= m.div_ceil(8) {
+        return encode(k, bits, count);
+    }
+    let mut out = Vec::with_capacity(HEADER_LEN + estimate);
+    let header = Header {
+        k,
+        m,
+        count,
+        encoding: Encoding::Rice(r),
+    };
+    header.write(&mut out);
+    out.extend_from_slice(&(ones as u64).to_le_bytes());
+    let mut writer = BitWriter { out, len: 0 };
+    let mut next = 0;
+    set_bits(&bits.as_bytes()).for_each(|i| {
+        writer.push_gap(i - next, r);
+        next = i + 1;
+    });
+    writer.push_gap(m - next, r);
+    writer.out
+}
+
+/// Picks the Rice parameter for `ones` set bits spread over `m` bits.
+/// The gaps between them are close to geometrically distributed, which `r = log2(ln(2) * m / ones)` suits best.
+fn rice_parameter(m: usize, ones: usize) -> u8 {
+    let mean_gap = m as f64 / ones.max(1) as f64;
+    (mean_gap * core::f64::consts::LN_2)
+        .log2()
+        .floor()
+        .clamp(0.0, MAX_RICE_PARAMETER as f64) as u8
+}
+
+/// The indices of the set bits of raw bits.
+fn set_bits(bytes: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, &byte)| byte != 0)
+        .flat_map(|(i, &byte)| {
+            (0..8)
+                .filter(move |b| byte >> b & 1 == 1)
+                .map(move |b| i * 8 + b)
+        })
+}
+
+/// Packs bits into bytes, lowest first.
+struct BitWriter {
+    out: Vec<u8>,
+    /// The number of bits pushed.
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.out.push(0);
+        }
+        if bit {
+            *self.out.last_mut().unwrap() |= 1 << (self.len % 8);
+        }
+        self.len += 1;
+    }
+
+    /// Rice codes a gap.
+    fn push_gap(&mut self, gap: usize, r: u8) {
+        (0..gap >> r).for_each(|_| self.push(true));
+        self.push(false);
+        (0..r).for_each(|b| self.push(gap >> b & 1 == 1));
+    }
+}
+
+/// Unpacks bits from bytes, lowest first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// The number of bits read.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn next(&mut self) -> Result<bool, BloomError> {
+        if self.pos == self.bytes.len() * 8 {
+            return Err(BloomError::InvalidFormat("the coded bits are truncated"));
+        }
+        self.pos += 1;
+        Ok(get(self.bytes, self.pos - 1))
+    }
+
+    /// Reads a Rice coded gap, failing if it is more than `max`.
+    fn gap(&mut self, r: u8, max: usize) -> Result<usize, BloomError> {
+        let mut high = 0usize;
+        while self.next()? {
+            high += 1;
+            // Stops runs of 1s that would overflow, or run past the end, from being read to the end.
+            if high > max >> r {
+                return Err(BloomError::InvalidFormat("a set bit is past the end"));
+            }
+        }
+        let mut gap = high << r;
+        for b in 0..r {
+            gap |= (self.next()? as usize) << b;
+        }
+        Ok(gap)
+    }
+}
+
+/// Decodes Rice coded bits into a bitvec of `m` bits.
+fn rice_decode(coded: &[u8], m: usize, r: u8) -> Result<BitVec, BloomError> {
+    if coded.len() < 8 {
+        return Err(BloomError::InvalidFormat("the coded bits are truncated"));
+    }
+    let ones = to_usize(read_u64(&coded[..8]))?;
+    let mut reader = BitReader {
+        bytes: &coded[8..],
+        pos: 0,
+    };
+    let past_end = BloomError::InvalidFormat("a set bit is past the end");
+    // The set bits are read before the bitvec is allocated, so `m` is only trusted once the gap
+    // after them shows the coded bits reach it. Each index takes at least one coded bit,
+    // so they take no more memory than the coded bits would if every one was set.
+    let mut set = Vec::new();
+    let mut next = 0usize;
+    for _ in 0..ones {
+        let gap = reader.gap(r, m)?;
+        let i = next.checked_add(gap).filter(|&i| i < m).ok_or(past_end)?;
+        set.push(i);
+        next = i + 1;
+    }
+    if next.checked_add(reader.gap(r, m)?) != Some(m) {
+        return Err(BloomError::InvalidFormat(
+            "the coded bits don't reach the number of bits",
+        ));
+    }
+    let padding = &reader.bytes[reader.pos / 8..];
+    if reader.bytes.len() != reader.pos.div_ceil(8)
+        || padding.first().is_some_and(|&b| b >> (reader.pos % 8) != 0)
+    {
+        return Err(BloomError::InvalidFormat(
+            "the coded bits are followed by more bits",
+        ));
+    }
+    let mut bit_vec = BitVec::from_elem(m, false);
+    set.into_iter().for_each(|i| bit_vec.set(i, true));
+    Ok(bit_vec)
+}
+
+/// Deserializes a filter's bits, in any encoding, checking that they were made by hashers performing `k` hashes.
+pub(crate) fn decode(bytes: &[u8], k: usize) -> Result<(BitVec, Option<usize>), BloomError> {
+    let (header, bits) = Header::read_any(bytes, k)?;
+    let bit_vec = match header.encoding {
+        Encoding::Raw => {
+            check_raw(bits, header.m)?;
+            bits_to_bit_vec(bits, header.m)
+        }
+        Encoding::Rice(r) => rice_decode(bits, header.m, r)?,
+    };
+    Ok((bit_vec, header.count))
+}
+
+/// Copies the first `m` serialized bits into a bitvec.
+pub(crate) fn bits_to_bit_vec(bits: &[u8], m: usize) -> BitVec {
+    let mut bit_vec = BitVec::from_elem(m, false);
+    // Safety: the length of the storage isn't changed, and the bits past `m` are masked off below,
+    // so the vec's unused bits stay unset.
+    let storage = unsafe { bit_vec.storage_mut() };
+    storage
+        .iter_mut()
+        .zip(bits.chunks(4))
+        .for_each(|(block, chunk)| {
+            let mut buf = [0; 4];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            *block = u32::from_le_bytes(buf);
+        });
+    if let Some(last) = storage.last_mut().filter(|_| !m.is_multiple_of(32)) {
+        *last &= (1 << (m % 32)) - 1;
+    }
+    bit_vec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for &m in &[1, 7, 8, 9, 31, 32, 33, 100, 1000] {
+            let bit_vec = BitVec::from_fn(m, |i| i % 3 == 0 || i == m - 1);
+            let bytes = encode(4, &bit_vec, Some(12));
+            assert_eq!(bytes.len(), HEADER_LEN + m.div_ceil(8));
+            let (_, bits) = Header::read(&bytes, 4).unwrap();
+            (0..m).for_each(|i| assert_eq!(get(bits, i), bit_vec[i]));
+            assert_eq!(decode(&bytes, 4), Ok((bit_vec, Some(12))));
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let mut bit_vec = BitVec::from_elem(10, false);
+        bit_vec.set(0, true);
+        bit_vec.set(9, true);
+        let bytes = encode(3, &bit_vec, None);
+        assert_eq!(&bytes[0..8], b"BLMF\x01\x00\x00\x00");
+        assert_eq!(&bytes[8..16], &3u64.to_le_bytes());
+        assert_eq!(&bytes[16..24], &10u64.to_le_bytes());
+        assert_eq!(&bytes[24..32], &u64::MAX.to_le_bytes());
+        assert_eq!(&bytes[32..], &[0b0000_0001, 0b0000_0010]);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let bytes = encode(3, &BitVec::from_elem(10, false), None);
+        assert_eq!(
+            decode(&bytes[..20], 3).err(),
+            Some(BloomError::InvalidFormat("too short to hold a header"))
+        );
+        assert_eq!(
+            decode(&bytes[..33], 3).err(),
+            Some(BloomError::InvalidFormat(
+                "the number of bytes doesn't match the number of bits"
+            ))
+        );
+        assert_eq!(
+            decode(&bytes, 2).err(),
+            Some(BloomError::ConflictingHashes {
+                requested: 3,
+                hashers: 2
+            })
+        );
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(
+            decode(&bad, 3).err(),
+            Some(BloomError::InvalidFormat("not a serialized filter"))
+        );
+        let mut bad = bytes.clone();
+        bad[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            hashes(&bad),
+            Err(BloomError::InvalidFormat("more hashes than MAX_HASHES"))
+        );
+        bad[8..16].copy_from_slice(&(MAX_HASHES as u64).to_le_bytes());
+        assert_eq!(hashes(&bad), Ok(MAX_HASHES));
+        bad[8..16].copy_from_slice(&(MAX_HASHES as u64 + 1).to_le_bytes());
+        assert_eq!(decode(&bad, MAX_HASHES + 1).map(|_| ()), Ok(()));
+        let mut bad = bytes.clone();
+        bad[33] = 0b1000_0000;
+        assert_eq!(
+            decode(&bad, 3).err(),
+            Some(BloomError::InvalidFormat("bits past the end are set"))
+        );
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        for &m in &[1, 7, 64, 100, 1000, 100_000] {
+            for &every in &[1, 2, 5, 50, 1000, usize::MAX] {
+                let bit_vec = BitVec::from_fn(m, |i| i % every == every - 1 || i == m / 2);
+                let bytes = encode_compact(4, &bit_vec, Some(3));
+                assert!(bytes.len() <= HEADER_LEN + m.div_ceil(8));
+                assert_eq!(decode(&bytes, 4), Ok((bit_vec, Some(3))), "{} {}", m, every);
+            }
+        }
+    }
+
+    #[test]
+    fn compact_picks_by_density() {
+        let sparse = BitVec::from_fn(100_000, |i| i % 1000 == 0);
+        let bytes = encode_compact(4, &sparse, None);
+        assert_eq!(bytes[5], RICE);
+        assert_eq!(bytes[6], rice_parameter(100_000, 100));
+        assert!(bytes.len() < HEADER_LEN + 8 + 100 * 12 / 8);
+        assert_eq!(
+            Header::read(&bytes, 4).err(),
+            Some(BloomError::InvalidFormat(
+                "compressed bits can't be looked up in place"
+            ))
+        );
+        let dense = BitVec::from_fn(100_000, |i| i % 3 == 0);
+        assert_eq!(encode_compact(4, &dense, None), encode(4, &dense, None));
+    }
+
+    #[test]
+    fn rejects_invalid_rice() {
+        let sparse = BitVec::from_fn(1000, |i| i % 100 == 0);
+        let bytes = encode_compact(4, &sparse, None);
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1], 4).err(),
+            Some(BloomError::InvalidFormat("the coded bits are truncated"))
+        );
+        let mut bad = bytes.clone();
+        bad.push(0);
+        assert_eq!(
+            decode(&bad, 4).err(),
+            Some(BloomError::InvalidFormat(
+                "the coded bits are followed by more bits"
+            ))
+        );
+        let mut bad = bytes.clone();
+        bad[HEADER_LEN] += 1;
+        assert!(decode(&bad, 4).is_err());
+        let mut bad = bytes;
+        bad[6] = MAX_RICE_PARAMETER + 1;
+        assert_eq!(
+            decode(&bad, 4).err(),
+            Some(BloomError::InvalidFormat("unsupported encoding"))
+        );
+    }
+
+    #[test]
+    fn rejects_rice_claiming_more_bits_than_coded() {
+        let forge = |m: u64, ones: u64, coded: &[u8]| {
+            let mut bytes = b"BLMF\x01\x01\x00\x00".to_vec();
+            bytes.extend_from_slice(&3u64.to_le_bytes());
+            bytes.extend_from_slice(&m.to_le_bytes());
+            bytes.extend_from_slice(&UNTRACKED.to_le_bytes());
+            bytes.extend_from_slice(&ones.to_le_bytes());
+            bytes.extend_from_slice(coded);
+            bytes
+        };
+        // Nothing is allocated for the bits before they are found not to be there.
+        assert_eq!(
+            decode(&forge(1 << 62, 0, &[]), 3).err(),
+            Some(BloomError::InvalidFormat("the coded bits are truncated"))
+        );
+        // More set bits than there are coded bits for.
+        assert_eq!(
+            decode(&forge(1 << 62, u64::MAX, &[0; 8]), 3).err(),
+            Some(BloomError::InvalidFormat("the coded bits are truncated"))
+        );
+        // Two empty gaps, the second of which should have been 1 << 62.
+        assert_eq!(
+            decode(&forge(1 << 62, 1, &[0]), 3).err(),
+            Some(BloomError::InvalidFormat(
+                "the coded bits don't reach the number of bits"
+            ))
+        );
+        // A gap of 3 up to `m` of 3.
+        assert_eq!(
+            decode(&forge(3, 0, &[0b0111]), 3),
+            Ok((BitVec::from_elem(3, false), None))
+        );
+    }
+
+    #[test]
+    fn compact_empty_filters_code_every_bit() {
+        let empty = BitVec::from_elem(1 << 20, false);
+        let bytes = encode_compact(4, &empty, None);
+        assert_eq!(bytes[6], MAX_RICE_PARAMETER);
+        // A 1 for every `2^16` bits, then the 0 and low bits.
+        assert_eq!(bytes.len(), HEADER_LEN + 8 + (16 + 1 + 16usize).div_ceil(8));
+        assert_eq!(decode(&bytes, 4), Ok((empty, None)));
+    }
+}
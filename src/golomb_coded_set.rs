@@ -0,0 +1,644 @@
+//! Golomb coded sets, as specified for compact block filters by
+//! [BIP158](https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki).
+//!
+//! Each key is hashed with SipHash-2-4 to a number below `N * M`, where `N` is the number of keys
+//! and `1 / M` is the false positive rate.
+//! The hashes are sorted, and the differences between consecutive hashes are Golomb-Rice coded with the parameter `P`:
+//! each is written as its quotient by `2^P` in unary (that many 1s, then a 0), followed by its low `P` bits,
+//! highest first.
+//!
+//! The set is `N` as a Bitcoin `CompactSize`, followed by the coded differences, packed highest bit first,
+//! with the last byte padded with 0s.
+//! This takes around `P + 1.5` bits per key, compared to the `1.44 * log2(M)` bits per key of a bloom filter
+//! with the same false positive rate, but lookups have to decode the set up to where the key would be.
+
+use crate::error::BloomError;
+
+/// `P` for BIP158's basic block filters.
+pub const BASIC_FILTER_P: u8 = 19;
+/// `M` for BIP158's basic block filters.
+pub const BASIC_FILTER_M: u64 = 784_931;
+
+/// SipHash-2-4 with a 128 bit key.
+fn siphash(key: &[u8; 16], bytes: &[u8]) -> u64 {
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+    fn compress(v: &mut [u64; 4], m: u64) {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    }
+    fn read_le(bytes: &[u8]) -> u64 {
+        let mut buf = [0; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(buf)
+    }
+
+    let (k0, k1) = (read_le(&key[..8]), read_le(&key[8..]));
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        compress(&mut v, read_le(word));
+    }
+    compress(
+        &mut v,
+        read_le(words.remainder()) | (bytes.len() as u64) << 56,
+    );
+    v[2] ^= 0xff;
+    (0..4).for_each(|_| round(&mut v));
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+/// Packs bits into bytes, highest first.
+struct BitWriter {
+    out: Vec<u8>,
+    /// The number of bits pushed.
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.out.push(0);
+        }
+        if bit {
+            *self.out.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+}
+
+/// Unpacks bits from bytes, highest first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    /// The number of bits read.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn next(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.pos / 8)?;
+        let bit = byte & 0x80 >> (self.pos % 8) != 0;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    /// Reads a Golomb-Rice coded number.
+    fn read_golomb_rice(&mut self, p: u8) -> Option<u64> {
+        let mut quotient = 0u64;
+        while self.next()? {
+            quotient += 1;
+        }
+        let mut n = quotient.checked_mul(1 << p)?;
+        for b in (0..p).rev() {
+            n |= (self.next()? as u64) << b;
+        }
+        Some(n)
+    }
+}
+
+fn write_compact_size(n: u64, out: &mut Vec<u8>) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+/// Reads a `CompactSize`, returning it and the number of bytes it took.
+fn read_compact_size(bytes: &[u8]) -> Result<(u64, usize), BloomError> {
+    const TRUNCATED: BloomError = BloomError::InvalidFormat("too short to hold the number of keys");
+    let read = |len: usize| {
+        let field = bytes.get(1..1 + len).ok_or(TRUNCATED)?;
+        let mut buf = [0; 8];
+        buf[..len].copy_from_slice(field);
+        Ok((u64::from_le_bytes(buf), 1 + len))
+    };
+    match bytes.first() {
+        None => Err(TRUNCATED),
+        Some(&0xfd) => read(2),
+        Some(&0xfe) => read(4),
+        Some(&0xff) => read(8),
+        Some(&n) => Ok((n as u64, 1)),
+    }
+}
+
+/// Configures and builds a `GolombCodedSet`.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::GolombCodedSet;
+/// let key = [7; 16];
+/// let set = GolombCodedSet::builder(key)
+///     .golomb_parameter(10)
+///     .inverse_false_positive_rate(1 << 10)
+///     .build(vec!["hello", "there"])
+///     .unwrap();
+/// assert!(set.contains("hello"));
+/// assert!(set.contains_any(&["not here", "there"]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct GolombCodedSetBuilder {
+    key: [u8; 16],
+    p: u8,
+    m: u64,
+}
+
+impl GolombCodedSetBuilder {
+    /// Sets the Golomb-Rice parameter (`P`), which is the number of low bits of each difference that are written out.
+    /// It is best set to around `log2(M)`.
+    pub fn golomb_parameter(mut self, p: u8) -> Self {
+        self.p = p;
+        self
+    }
+
+    /// Sets the inverse of the false positive rate (`M`).
+    pub fn inverse_false_positive_rate(mut self, m: u64) -> Self {
+        self.m = m;
+        self
+    }
+
+    /// Hashes, sorts and codes the keys, leaving out any duplicates.
+    ///
+    /// # Errors
+    /// If the Golomb-Rice parameter is 64 or more, `M` is 0, or `N * M` doesn't fit in a `u64`.
+    pub fn build<I, B>(&self, keys: I) -> Result<GolombCodedSet, BloomError>
+    where
+        I: IntoIterator<Item = B>,
+        B: AsRef<[u8]>,
+    {
+        let p = check_p(self.p)?;
+        let mut keys: Vec<B> = keys.into_iter().collect();
+        keys.sort_unstable_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        keys.dedup_by(|a, b| a.as_ref() == b.as_ref());
+        let n = keys.len() as u64;
+        let set = GolombCodedSet {
+            n,
+            range: checked_range(n, self.m)?,
+            p,
+            key: self.key,
+            bytes: Vec::new(),
+            start: 0,
+        };
+        let mut hashes: Vec<u64> = keys.iter().map(|key| set.hash(key.as_ref())).collect();
+        hashes.sort_unstable();
+
+        let mut bytes = Vec::new();
+        write_compact_size(n, &mut bytes);
+        let start = bytes.len();
+        let mut writer = BitWriter { out: bytes, len: 0 };
+        let mut last = 0;
+        hashes.iter().for_each(|&hash| {
+            let delta = hash - last;
+            (0..delta >> self.p).for_each(|_| writer.push(true));
+            writer.push(false);
+            (0..self.p)
+                .rev()
+                .for_each(|b| writer.push(delta >> b & 1 == 1));
+            last = hash;
+        });
+        Ok(GolombCodedSet {
+            bytes: writer.out,
+            start,
+            ..set
+        })
+    }
+}
+
+fn check_p(p: u8) -> Result<u8, BloomError> {
+    if p < 64 {
+        Ok(p)
+    } else {
+        Err(BloomError::InvalidGolombParameter(p))
+    }
+}
+
+/// Gets `N * M`, which the keys are hashed below, counting an empty set as holding 1 key so hashing stays defined.
+fn checked_range(n: u64, m: u64) -> Result<u64, BloomError> {
+    if m == 0 {
+        return Err(BloomError::InvalidGolombRange { n, m });
+    }
+    n.max(1)
+        .checked_mul(m)
+        .ok_or(BloomError::InvalidGolombRange { n, m })
+}
+
+/// A static set with no false negatives, and a false positive rate of `1 / M`,
+/// which takes less space than a bloom filter with the same false positive rate.
+/// Unlike a bloom filter, nothing can be inserted into it once it is built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GolombCodedSet {
+    /// The number of keys.
+    n: u64,
+    /// `N * M`
+    range: u64,
+    p: u8,
+    key: [u8; 16],
+    bytes: Vec<u8>,
+    /// Where the coded differences start in `bytes`.
+    start: usize,
+}
+
+impl GolombCodedSet {
+    /// Creates a builder for a set hashed with the provided SipHash key, with BIP158's parameters for basic block filters.
+    /// For block filters, the key is the first 16 bytes of the block hash.
+    pub fn builder(key: [u8; 16]) -> GolombCodedSetBuilder {
+        GolombCodedSetBuilder {
+            key,
+            p: BASIC_FILTER_P,
+            m: BASIC_FILTER_M,
+        }
+    }
+
+    /// Reads a set from the bytes returned by `as_bytes`.
+    ///
+    /// # Arguments
+    /// * `bytes` - The set.
+    /// * `key` - The SipHash key it was built with.
+    /// * `p` - The Golomb-Rice parameter it was built with.
+    /// * `m` - The inverse of the false positive rate it was built with.
+    ///
+    /// # Errors
+    /// If the bytes don't hold as many keys as they start by saying they do,
+    /// if they are followed by more than padding, or if `p` or `m` are out of range.
+    pub fn from_bytes(bytes: Vec<u8>, key: [u8; 16], p: u8, m: u64) -> Result<Self, BloomError> {
+        let p = check_p(p)?;
+        let (n, start) = read_compact_size(&bytes)?;
+        let range = checked_range(n, m)?;
+        let set = GolombCodedSet {
+            n,
+            range,
+            p,
+            key,
+            bytes,
+            start,
+        };
+        // Decoding everything up front means lookups never run into malformed bits.
+        let mut reader = set.reader();
+        let mut last = 0u64;
+        for _ in 0..n {
+            last = reader
+                .read_golomb_rice(p)
+                .and_then(|delta| last.checked_add(delta))
+                .filter(|&hash| hash < range)
+                .ok_or(BloomError::InvalidFormat(
+                    "the set holds fewer keys than it says",
+                ))?;
+        }
+        // Only 0s may follow the last key, up to the end of its byte.
+        let rest = reader.pos % 8;
+        let padded = reader.pos / 8 + usize::from(rest != 0);
+        let padding = reader
+            .bytes
+            .get(reader.pos / 8)
+            .map_or(0, |b| b & 0xff >> rest);
+        if reader.bytes.len() != padded || (rest != 0 && padding != 0) {
+            return Err(BloomError::InvalidFormat(
+                "the coded keys are followed by more bits",
+            ));
+        }
+        Ok(set)
+    }
+
+    /// Gets the bytes of the set, to be read with `from_bytes`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Gets the number of keys in the set (`N`).
+    pub fn len(&self) -> usize {
+        self.n as usize
+    }
+
+    /// Checks if the set holds no keys.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Hashes a key to below `N * M`.
+    fn hash(&self, key: &[u8]) -> u64 {
+        ((siphash(&self.key, key) as u128 * self.range as u128) >> 64) as u64
+    }
+
+    fn reader(&self) -> BitReader<'_> {
+        BitReader {
+            bytes: &self.bytes[self.start..],
+            pos: 0,
+        }
+    }
+
+    /// Decodes the hashes of the keys, in order.
+    fn hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        let mut reader = self.reader();
+        let mut last = 0;
+        (0..self.n).map(move |_| {
+            // `from_bytes` and `build` both make sure there are `n` hashes.
+            last += reader.read_golomb_rice(self.p).unwrap_or(0);
+            last
+        })
+    }
+
+    /// Tests to see if the key is in the set.
+    /// This will return false positives, at a rate of `1 / M`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::GolombCodedSet;
+    /// let set = GolombCodedSet::builder([0; 16]).build(vec!["hello"]).unwrap();
+    /// assert!(set.contains("hello"));
+    /// assert!(!set.contains("not here"));
+    /// ```
+    pub fn contains<B: AsRef<[u8]>>(&self, key: B) -> bool {
+        let target = self.hash(key.as_ref());
+        self.hashes()
+            .find(|&hash| hash >= target)
+            .is_some_and(|hash| hash == target)
+    }
+
+    /// Tests to see if any of the keys are in the set, decoding the set only once.
+    /// This will return false positives, at a rate of up to `keys.len() / M`.
+    pub fn contains_any<B: AsRef<[u8]>>(&self, keys: &[B]) -> bool {
+        let mut targets: Vec<u64> = keys.iter().map(|key| self.hash(key.as_ref())).collect();
+        targets.sort_unstable();
+        let mut targets = targets.into_iter().peekable();
+        let mut hashes = self.hashes().peekable();
+        while let (Some(&target), Some(&hash)) = (targets.peek(), hashes.peek()) {
+            if target == hash {
+                return true;
+            } else if target < hash {
+                targets.next();
+            } else {
+                hashes.next();
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// The key of a block filter, which is the first 16 bytes of the block hash, as it is stored rather than displayed.
+    fn block_key(displayed_hash: &str) -> [u8; 16] {
+        let mut hash = hex(displayed_hash);
+        hash.reverse();
+        let mut key = [0; 16];
+        key.copy_from_slice(&hash[..16]);
+        key
+    }
+
+    #[test]
+    fn siphash_vector() {
+        // From the appendix of the SipHash paper.
+        let key: Vec<u8> = (0..16).collect();
+        let mut key_array = [0; 16];
+        key_array.copy_from_slice(&key);
+        let message: Vec<u8> = (0..15).collect();
+        assert_eq!(siphash(&key_array, &message), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn bip158_genesis() {
+        // Block 0 of testnet3, whose only output script is the one of the genesis coinbase.
+        let key = block_key("000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943");
+        let script = hex(
+            "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f3\
+             5504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac",
+        );
+        let set = GolombCodedSet::builder(key).build(vec![&script]).unwrap();
+        assert_eq!(set.as_bytes(), &hex("019dfca8")[..]);
+        assert!(set.contains(&script));
+        let read = GolombCodedSet::from_bytes(hex("019dfca8"), key, BASIC_FILTER_P, BASIC_FILTER_M);
+        assert_eq!(read, Ok(set));
+    }
+
+    /// Reads a `CompactSize` from the front of `bytes`, and moves past it.
+    fn take_compact_size(bytes: &mut &[u8]) -> usize {
+        let (n, len) = read_compact_size(bytes).unwrap();
+        *bytes = &bytes[len..];
+        n as usize
+    }
+
+    fn take<'a>(bytes: &mut &'a [u8], len: usize) -> &'a [u8] {
+        let (taken, rest) = bytes.split_at(len);
+        *bytes = rest;
+        taken
+    }
+
+    /// Gets the output scripts of every transaction in a serialized block, which may hold witness data.
+    fn output_scripts(block: &[u8]) -> Vec<Vec<u8>> {
+        let mut bytes = &block[80..];
+        let mut scripts = Vec::new();
+        for _ in 0..take_compact_size(&mut bytes) {
+            take(&mut bytes, 4); // The version.
+                                 // Transactions always have inputs, so a 0 where their count would be is the segwit marker.
+            let segwit = bytes[0] == 0;
+            if segwit {
+                take(&mut bytes, 2);
+            }
+            let inputs = take_compact_size(&mut bytes);
+            for _ in 0..inputs {
+                take(&mut bytes, 36); // The outpoint.
+                let len = take_compact_size(&mut bytes);
+                take(&mut bytes, len + 4); // The script and sequence.
+            }
+            for _ in 0..take_compact_size(&mut bytes) {
+                take(&mut bytes, 8); // The value.
+                let len = take_compact_size(&mut bytes);
+                scripts.push(take(&mut bytes, len).to_vec());
+            }
+            if segwit {
+                for _ in 0..inputs {
+                    for _ in 0..take_compact_size(&mut bytes) {
+                        let len = take_compact_size(&mut bytes);
+                        take(&mut bytes, len);
+                    }
+                }
+            }
+            take(&mut bytes, 4); // The lock time.
+        }
+        assert!(bytes.is_empty());
+        scripts
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn bip158_vectors() {
+        const OP_RETURN: u8 = 0x6a;
+        // `bip-0158/testnet-19.json` from the BIP repository.
+        // Each row is the height, block hash, block, scripts spent by the block,
+        // previous filter header, basic filter, filter header, and notes.
+        let vectors: Vec<serde_json::Value> =
+            serde_json::from_str(include_str!("../fixtures/bip158/testnet-19.json")).unwrap();
+        let string = |value: &serde_json::Value| value.as_str().unwrap().to_string();
+        for vector in &vectors[1..] {
+            let key = block_key(&string(&vector[1]));
+            let spent = vector[3]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|s| hex(&string(s)));
+            // Every output script but empty and OP_RETURN ones, and every non-empty spent script.
+            let scripts: Vec<Vec<u8>> = output_scripts(&hex(&string(&vector[2])))
+                .into_iter()
+                .filter(|script| script.first().is_some_and(|&op| op != OP_RETURN))
+                .chain(spent.filter(|script| !script.is_empty()))
+                .collect();
+            let filter = hex(&string(&vector[5]));
+            let set = GolombCodedSet::builder(key).build(&scripts).unwrap();
+            assert_eq!(set.as_bytes(), &filter[..], "block {}", vector[0]);
+            scripts
+                .iter()
+                .for_each(|script| assert!(set.contains(script)));
+            let read = GolombCodedSet::from_bytes(filter, key, BASIC_FILTER_P, BASIC_FILTER_M);
+            assert_eq!(read, Ok(set), "block {}", vector[0]);
+        }
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let build = |p, m| {
+            GolombCodedSet::builder([0; 16])
+                .golomb_parameter(p)
+                .inverse_false_positive_rate(m)
+                .build(vec!["a", "b"])
+                .err()
+        };
+        assert_eq!(
+            build(64, 1 << 10),
+            Some(BloomError::InvalidGolombParameter(64))
+        );
+        assert_eq!(
+            build(10, 0),
+            Some(BloomError::InvalidGolombRange { n: 2, m: 0 })
+        );
+        assert_eq!(
+            build(10, u64::MAX),
+            Some(BloomError::InvalidGolombRange { n: 2, m: u64::MAX })
+        );
+        assert_eq!(
+            GolombCodedSet::from_bytes(vec![0], [0; 16], 64, 1).err(),
+            Some(BloomError::InvalidGolombParameter(64))
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let set = GolombCodedSet::builder([1; 16])
+            .build(Vec::<&[u8]>::new())
+            .unwrap();
+        assert_eq!(set.as_bytes(), &[0]);
+        assert!(set.is_empty());
+        assert!(!set.contains("anything"));
+        assert!(!set.contains_any(&["anything"]));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn round_trip() {
+        let keys: Vec<String> = (0..1000).map(|i| format!("key{}", i)).collect();
+        let set = GolombCodedSet::builder([3; 16])
+            .golomb_parameter(8)
+            .inverse_false_positive_rate(256)
+            .build(keys.iter().chain(keys.iter().take(10)))
+            .unwrap();
+        assert_eq!(set.len(), 1000);
+        keys.iter().for_each(|key| assert!(set.contains(key)));
+        let false_positives = (1000..11_000)
+            .filter(|i| set.contains(format!("key{}", i)))
+            .count();
+        assert!(false_positives < 80, "{}", false_positives);
+        // Around P + 1.5 bits per key.
+        assert!(
+            set.as_bytes().len() < 1000 * 10 / 8,
+            "{}",
+            set.as_bytes().len()
+        );
+        let read = GolombCodedSet::from_bytes(set.as_bytes().to_vec(), [3; 16], 8, 256).unwrap();
+        assert_eq!(read, set);
+    }
+
+    #[test]
+    fn contains_any_agrees_with_contains() {
+        let set = GolombCodedSet::builder([5; 16])
+            .golomb_parameter(4)
+            .inverse_false_positive_rate(16)
+            .build((0..100).map(|i| format!("key{}", i)))
+            .unwrap();
+        for start in (0..300).step_by(7) {
+            let batch: Vec<String> = (start..start + 5).map(|i| format!("key{}", i)).collect();
+            assert_eq!(
+                set.contains_any(&batch),
+                batch.iter().any(|key| set.contains(key)),
+                "{}",
+                start
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let set = GolombCodedSet::builder([9; 16])
+            .build(vec!["a", "b", "c"])
+            .unwrap();
+        let read = |bytes: &[u8]| {
+            GolombCodedSet::from_bytes(bytes.to_vec(), [9; 16], BASIC_FILTER_P, BASIC_FILTER_M)
+                .err()
+        };
+        let bytes = set.as_bytes();
+        assert_eq!(
+            read(&[]),
+            Some(BloomError::InvalidFormat(
+                "too short to hold the number of keys"
+            ))
+        );
+        assert_eq!(
+            read(&bytes[..bytes.len() - 2]),
+            Some(BloomError::InvalidFormat(
+                "the set holds fewer keys than it says"
+            ))
+        );
+        let mut longer = bytes.to_vec();
+        longer.push(0);
+        assert_eq!(
+            read(&longer),
+            Some(BloomError::InvalidFormat(
+                "the coded keys are followed by more bits"
+            ))
+        );
+    }
+}
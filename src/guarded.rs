@@ -0,0 +1,383 @@
+//! A filter in front of a key-value store, which skips reading keys that were never put.
+
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::rehasher::ReHasher;
+use core::convert::Infallible;
+use core::fmt;
+use core::hash::Hash;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+
+/// A key-value store that a `BloomGuarded` can sit in front of.
+pub trait Store {
+    /// The keys, which are hashed into the filter.
+    type Key: Hash;
+    /// The values.
+    type Value;
+    /// The error of a failed read or write.
+    type Error;
+
+    /// Reads the value of a key.
+    fn get(&self, key: &Self::Key) -> Result<Option<Self::Value>, Self::Error>;
+    /// Writes the value of a key.
+    fn put(&mut self, key: Self::Key, value: Self::Value) -> Result<(), Self::Error>;
+    /// Removes a key, and its value.
+    fn remove(&mut self, key: &Self::Key) -> Result<(), Self::Error>;
+    /// Calls `f` with every key in the store.
+    fn scan_keys<F: FnMut(&Self::Key)>(&self, f: F) -> Result<(), Self::Error>;
+}
+
+/// An in-memory `Store`, for tests.
+#[derive(Debug, Clone, Default)]
+pub struct HashMapStore<K, V>(pub HashMap<K, V>);
+
+impl<K: Hash + Eq, V: Clone> Store for HashMapStore<K, V> {
+    type Key = K;
+    type Value = V;
+    type Error = Infallible;
+
+    fn get(&self, key: &K) -> Result<Option<V>, Infallible> {
+        Ok(self.0.get(key).cloned())
+    }
+
+    fn put(&mut self, key: K, value: V) -> Result<(), Infallible> {
+        self.0.insert(key, value);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &K) -> Result<(), Infallible> {
+        self.0.remove(key);
+        Ok(())
+    }
+
+    fn scan_keys<F: FnMut(&K)>(&self, f: F) -> Result<(), Infallible> {
+        self.0.keys().for_each(f);
+        Ok(())
+    }
+}
+
+/// The reasons a `BloomGuarded` can't be created.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardError<E> {
+    /// The filter can't be sized with the provided parameters.
+    Bloom(BloomError),
+    /// The store's keys can't be scanned.
+    Store(E),
+}
+
+impl<E> From<BloomError> for GuardError<E> {
+    fn from(e: BloomError) -> Self {
+        GuardError::Bloom(e)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for GuardError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuardError::Bloom(e) => e.fmt(f),
+            GuardError::Store(e) => write!(f, "the store's keys can't be scanned: {}", e),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for GuardError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GuardError::Bloom(e) => Some(e),
+            GuardError::Store(e) => Some(e),
+        }
+    }
+}
+
+/// How the lookups through a `BloomGuarded` went.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GuardStats {
+    /// Lookups that the filter passed on to the store, which found the key.
+    pub hits: usize,
+    /// Lookups that the filter answered without reading the store.
+    pub skips: usize,
+    /// Lookups that the filter passed on to the store, which didn't find the key.
+    pub false_positives: usize,
+    /// The number of times the filter has been rebuilt from the store.
+    pub rebuilds: usize,
+}
+
+/// Wraps a `Store`, keeping a `BloomFilter` of its keys, so that reads of keys that were never put
+/// are answered without reaching the store.
+///
+/// The filter is rebuilt from a scan of the store's keys, sized for twice as many,
+/// once more keys have been put than it was sized for.
+/// Removed keys stay in the filter until it is rebuilt, which can be done with `rebuild`.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::guarded::BloomGuarded;
+/// use bloom_filter::guarded::HashMapStore;
+/// let mut guarded = BloomGuarded::new(HashMapStore::default(), 1000, 0.01).unwrap();
+/// guarded.put("hello", 1).unwrap();
+/// assert_eq!(guarded.get(&"hello"), Ok(Some(1)));
+/// assert_eq!(guarded.get(&"not here"), Ok(None));
+/// assert_eq!(guarded.stats().hits, 1);
+/// assert_eq!(guarded.stats().skips, 1);
+/// ```
+pub struct BloomGuarded<S: Store> {
+    store: S,
+    filter: BloomFilter<S::Key, ReHasher<DefaultHasher>>,
+    p: f64,
+    /// The smallest number of keys the filter is sized for.
+    min_capacity: usize,
+    /// The number of keys the filter is sized for.
+    capacity: usize,
+    /// The number of keys that have been put into the filter since it was built,
+    /// not counting those it already appeared to contain.
+    inserted: usize,
+    hits: AtomicUsize,
+    skips: AtomicUsize,
+    false_positives: AtomicUsize,
+    rebuilds: usize,
+}
+
+impl<S: Store> BloomGuarded<S> {
+    /// Wraps a store, scanning the keys already in it into a filter.
+    ///
+    /// # Arguments
+    /// * `store` - The store to guard.
+    /// * `n` - The number of keys the filter is sized for, at least.
+    /// * `p` - The false positive rate the filter is sized for.
+    ///
+    /// # Errors
+    /// If the store's keys can't be scanned.
+    ///
+    /// # Panics
+    /// If `n` is 0, or `p` is not within `(0, 1)`.
+    pub fn new(store: S, n: usize, p: f64) -> Result<Self, S::Error> {
+        match Self::try_new(store, n, p) {
+            Ok(guarded) => Ok(guarded),
+            Err(GuardError::Store(e)) => Err(e),
+            Err(GuardError::Bloom(e)) => panic!("{}", e),
+        }
+    }
+
+    /// Wraps a store, scanning the keys already in it into a filter,
+    /// returning an error instead of panicking if `n` is 0 or `p` is not within `(0, 1)`.
+    ///
+    /// # Errors
+    /// If `n` is 0, if `p` is not within `(0, 1)`, or if the store's keys can't be scanned.
+    pub fn try_new(store: S, n: usize, p: f64) -> Result<Self, GuardError<S::Error>> {
+        let mut guarded = BloomGuarded {
+            store,
+            filter: BloomFilter::try_optimal_new(n, p)?,
+            p,
+            min_capacity: n,
+            capacity: n,
+            inserted: 0,
+            hits: AtomicUsize::new(0),
+            skips: AtomicUsize::new(0),
+            false_positives: AtomicUsize::new(0),
+            rebuilds: 0,
+        };
+        guarded.scan(n).map_err(GuardError::Store)?;
+        Ok(guarded)
+    }
+
+    /// Reads the value of a key, unless the filter shows it was never put.
+    ///
+    /// # Errors
+    /// If the store fails to read the key.
+    pub fn get(&self, key: &S::Key) -> Result<Option<S::Value>, S::Error> {
+        if !self.filter.contains(key) {
+            self.skips.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }
+        let value = self.store.get(key)?;
+        let counter = match value {
+            Some(_) => &self.hits,
+            None => &self.false_positives,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Ok(value)
+    }
+
+    /// Writes the value of a key, adding the key to the filter.
+    /// If this takes the filter past the number of keys it was sized for, it is rebuilt.
+    ///
+    /// # Errors
+    /// If the store fails to write the key,
+    /// or to scan its keys for a rebuild, in which case the value was still written, and the old filter is kept.
+    pub fn put(&mut self, key: S::Key, value: S::Value) -> Result<(), S::Error> {
+        let new = !self.filter.contains(&key);
+        // Added before the write, so that the filter never misses a key the store has.
+        self.filter.insert(&key);
+        self.store.put(key, value)?;
+        if new {
+            self.inserted += 1;
+            if self.inserted > self.capacity {
+                self.rebuild()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a key from the store. It stays in the filter until the filter is rebuilt.
+    ///
+    /// # Errors
+    /// If the store fails to remove the key.
+    pub fn remove(&mut self, key: &S::Key) -> Result<(), S::Error> {
+        self.store.remove(key)
+    }
+
+    /// Rebuilds the filter from a scan of the store's keys, sized for twice as many keys as it holds,
+    /// which drops removed keys from it.
+    ///
+    /// # Errors
+    /// If the store's keys can't be scanned, in which case the filter is left as it was.
+    pub fn rebuild(&mut self) -> Result<(), S::Error> {
+        self.scan(self.inserted * 2)?;
+        self.rebuilds += 1;
+        Ok(())
+    }
+
+    /// Replaces the filter with one sized for at least `n` keys, holding the keys in the store.
+    fn scan(&mut self, n: usize) -> Result<(), S::Error> {
+        let mut capacity = n.max(self.min_capacity);
+        loop {
+            let mut filter = BloomFilter::optimal_new(capacity, self.p);
+            let mut inserted = 0;
+            self.store.scan_keys(|key| {
+                filter.insert(key);
+                inserted += 1;
+            })?;
+            if inserted <= capacity {
+                self.filter = filter;
+                self.capacity = capacity;
+                self.inserted = inserted;
+                return Ok(());
+            }
+            capacity = inserted * 2;
+        }
+    }
+
+    /// Gets how the lookups so far went.
+    pub fn stats(&self) -> GuardStats {
+        GuardStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            skips: self.skips.load(Ordering::Relaxed),
+            false_positives: self.false_positives.load(Ordering::Relaxed),
+            rebuilds: self.rebuilds,
+        }
+    }
+
+    /// Gets the number of keys the filter is currently sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Gets the guarded store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Unwraps the guarded store, dropping the filter.
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Counts the reads that reach a `HashMapStore`.
+    #[derive(Default)]
+    struct CountingStore {
+        map: HashMapStore<u32, u32>,
+        reads: Cell<usize>,
+    }
+
+    impl Store for CountingStore {
+        type Key = u32;
+        type Value = u32;
+        type Error = Infallible;
+
+        fn get(&self, key: &u32) -> Result<Option<u32>, Infallible> {
+            self.reads.set(self.reads.get() + 1);
+            self.map.get(key)
+        }
+
+        fn put(&mut self, key: u32, value: u32) -> Result<(), Infallible> {
+            self.map.put(key, value)
+        }
+
+        fn remove(&mut self, key: &u32) -> Result<(), Infallible> {
+            self.map.remove(key)
+        }
+
+        fn scan_keys<F: FnMut(&u32)>(&self, f: F) -> Result<(), Infallible> {
+            self.map.scan_keys(f)
+        }
+    }
+
+    #[test]
+    fn skips_missing_keys() {
+        let mut guarded = BloomGuarded::new(CountingStore::default(), 1000, 0.01).unwrap();
+        (0..500).for_each(|i| guarded.put(i, i * 2).unwrap());
+        (0..500).for_each(|i| assert_eq!(guarded.get(&i), Ok(Some(i * 2))));
+        (500..10_500).for_each(|i| assert_eq!(guarded.get(&i), Ok(None)));
+
+        let stats = guarded.stats();
+        assert_eq!(stats.hits, 500);
+        assert_eq!(stats.skips + stats.false_positives, 10_000);
+        assert!(stats.false_positives < 100, "{:?}", stats);
+        assert_eq!(guarded.store().reads.get(), 500 + stats.false_positives);
+        assert_eq!(stats.rebuilds, 0);
+    }
+
+    #[test]
+    fn rebuilds_when_saturated() {
+        let mut guarded = BloomGuarded::new(HashMapStore::default(), 100, 0.01).unwrap();
+        (0..1000).for_each(|i| guarded.put(i, ()).unwrap());
+        assert!(guarded.stats().rebuilds >= 3);
+        assert!(guarded.capacity() >= 1000);
+        (0..1000).for_each(|i| assert_eq!(guarded.get(&i), Ok(Some(()))));
+        (1000..11_000).for_each(|i| assert_eq!(guarded.get(&i), Ok(None)));
+        let stats = guarded.stats();
+        assert_eq!(stats.skips + stats.false_positives, 10_000);
+        assert!(stats.false_positives < 200, "{:?}", stats);
+    }
+
+    #[test]
+    fn scans_existing_keys() {
+        let store = HashMapStore((0..300).map(|i| (i, i)).collect());
+        let guarded = BloomGuarded::new(store, 100, 0.01).unwrap();
+        assert!(guarded.capacity() >= 300);
+        (0..300).for_each(|i| assert_eq!(guarded.get(&i), Ok(Some(i))));
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(
+            BloomGuarded::try_new(HashMapStore::<u32, u32>::default(), 0, 0.01).err(),
+            Some(GuardError::Bloom(BloomError::NoElements))
+        );
+        assert_eq!(
+            BloomGuarded::try_new(HashMapStore::<u32, u32>::default(), 100, 1.0).err(),
+            Some(GuardError::Bloom(BloomError::InvalidFalsePositiveRate(1.0)))
+        );
+    }
+
+    #[test]
+    fn rebuild_drops_removed_keys() {
+        let mut guarded = BloomGuarded::new(HashMapStore::default(), 100, 0.01).unwrap();
+        guarded.put("hello", 1).unwrap();
+        guarded.remove(&"hello").unwrap();
+        assert_eq!(guarded.get(&"hello"), Ok(None));
+        assert_eq!(guarded.stats().false_positives, 1);
+        guarded.rebuild().unwrap();
+        assert_eq!(guarded.get(&"hello"), Ok(None));
+        assert_eq!(guarded.stats().skips, 1);
+    }
+}
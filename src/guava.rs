@@ -0,0 +1,319 @@
+//! Compatibility with Guava's `com.google.common.hash.BloomFilter`,
+//! so that filters written by `BloomFilter.writeTo` on the JVM can be read here, and the other way around.
+//!
+//! The format is big endian, as written by a `DataOutputStream`.
+//!
+//! | Offset | Size             | Field                                                 |
+//! |--------|------------------|-------------------------------------------------------|
+//! | 0      | 1                | The ordinal of the `GuavaStrategy`                    |
+//! | 1      | 1                | The number of hashes (`k`), unsigned                  |
+//! | 2      | 4                | The number of 64 bit words of bits, signed            |
+//! | 6      | `8 * words`      | The words. Bit `i` is `words[i / 64] >> (i % 64) & 1` |
+//!
+//! The bits are laid out like a `Vec<u64>` `BitStore`, which is what filters read from the format are backed by.
+
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use core::convert::TryFrom;
+use core::hash::Hash;
+
+const HEADER_LEN: usize = 6;
+
+/// The ways Guava hashes an element to indices, named after their enum constants in `BloomFilterStrategies`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuavaStrategy {
+    /// Uses 32 bits of the hash, so only the first 2^31 bits of a filter are ever set.
+    /// Guava only writes it for filters created before version 11.0.
+    Murmur128Mitz32 = 0,
+    /// Uses all 128 bits of the hash. This is what `BloomFilter.create` uses.
+    Murmur128Mitz64 = 1,
+}
+
+/// `MurmurHash3_x64_128` with a seed of 0, which is `Hashing.murmur3_128()`, returning the two halves of the hash.
+fn murmur3_x64_128(bytes: &[u8]) -> (u64, u64) {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    fn mix_k1(k1: u64) -> u64 {
+        k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2)
+    }
+    fn mix_k2(k2: u64) -> u64 {
+        k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1)
+    }
+    fn fmix64(mut k: u64) -> u64 {
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        k ^ (k >> 33)
+    }
+    fn read_le(bytes: &[u8]) -> u64 {
+        let mut buf = [0; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(buf)
+    }
+
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let mut blocks = bytes.chunks_exact(16);
+    for block in &mut blocks {
+        h1 ^= mix_k1(read_le(&block[..8]));
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+        h2 ^= mix_k2(read_le(&block[8..]));
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+    let tail = blocks.remainder();
+    h1 ^= mix_k1(read_le(&tail[..tail.len().min(8)]));
+    h2 ^= mix_k2(read_le(&tail[tail.len().min(8)..]));
+    h1 ^= bytes.len() as u64;
+    h2 ^= bytes.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    (h1, h2)
+}
+
+/// Hashes values to indices the way Guava does.
+///
+/// # Note
+/// Guava hashes the bytes a `Funnel` puts into a hasher.
+/// Here, the bytes a value passes to `Hasher::write`, and the integers it writes, in little endian, are hashed instead,
+/// which matches `Funnels.stringFunnel(UTF_8)` for `str` and `String`, `Funnels.byteArrayFunnel()` for `[u8]` and `Vec<u8>`,
+/// and `Funnels.longFunnel()` and `Funnels.integerFunnel()` for `i64` and `i32`.
+/// Single bytes, and lengths written with `write_usize`, don't contribute to the hash.
+#[derive(Debug, Clone)]
+pub struct GuavaHasher {
+    strategy: GuavaStrategy,
+    hashes: u8,
+}
+
+impl GuavaHasher {
+    /// Creates a new GuavaHasher
+    ///
+    /// # Arguments
+    /// * `strategy` - How values are hashed to indices.
+    /// * `hashes` - The number of hashes (`k`), which Guava limits to 255.
+    pub fn new(strategy: GuavaStrategy, hashes: u8) -> Self {
+        GuavaHasher { strategy, hashes }
+    }
+
+    /// Gets how values are hashed to indices.
+    pub fn strategy(&self) -> GuavaStrategy {
+        self.strategy
+    }
+}
+
+impl Default for GuavaHasher {
+    fn default() -> Self {
+        // Guava's default false positive rate of 0.03 takes 5 hashes.
+        GuavaHasher::new(GuavaStrategy::Murmur128Mitz64, 5)
+    }
+}
+
+impl HashToIndices for GuavaHasher {
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
+        let (h1, h2) = murmur3_x64_128(&crate::item_bytes::item_bytes(value));
+        let modulus = modulus as u64;
+        match self.strategy {
+            GuavaStrategy::Murmur128Mitz32 => {
+                let (hash1, hash2) = (h1 as i32, (h1 >> 32) as i32);
+                (1..=self.hashes as i32)
+                    .map(|i| {
+                        let combined = hash1.wrapping_add(i.wrapping_mul(hash2));
+                        let combined = if combined < 0 { !combined } else { combined };
+                        (combined as u64 % modulus) as usize
+                    })
+                    .collect()
+            }
+            GuavaStrategy::Murmur128Mitz64 => (0..self.hashes as u64)
+                .map(|i| {
+                    let combined = h1.wrapping_add(i.wrapping_mul(h2));
+                    ((combined & i64::MAX as u64) % modulus) as usize
+                })
+                .collect(),
+        }
+    }
+}
+
+impl K for GuavaHasher {
+    fn k(&self) -> usize {
+        self.hashes as usize
+    }
+}
+
+impl<T> BloomFilter<T, GuavaHasher, Vec<u64>> {
+    /// Reads a filter written by Guava's `BloomFilter.writeTo`.
+    ///
+    /// # Errors
+    /// If the bytes aren't a filter written by `writeTo`, or the strategy isn't one of the `GuavaStrategy`s.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::guava::GuavaHasher;
+    /// use bloom_filter::guava::GuavaStrategy;
+    /// use bloom_filter::BloomFilter;
+    /// let mut bf = BloomFilter::<&str, _, _>::with_store(vec![0; 150], GuavaHasher::new(GuavaStrategy::Murmur128Mitz64, 7));
+    /// bf.insert(&"hello");
+    /// let bytes = bf.to_guava_bytes();
+    /// let bf = BloomFilter::<&str, _, _>::from_guava_bytes(&bytes).unwrap();
+    /// assert!(bf.contains(&"hello"));
+    /// ```
+    pub fn from_guava_bytes(bytes: &[u8]) -> Result<Self, BloomError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(BloomError::InvalidFormat("too short to hold a header"));
+        }
+        let strategy = match bytes[0] {
+            0 => GuavaStrategy::Murmur128Mitz32,
+            1 => GuavaStrategy::Murmur128Mitz64,
+            _ => return Err(BloomError::InvalidFormat("unsupported strategy")),
+        };
+        crate::error::check_k(bytes[1] as usize)?;
+        let mut len = [0; 4];
+        len.copy_from_slice(&bytes[2..HEADER_LEN]);
+        let num_words = usize::try_from(i32::from_be_bytes(len))
+            .map_err(|_| BloomError::InvalidFormat("the number of words is negative"))?;
+        crate::error::check_m(num_words)?;
+        let words = &bytes[HEADER_LEN..];
+        if words.len() != num_words * 8 {
+            return Err(BloomError::InvalidFormat(
+                "the number of bytes doesn't match the number of words",
+            ));
+        }
+        let words = words
+            .chunks(8)
+            .map(|word| {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(word);
+                u64::from_be_bytes(buf)
+            })
+            .collect();
+        Ok(BloomFilter::from_parts(
+            words,
+            GuavaHasher::new(strategy, bytes[1]),
+        ))
+    }
+
+    /// Writes the filter the way Guava's `BloomFilter.writeTo` does, to be read with `BloomFilter.readFrom`.
+    ///
+    /// # Panics
+    /// If the filter has more than `i32::MAX` words, which Guava can't read.
+    pub fn to_guava_bytes(&self) -> Vec<u8> {
+        let num_words = i32::try_from(self.bits.len()).expect("too many words for Guava");
+        let mut out = Vec::with_capacity(HEADER_LEN + self.bits.len() * 8);
+        out.push(self.k.strategy as u8);
+        out.push(self.k.hashes);
+        out.extend_from_slice(&num_words.to_be_bytes());
+        self.bits
+            .iter()
+            .for_each(|word| out.extend_from_slice(&word.to_be_bytes()));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRINGS_MITZ64: &[u8] = include_bytes!("../fixtures/guava/strings_mitz64.bin");
+    const STRINGS_MITZ32: &[u8] = include_bytes!("../fixtures/guava/strings_mitz32.bin");
+    const LONGS_MITZ64: &[u8] = include_bytes!("../fixtures/guava/longs_mitz64.bin");
+
+    fn items() -> Vec<String> {
+        (0..100).map(|i| format!("item{}", i)).collect()
+    }
+
+    fn longs() -> Vec<i64> {
+        (0..200).map(|i| i * 1_000_003 - 7).collect()
+    }
+
+    #[test]
+    fn murmur3_vectors() {
+        assert_eq!(murmur3_x64_128(b""), (0, 0));
+        assert_eq!(
+            murmur3_x64_128(b"Hello, world!"),
+            (0xf151_2dd1_d2d6_65df, 0x2c32_6650_a8f3_c564)
+        );
+        assert_eq!(
+            murmur3_x64_128(b"Lorem ipsum dolor sit amet, consectetur adipisicing elit"),
+            (0x6fdc_5efd_2cb0_5c6f, 0x4670_4160_fff0_9de6)
+        );
+    }
+
+    #[test]
+    fn reads_strings() {
+        for &(bytes, strategy) in &[
+            (STRINGS_MITZ64, GuavaStrategy::Murmur128Mitz64),
+            (STRINGS_MITZ32, GuavaStrategy::Murmur128Mitz32),
+        ] {
+            let bf = BloomFilter::<String, _, _>::from_guava_bytes(bytes).unwrap();
+            assert_eq!(bf.k.strategy(), strategy);
+            assert_eq!(bf.k(), 7);
+            assert_eq!(bf.num_bits(), 9600);
+            items().iter().for_each(|item| assert!(bf.contains(item)));
+            let false_positives = (100..10_100)
+                .filter(|i| bf.contains(&format!("item{}", i)))
+                .count();
+            assert!(false_positives < 200, "{}", false_positives);
+            assert_eq!(bf.to_guava_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn reads_longs() {
+        let bf = BloomFilter::<i64, _, _>::from_guava_bytes(LONGS_MITZ64).unwrap();
+        longs().iter().for_each(|n| assert!(bf.contains(n)));
+        assert_eq!(bf.to_guava_bytes(), LONGS_MITZ64);
+    }
+
+    #[test]
+    fn writes_like_guava() {
+        let fixture = BloomFilter::<i64, _, _>::from_guava_bytes(LONGS_MITZ64).unwrap();
+        let mut bf: BloomFilter<i64, GuavaHasher, Vec<u64>> = BloomFilter::with_store(
+            vec![0; fixture.bits.len()],
+            GuavaHasher::new(GuavaStrategy::Murmur128Mitz64, fixture.k.hashes),
+        );
+        longs().iter().for_each(|n| bf.insert(n));
+        assert_eq!(bf.to_guava_bytes(), LONGS_MITZ64);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let read = |bytes: &[u8]| BloomFilter::<String, _, _>::from_guava_bytes(bytes).err();
+        assert_eq!(
+            read(&STRINGS_MITZ64[..4]),
+            Some(BloomError::InvalidFormat("too short to hold a header"))
+        );
+        assert_eq!(
+            read(&STRINGS_MITZ64[..STRINGS_MITZ64.len() - 1]),
+            Some(BloomError::InvalidFormat(
+                "the number of bytes doesn't match the number of words"
+            ))
+        );
+        let mut bad = STRINGS_MITZ64.to_vec();
+        bad[0] = 2;
+        assert_eq!(
+            read(&bad),
+            Some(BloomError::InvalidFormat("unsupported strategy"))
+        );
+        let mut bad = STRINGS_MITZ64.to_vec();
+        bad[1] = 0;
+        assert_eq!(read(&bad), Some(BloomError::NoHashes));
+        let mut bad = STRINGS_MITZ64.to_vec();
+        bad[2] = 0x80;
+        assert_eq!(
+            read(&bad),
+            Some(BloomError::InvalidFormat("the number of words is negative"))
+        );
+    }
+}
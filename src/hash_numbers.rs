@@ -33,6 +33,45 @@ pub struct Five<H1, H2, H3, H4, H5>(
     BuildHasherDefault<H5>,
 );
 
+// Derived Clone impls would require the hashers themselves to be Clone,
+// but only the BuildHasherDefaults are held.
+impl<H> Clone for One<H> {
+    fn clone(&self) -> Self {
+        One(self.0.clone())
+    }
+}
+impl<H1, H2> Clone for Two<H1, H2> {
+    fn clone(&self) -> Self {
+        Two(self.0.clone(), self.1.clone())
+    }
+}
+impl<H1, H2, H3> Clone for Three<H1, H2, H3> {
+    fn clone(&self) -> Self {
+        Three(self.0.clone(), self.1.clone(), self.2.clone())
+    }
+}
+impl<H1, H2, H3, H4> Clone for Four<H1, H2, H3, H4> {
+    fn clone(&self) -> Self {
+        Four(
+            self.0.clone(),
+            self.1.clone(),
+            self.2.clone(),
+            self.3.clone(),
+        )
+    }
+}
+impl<H1, H2, H3, H4, H5> Clone for Five<H1, H2, H3, H4, H5> {
+    fn clone(&self) -> Self {
+        Five(
+            self.0.clone(),
+            self.1.clone(),
+            self.2.clone(),
+            self.3.clone(),
+            self.4.clone(),
+        )
+    }
+}
+
 impl<H> K for BuildHasherDefault<H> {
     fn k(&self) -> usize {
         1
@@ -69,18 +108,14 @@ impl<H1, H2, H3, H4, H5> K for Five<H1, H2, H3, H4, H5> {
 }
 
 impl<H: Hasher + Default> HashToIndices for One<H> {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
-        let mut h = self.0.build_hasher();
-        value.hash(&mut h);
-        vec![h.finish() as usize % modulus]
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
+        vec![self.0.hash_one(value) as usize % modulus]
     }
 }
 
 impl<H: BuildHasher + Default> HashToIndices for H {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
-        let mut h = self.build_hasher();
-        value.hash(&mut h);
-        vec![h.finish() as usize % modulus]
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
+        vec![self.hash_one(value) as usize % modulus]
     }
 }
 
@@ -89,16 +124,10 @@ where
     H1: Hasher + Default,
     H2: Hasher + Default,
 {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
-        let mut h1 = self.0.build_hasher();
-        value.hash(&mut h1);
-
-        let mut h2 = self.1.build_hasher();
-        value.hash(&mut h2);
-
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
         vec![
-            h1.finish() as usize % modulus,
-            h2.finish() as usize % modulus,
+            self.0.hash_one(value) as usize % modulus,
+            self.1.hash_one(value) as usize % modulus,
         ]
     }
 }
@@ -108,19 +137,11 @@ where
     H2: Hasher + Default,
     H3: Hasher + Default,
 {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
-        let mut h1 = self.0.build_hasher();
-        value.hash(&mut h1);
-
-        let mut h2 = self.1.build_hasher();
-        value.hash(&mut h2);
-
-        let mut h3 = self.2.build_hasher();
-        value.hash(&mut h3);
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
         vec![
-            h1.finish() as usize % modulus,
-            h2.finish() as usize % modulus,
-            h3.finish() as usize % modulus,
+            self.0.hash_one(value) as usize % modulus,
+            self.1.hash_one(value) as usize % modulus,
+            self.2.hash_one(value) as usize % modulus,
         ]
     }
 }
@@ -131,23 +152,12 @@ where
     H3: Hasher + Default,
     H4: Hasher + Default,
 {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
-        let mut h1 = self.0.build_hasher();
-        value.hash(&mut h1);
-
-        let mut h2 = self.1.build_hasher();
-        value.hash(&mut h2);
-
-        let mut h3 = self.2.build_hasher();
-        value.hash(&mut h3);
-
-        let mut h4 = self.3.build_hasher();
-        value.hash(&mut h4);
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
         vec![
-            h1.finish() as usize % modulus,
-            h2.finish() as usize % modulus,
-            h3.finish() as usize % modulus,
-            h4.finish() as usize % modulus,
+            self.0.hash_one(value) as usize % modulus,
+            self.1.hash_one(value) as usize % modulus,
+            self.2.hash_one(value) as usize % modulus,
+            self.3.hash_one(value) as usize % modulus,
         ]
     }
 }
@@ -159,27 +169,13 @@ where
     H4: Hasher + Default,
     H5: Hasher + Default,
 {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
-        let mut h1 = self.0.build_hasher();
-        value.hash(&mut h1);
-
-        let mut h2 = self.1.build_hasher();
-        value.hash(&mut h2);
-
-        let mut h3 = self.2.build_hasher();
-        value.hash(&mut h3);
-
-        let mut h4 = self.3.build_hasher();
-        value.hash(&mut h4);
-
-        let mut h5 = self.4.build_hasher();
-        value.hash(&mut h5);
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
         vec![
-            h1.finish() as usize % modulus,
-            h2.finish() as usize % modulus,
-            h3.finish() as usize % modulus,
-            h4.finish() as usize % modulus,
-            h5.finish() as usize % modulus,
+            self.0.hash_one(value) as usize % modulus,
+            self.1.hash_one(value) as usize % modulus,
+            self.2.hash_one(value) as usize % modulus,
+            self.3.hash_one(value) as usize % modulus,
+            self.4.hash_one(value) as usize % modulus,
         ]
     }
 }
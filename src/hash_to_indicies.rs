@@ -9,7 +9,7 @@ pub trait HashToIndices: Default {
     /// resulting hash value.
     /// The usizes in the returned vector are only considered indices because they are constrained to
     /// the size of the BitVec used in the BloomFilter.
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize>;
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize>;
 }
 
 pub trait K {
@@ -0,0 +1,71 @@
+//! The bytes of a value, as other implementations of bloom filters hash them.
+
+use core::hash::Hash;
+use core::hash::Hasher;
+
+/// Gathers the bytes of a value, leaving out the length prefixes of slices and the terminators of `str`s,
+/// so that strings and byte slices are hashed as just their bytes.
+/// Integers are written little endian, regardless of the platform.
+#[derive(Default)]
+struct ItemBytes(Vec<u8>);
+
+impl Hasher for ItemBytes {
+    fn finish(&self) -> u64 {
+        // Only the gathered bytes are used.
+        0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes)
+    }
+
+    fn write_u8(&mut self, _: u8) {}
+
+    fn write_usize(&mut self, _: usize) {}
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write(&n.to_le_bytes())
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write(&n.to_le_bytes())
+    }
+}
+
+/// Gets the bytes a value passes to `Hasher::write`, and the integers it writes, in little endian.
+/// Single bytes and lengths written with `write_usize` are left out.
+pub(crate) fn item_bytes<T: Hash + ?Sized>(value: &T) -> Vec<u8> {
+    let mut bytes = ItemBytes::default();
+    value.hash(&mut bytes);
+    bytes.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_and_bytes_alike() {
+        assert_eq!(item_bytes(&"item1"), b"item1");
+        assert_eq!(item_bytes(&"item1".to_string()), b"item1");
+        assert_eq!(item_bytes(&&b"item1"[..]), b"item1");
+        assert_eq!(item_bytes(&b"item1".to_vec()), b"item1");
+        assert_eq!(item_bytes(&0x0102_0304i32), [4, 3, 2, 1]);
+    }
+}
@@ -1,5 +1,3 @@
-#![feature(test)]
-
 #![cfg_attr(not(feature = "std"), no_std)]
 
 
@@ -8,19 +6,65 @@
 //! and number of bits in the filter (`m`).
 //! The false positive rate will increase as `n` rises, and will fall as `k` and `m` rise.
 
-extern crate test;
-
+pub mod atomic_bloom_filter;
+pub mod atomic_counting_bloom_filter;
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod bit_store;
 pub mod bloom_filter;
+pub mod bloom_filter_ref;
+pub mod builder;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod counting_bloom_filter;
 pub mod counting_w_lock_bloom_filter;
+#[cfg(feature = "std")]
+pub mod dedup;
+pub mod error;
+pub mod filter;
+pub mod format;
+pub mod golomb_coded_set;
+pub mod guava;
+#[cfg(feature = "std")]
+pub mod guarded;
 pub mod hash_numbers;
 pub mod hash_to_indicies;
+mod item_bytes;
+pub mod measure;
+#[cfg(feature = "memmap")]
+pub mod mmap_bloom_filter;
+#[cfg(feature = "python")]
+mod python;
+pub mod redis_bloom;
 pub mod rehasher;
+pub mod sizing;
+#[cfg(feature = "net")]
+pub mod sync_protocol;
+#[cfg(feature = "std")]
+pub mod sharded_bloom_filter;
+mod sync;
+pub mod versioned_bloom_filter;
 pub mod w_lock_bloom_filter;
 
+pub use crate::atomic_bloom_filter::AtomicBloomFilter;
+pub use crate::atomic_counting_bloom_filter::AtomicCountingBloomFilter;
+pub use crate::bit_store::BitStore;
 pub use crate::bloom_filter::BloomFilter;
+pub use crate::bloom_filter_ref::BloomFilterRef;
+pub use crate::builder::BloomFilterBuilder;
 pub use crate::counting_bloom_filter::CountingBloomFilter;
 pub use crate::counting_w_lock_bloom_filter::CountingWLockBloomFilter;
+#[cfg(feature = "std")]
+pub use crate::dedup::BloomDedupExt;
+pub use crate::error::BloomError;
+pub use crate::filter::Filter;
+pub use crate::golomb_coded_set::GolombCodedSet;
+#[cfg(feature = "memmap")]
+pub use crate::mmap_bloom_filter::MmapBloomFilter;
+pub use crate::redis_bloom::RedisBloomChain;
+#[cfg(feature = "std")]
+pub use crate::sharded_bloom_filter::ShardedBloomFilter;
+pub use crate::versioned_bloom_filter::VersionedBloomFilter;
 pub use crate::w_lock_bloom_filter::WLockBloomFilter;
 
 pub use crate::rehasher::ReHasher;
@@ -34,7 +78,8 @@ pub use crate::rehasher::ReHasher;
 /// m: number of bits
 pub fn false_positive_rate(k: usize, n: usize, m: usize) -> f64 {
     use core::f64::consts::E;
-    (1.0 - E.powf(((0 - k as isize) * n as isize) as f64 / m as f64)).powi(k as i32)
+    // Computed in f64, as `k * n` can overflow.
+    (1.0 - E.powf(-(k as f64) * n as f64 / m as f64)).powi(k as i32)
 }
 
 /// Gets the required number of bits (`m`) if given `k`, `n` and `p`.
@@ -42,19 +87,47 @@ pub fn false_positive_rate(k: usize, n: usize, m: usize) -> f64 {
 /// # Note
 /// This is useful if you want to choose `k` beforehand for performance reasons,
 /// and you want to know how big the bloom filter will need to be to achieve a desired false positive rate.
-pub fn m_from_knp(k: usize, n: usize, p: f64) -> usize {
-    -((k * n) as f64 / (1f64 - p.powf(1.0 / (k as f64))).ln()) as usize
+///
+/// # Errors
+/// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+/// if `p` is so close to 1 that no bits would be needed, or if more bits would be needed than fit in a `usize`.
+pub fn m_from_knp(k: usize, n: usize, p: f64) -> Result<usize, BloomError> {
+    let k = error::check_k(k)?;
+    let n = error::check_n(n)?;
+    let p = error::check_p(p)?;
+    // Computed in f64, as `k * n` can overflow.
+    error::check_computed_m(-(k as f64 * n as f64) / (1f64 - p.powf(1.0 / (k as f64))).ln())
 }
 
 /// Gets the required number of bits (`m`) assuming an optimal `k`, using `n` and `p`.
-pub fn optimal_m(n: usize, p: f64) -> usize {
+///
+/// # Errors
+/// If `n` is 0, if `p` is not within `(0, 1)`, or if more bits would be needed than fit in a `usize`.
+pub fn optimal_m(n: usize, p: f64) -> Result<usize, BloomError> {
+    let n = error::check_n(n)?;
+    let p = error::check_p(p)?;
     // m = ceil((n * log(p)) / log(1 / pow(2, log(2))))
-    ((n as f64 * p.ln()) / (1.0 / 2f64.powf(2f64.ln())).ln()).ceil() as usize
+    error::check_computed_m((n as f64 * p.ln()) / (1.0 / 2f64.powf(2f64.ln())).ln())
 }
 
 /// This gets the optimal number of hashes to perform (`k`) given `n` and `m`.
-pub fn optimal_k(n: usize, m: usize) -> usize {
-    ((m / n) as f64 * 2f64.ln()).ceil() as usize
+/// Of the whole numbers either side of the ideal `k`, this picks the one with the lower false positive rate.
+/// At least one hash is always needed, so this is never less than 1.
+///
+/// # Errors
+/// If `n` or `m` are 0.
+pub fn optimal_k(n: usize, m: usize) -> Result<usize, BloomError> {
+    let n = error::check_n(n)?;
+    let m = error::check_m(m)?;
+    // k = (m / n) * ln(2)
+    let ideal = m as f64 / n as f64 * 2f64.ln();
+    let below = core::cmp::max(ideal.floor() as usize, 1);
+    let above = core::cmp::max(ideal.ceil() as usize, 1);
+    if false_positive_rate(above, n, m) < false_positive_rate(below, n, m) {
+        Ok(above)
+    } else {
+        Ok(below)
+    }
 }
 
 #[cfg(test)]
@@ -63,570 +136,68 @@ mod tests {
     #[test]
     fn needed_size1() {
         let m = optimal_m(2000, 0.001);
-        assert_eq!(m, 28756)
+        assert_eq!(m, Ok(28756))
     }
 
     #[test]
     fn optimal_k1() {
         let m = 28756;
         let k = optimal_k(2000, m);
-        assert_eq!(k, 10)
+        assert_eq!(k, Ok(10))
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)] // Miri makes `ln` imprecise, which can round `m` up past 10000.
     fn solve_for_m() {
         let p = false_positive_rate(4, 1000, 10000);
         let m = m_from_knp(4, 1000, p);
-        assert_eq!(m, 10000)
+        assert_eq!(m, Ok(10000))
     }
-}
-
-#[cfg(test)]
-mod benches {
-    use super::*;
-    use test::Bencher;
-    use murmur3::murmur3_32::MurmurHasher;
-    mod thousand_elements {
-        use super::*;
-        mod normal {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-        }
-
-        mod counting {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-        }
-
-
-        mod wl {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| assert!(bf.contains(&n)));
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-        }
-
-        mod counting_wl {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    (0..1000)
-                        .for_each(|n| bf.insert(&n));
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| assert!(bf.contains(&n)));
-                })
-            }
 
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                (0..1000)
-                    .for_each(|n| bf.insert(&n));
-                b.iter(|| {
-                    (0..1000)
-                        .for_each(|n| { bf.contains(&n); });
-                })
-            }
-        }
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(optimal_m(0, 0.01), Err(BloomError::NoElements));
+        assert_eq!(
+            optimal_m(100, 0.0),
+            Err(BloomError::InvalidFalsePositiveRate(0.0))
+        );
+        assert_eq!(
+            optimal_m(100, 1.5),
+            Err(BloomError::InvalidFalsePositiveRate(1.5))
+        );
+        assert!(optimal_m(100, f64::NAN).is_err());
+        assert_eq!(optimal_k(0, 1000), Err(BloomError::NoElements));
+        assert_eq!(optimal_k(1000, 0), Err(BloomError::NoBits));
+        assert_eq!(m_from_knp(0, 100, 0.01), Err(BloomError::NoHashes));
+        assert_eq!(
+            m_from_knp(100, 1, 1.0 - f64::EPSILON),
+            Err(BloomError::NoBits)
+        );
     }
-    
-    
-    mod single_element {
-        use super::*;
-
-        mod normal {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let mut bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let bf = BloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-        }
-
-        mod counting {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let mut bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let bf = CountingBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-        }
-
-
-        mod wl {
-            use super::*;
 
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                b.iter(|| {
-                    bf.contains(&0)
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let bf = WLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-        }
-
-        mod counting_wl {
-            use super::*;
-
-            #[bench]
-            fn optimal_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::optimal_new(1000, 0.001);
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_insert(b: &mut Bencher) {
-                b.iter(|| {
-                    let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                    bf.insert(&0);
-                })
-            }
-
-            #[bench]
-            fn k1_get(b: &mut Bencher) {
-                let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(1));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k4_get(b: &mut Bencher) {
-                let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(4));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-
-            #[bench]
-            fn k7_get(b: &mut Bencher) {
-                let bf = CountingWLockBloomFilter::<i32, ReHasher<MurmurHasher>>::with_rate(1000, 0.001, ReHasher::new(7));
-                b.iter(|| {
-                    bf.contains(&0);
-                })
-            }
-        }
+    #[test]
+    fn large_k_and_n_do_not_overflow() {
+        assert_eq!(
+            m_from_knp(1 << 20, 1 << 50, 0.5),
+            Err(BloomError::TooManyBits)
+        );
+        assert_eq!(
+            optimal_m(usize::MAX, f64::MIN_POSITIVE),
+            Err(BloomError::TooManyBits)
+        );
+        assert!((false_positive_rate(3, usize::MAX / 2, 1000) - 1.0).abs() < 1e-12);
     }
 
+    #[test]
+    fn optimal_k_does_not_truncate_the_ratio() {
+        // Ideal k = 4.16, which used to be rounded up to 5.
+        assert_eq!(optimal_k(1000, 6000), Ok(4));
+        // Ideal k = 6.86, which used to be computed from m / n = 9 instead of 9.9.
+        assert_eq!(optimal_k(1000, 9900), Ok(7));
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn optimal_k_is_at_least_1() {
+        assert_eq!(optimal_k(1000, 500), Ok(1));
+    }
+}
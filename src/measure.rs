@@ -0,0 +1,273 @@
+//! Measures the false positive rate filters actually achieve,
+//! to check hashers against the theoretical `false_positive_rate`.
+
+use crate::filter::Filter;
+
+/// The number of keys, none of which were inserted, that are probed in each trial of `measure_fpr`.
+pub const PROBES_PER_TRIAL: usize = 10_000;
+
+/// Measures the false positive rate of the filters made by `filter_factory`.
+///
+/// Each trial inserts `n` pseudo-random keys into a fresh filter,
+/// then probes it with `PROBES_PER_TRIAL` keys that were never inserted.
+/// Keys are never repeated, within or across trials, and are the same every time this is run.
+///
+/// # Arguments
+/// * `filter_factory` - Makes an empty filter for each trial.
+/// * `n` - The number of keys to insert in each trial.
+/// * `trials` - The number of filters to measure.
+///
+/// # Panics
+/// If `trials` is 0.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::BloomFilter;
+/// use bloom_filter::ReHasher;
+/// use bloom_filter::false_positive_rate;
+/// use bloom_filter::measure::measure_fpr;
+/// use murmur3::murmur3_32::MurmurHasher;
+/// let observed = measure_fpr(
+///     || BloomFilter::<u64, ReHasher<MurmurHasher>>::new(10_000, ReHasher::new(7)),
+///     1000,
+///     5,
+/// );
+/// let expected = false_positive_rate(7, 1000, 10_000);
+/// assert!((observed - expected).abs() < expected / 2.0);
+/// ```
+pub fn measure_fpr<F, M>(mut filter_factory: M, n: usize, trials: usize) -> f64
+where
+    F: Filter<u64>,
+    M: FnMut() -> F,
+{
+    assert!(trials > 0, "at least one trial must be run");
+    let mut keys = (0..).map(key);
+    let mut false_positives = 0;
+    for _ in 0..trials {
+        let mut filter = filter_factory();
+        keys.by_ref().take(n).for_each(|k| filter.insert(&k));
+        false_positives += keys
+            .by_ref()
+            .take(PROBES_PER_TRIAL)
+            .filter(|k| filter.contains(k))
+            .count();
+    }
+    false_positives as f64 / (trials * PROBES_PER_TRIAL) as f64
+}
+
+/// Scrambles `i` with SplitMix64's finalizer.
+/// This is a bijection, so distinct `i`s always give distinct keys.
+fn key(i: u64) -> u64 {
+    let mut z = i.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::bit_store::BitStore;
+    use crate::bloom_filter::BloomFilter;
+    use crate::hash_numbers::Five;
+    use crate::hash_numbers::Four;
+    use crate::hash_numbers::One;
+    use crate::hash_numbers::Three;
+    use crate::hash_numbers::Two;
+    use crate::hash_to_indicies::HashToIndices;
+    use crate::hash_to_indicies::K;
+    use crate::rehasher::ReHasher;
+    use core::hash::BuildHasherDefault;
+    use core::hash::Hasher;
+    use hashers::fnv::FNV1aHasher32;
+    use hashers::fnv::FNV1aHasher64;
+    use hashers::jenkins::OAATHasher;
+    use murmur3::murmur3_32::MurmurHasher;
+    use std::borrow::Cow;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+
+    const N: usize = 1000;
+    const TRIALS: usize = 20;
+
+    /// Asserts that the observed false positive rate of filters with `m` bits using `hashers`
+    /// is within 6 standard deviations of the theoretical rate.
+    fn assert_matches_theory<U: HashToIndices + K + Clone>(m: usize, hashers: U) {
+        let k = hashers.k();
+        let observed = measure_fpr(
+            || BloomFilter::<u64, U>::new(m, hashers.clone()),
+            N,
+            TRIALS,
+        );
+        assert_near(observed, crate::false_positive_rate(k, N, m), TRIALS, m, k);
+    }
+
+    /// Asserts that a rate observed over `trials` trials is within 6 standard deviations of `expected`.
+    fn assert_near(observed: f64, expected: f64, trials: usize, m: usize, k: usize) {
+        // Each trial's filter fills up a bit differently,
+        // so the probes aren't quite independent, and a binomial bound is only a guide.
+        let probes = (trials * PROBES_PER_TRIAL) as f64;
+        let bound = 6.0 * (expected * (1.0 - expected) / probes).sqrt() + expected * 0.05;
+        assert!(
+            (observed - expected).abs() <= bound,
+            "m = {}, k = {}: observed {}, expected {} ± {}",
+            m,
+            k,
+            observed,
+            expected,
+            bound
+        );
+    }
+
+    /// Holds only the indices of the set bits, so filters with more bits than fit in memory can be measured.
+    struct SparseBits {
+        len: usize,
+        set: HashSet<usize, BuildHasherDefault<FNV1aHasher64>>,
+    }
+
+    impl BitStore for SparseBits {
+        fn len(&self) -> usize {
+            self.len
+        }
+
+        fn get(&self, i: usize) -> bool {
+            self.set.contains(&i)
+        }
+
+        fn set(&mut self, i: usize, value: bool) {
+            assert!(i < self.len);
+            if value {
+                self.set.insert(i);
+            } else {
+                self.set.remove(&i);
+            }
+        }
+
+        fn count_ones(&self) -> usize {
+            self.set.len()
+        }
+
+        fn as_bytes(&self) -> Cow<'_, [u8]> {
+            unreachable!("the filters being measured are never serialized")
+        }
+    }
+
+    /// Measures filters with `m` bits, `k = 1`, and `n` keys inserted, backed by `SparseBits`.
+    fn measure_sparse<H: Hasher + Default>(m: usize, n: usize) -> f64 {
+        measure_fpr(
+            || {
+                let bits = SparseBits {
+                    len: m,
+                    set: HashSet::default(),
+                };
+                BloomFilter::<u64, ReHasher<H>, SparseBits>::with_store(bits, ReHasher::new(1))
+            },
+            n,
+            1,
+        )
+    }
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys: Vec<u64> = (0..100_000).map(key).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), 100_000);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn rehasher_murmur() {
+        for &k in &[1, 4, 7] {
+            assert_matches_theory(10 * N, ReHasher::<MurmurHasher>::new(k));
+        }
+        assert_matches_theory(3 * N, ReHasher::<MurmurHasher>::new(2));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn rehasher_fnv() {
+        assert_matches_theory(10 * N, ReHasher::<FNV1aHasher32>::new(1));
+        assert_matches_theory(10 * N, ReHasher::<FNV1aHasher64>::new(1));
+    }
+
+    /// The low bits of an FNV hash only depend on the low bits of its state,
+    /// so rehashing the same value gives correlated indices.
+    /// At m = 10000, about 7.5% to 8% of probes are false positives whatever `k` is,
+    /// instead of 3.3% at k = 2, 1.2% at k = 4 and 0.8% at k = 7.
+    /// This checks that it stays several times worse than theory, but no worse than a single hash.
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn rehasher_fnv_multiple_hashes() {
+        let m = 10 * N;
+        let single_hash = crate::false_positive_rate(1, N, m);
+        for &k in &[2, 4, 7] {
+            let expected = crate::false_positive_rate(k, N, m);
+            for &observed in &[
+                measure_fpr(
+                    || BloomFilter::<u64, ReHasher<FNV1aHasher32>>::new(m, ReHasher::new(k)),
+                    N,
+                    TRIALS,
+                ),
+                measure_fpr(
+                    || BloomFilter::<u64, ReHasher<FNV1aHasher64>>::new(m, ReHasher::new(k)),
+                    N,
+                    TRIALS,
+                ),
+            ] {
+                assert!(
+                    observed > 2.0 * expected && observed < single_hash,
+                    "k = {}: observed {}, expected {}, a single hash gives {}",
+                    k,
+                    observed,
+                    expected,
+                    single_hash
+                );
+            }
+        }
+    }
+
+    /// A 32 bit hash only ever sets the first 2^32 bits, so past that,
+    /// the false positive rate is that of a filter with 2^32 bits, whatever `m` is.
+    /// A 64 bit hash of the same keys matches theory.
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn rehasher_fnv_large_m() {
+        let m = 1 << 35;
+        let n = 1 << 21;
+        let truncated_m = 1 << 32;
+        let observed = measure_sparse::<FNV1aHasher32>(m, n);
+        assert!(observed > 4.0 * crate::false_positive_rate(1, n, m));
+        assert_near(observed, crate::false_positive_rate(1, n, truncated_m), 1, m, 1);
+        let observed = measure_sparse::<FNV1aHasher64>(m, n);
+        assert_near(observed, crate::false_positive_rate(1, n, m), 1, m, 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn rehasher_other() {
+        assert_matches_theory(10 * N, ReHasher::<DefaultHasher>::new(7));
+        assert_matches_theory(10 * N, ReHasher::<OAATHasher>::new(7));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn hash_numbers() {
+        assert_matches_theory(5 * N, One::<MurmurHasher>::default());
+        assert_matches_theory(5 * N, BuildHasherDefault::<MurmurHasher>::default());
+        assert_matches_theory(5 * N, Two::<MurmurHasher, FNV1aHasher32>::default());
+        assert_matches_theory(
+            5 * N,
+            Three::<MurmurHasher, FNV1aHasher32, FNV1aHasher64>::default(),
+        );
+        assert_matches_theory(
+            8 * N,
+            Four::<MurmurHasher, FNV1aHasher32, FNV1aHasher64, DefaultHasher>::default(),
+        );
+        assert_matches_theory(
+            8 * N,
+            Five::<MurmurHasher, FNV1aHasher32, FNV1aHasher64, DefaultHasher, OAATHasher>::default(),
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::format;
+use crate::format::Header;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use core::hash::Hash;
+use core::marker::PhantomData;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// A read only bloom filter that answers lookups straight from a memory mapped file
+/// holding a filter serialized by `to_bytes`.
+///
+/// The bits are only paged in as lookups touch them,
+/// and every process that maps the same file shares the same page cache.
+#[derive(Debug)]
+pub struct MmapBloomFilter<T, K> {
+    mmap: Mmap,
+    num_bits: usize,
+    count: Option<usize>,
+    type_info: PhantomData<T>,
+    k: K,
+}
+
+impl<T, U: K> MmapBloomFilter<T, U> {
+    /// Memory maps a file holding a filter serialized by `to_bytes`.
+    ///
+    /// # Arguments
+    /// * `path` - The file to map.
+    /// * `hashers` - The same hashers the filter was populated with.
+    ///
+    /// # Errors
+    /// If the file can't be opened or mapped.
+    /// If it isn't a serialized filter, or was written by a filter with hashers that perform a different number of hashes,
+    /// the error's kind is `InvalidData`, and it wraps a `BloomError`.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated while it is mapped, by this or any other process.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::MmapBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut bf = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(2));
+    /// bf.insert(&"hello");
+    /// let path = std::env::temp_dir().join("bloom_filter_mmap_doctest");
+    /// std::fs::write(&path, bf.to_bytes()).unwrap();
+    /// let mapped = unsafe { MmapBloomFilter::<&str, ReHasher<MurmurHasher>>::open(&path, ReHasher::new(2)) }.unwrap();
+    /// assert!(mapped.contains(&"hello"));
+    /// # drop(mapped);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub unsafe fn open<P: AsRef<Path>>(path: P, hashers: U) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        let (header, _) = Header::read(&mmap, hashers.k())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(MmapBloomFilter {
+            num_bits: header.m,
+            count: header.count,
+            mmap,
+            type_info: PhantomData,
+            k: hashers,
+        })
+    }
+}
+
+impl<T, K> MmapBloomFilter<T, K> {
+    /// Gets the number of bits in the used in the bloom filter.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Gets the number of elements that were inserted, if the filter that was serialized counted them.
+    pub fn count(&self) -> Option<usize> {
+        self.count
+    }
+
+    fn bits(&self) -> &[u8] {
+        &self.mmap[format::HEADER_LEN..]
+    }
+}
+
+impl<T, K> MmapBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices,
+{
+    /// Tests to see if the provided value is in the bloom filter.
+    /// This will return false positives if the bits that are the result of hashing the value are already set.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///   These indices will be used to see if the element has been added.
+    pub fn contains(&self, value: &T) -> bool {
+        let bits = self.bits();
+        self.k
+            .hash_to_indices(value, self.num_bits)
+            .into_iter()
+            .all(|i| format::get(bits, i))
+    }
+}
+
+impl<T, U: K> K for MmapBloomFilter<T, U> {
+    fn k(&self) -> usize {
+        self.k.k()
+    }
+}
+
+#[cfg(all(test, not(miri)))]
+mod tests {
+    use super::*;
+    use crate::bloom_filter::BloomFilter;
+    use crate::error::BloomError;
+    use crate::rehasher::ReHasher;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("bloom_filter_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn agrees_with_the_owned_filter() {
+        let mut bf: BloomFilter<usize, ReHasher<MurmurHasher>> =
+            BloomFilter::new(1000, ReHasher::new(3));
+        (0..100).for_each(|n| bf.insert(&n));
+        let path = temp_path("agrees");
+        std::fs::write(&path, bf.to_bytes()).unwrap();
+        let mapped: MmapBloomFilter<usize, ReHasher<MurmurHasher>> =
+            unsafe { MmapBloomFilter::open(&path, ReHasher::new(3)) }.unwrap();
+        assert_eq!(mapped.num_bits(), 1000);
+        assert_eq!(mapped.count(), None);
+        (0..1000).for_each(|n| assert_eq!(mapped.contains(&n), bf.contains(&n)));
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = temp_path("rejects");
+        std::fs::write(&path, b"not a bloom filter, but long enough to hold a header").unwrap();
+        let err = unsafe {
+            MmapBloomFilter::<usize, ReHasher<MurmurHasher>>::open(&path, ReHasher::new(3))
+        }
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.into_inner().unwrap().downcast_ref::<BloomError>(),
+            Some(&BloomError::InvalidFormat("not a serialized filter"))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
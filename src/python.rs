@@ -0,0 +1,325 @@
+//! The `bloom_filter` Python module, exposing `BloomFilter` and `CountingBloomFilter`
+//! hashed with `ReHasher<MurmurHasher>`.
+//!
+//! Items may be `str`, `bytes` or `int`, and are hashed like `&str`, `&[u8]` and `i64` are in Rust,
+//! so `to_bytes` and `from_bytes` move filters to and from Rust filters of those types.
+//! A `str` and the `bytes` of its UTF-8 encoding are different items.
+
+use crate::bloom_filter::BloomFilter as RustBloomFilter;
+use crate::counting_bloom_filter::CountingBloomFilter as RustCountingBloomFilter;
+use crate::error::BloomError;
+use crate::hash_to_indicies::K;
+use crate::rehasher::ReHasher;
+use core::hash::Hash;
+use core::hash::Hasher;
+use murmur3::murmur3_32::MurmurHasher;
+use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::types::PyInt;
+use pyo3::types::PyString;
+
+type Filter = RustBloomFilter<Item, ReHasher<MurmurHasher>>;
+type CountingFilter = RustCountingBloomFilter<Item, ReHasher<MurmurHasher>>;
+
+/// An item passed from Python, which hashes like the Rust type it was converted to.
+enum Item {
+    Str(String),
+    Bytes(Vec<u8>),
+    Int(i64),
+}
+
+impl Hash for Item {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Item::Str(s) => s.hash(state),
+            Item::Bytes(b) => b.hash(state),
+            Item::Int(n) => n.hash(state),
+        }
+    }
+}
+
+impl Item {
+    fn extract(item: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if item.is_instance_of::<PyString>() {
+            Ok(Item::Str(item.extract()?))
+        } else if item.is_instance_of::<PyBytes>() {
+            Ok(Item::Bytes(item.extract()?))
+        } else if item.is_instance_of::<PyInt>() {
+            Ok(Item::Int(item.extract()?))
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "items must be str, bytes or int, not {}",
+                item.get_type().name()?
+            )))
+        }
+    }
+}
+
+fn value_error(e: BloomError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// Rounds an estimated number of items, which is infinite for a saturated filter, for `__len__`.
+fn estimated_len(estimate: f64) -> PyResult<usize> {
+    if estimate.is_finite() {
+        Ok(estimate.round() as usize)
+    } else {
+        Err(PyValueError::new_err(
+            "every bit is set, so the number of items can't be estimated",
+        ))
+    }
+}
+
+/// Reads a serialized filter with hashers that perform as many hashes as it was written with.
+fn read(bytes: &[u8]) -> Result<Filter, BloomError> {
+    let k = crate::format::hashes(bytes)?;
+    Filter::from_bytes(bytes, ReHasher::new(k))
+}
+
+/// A bloom filter of `str`, `bytes` and `int` items.
+///
+/// `BloomFilter(n, p)` is sized for `n` items with a false positive rate of `p`, like `optimal_new`.
+#[pyclass(module = "bloom_filter")]
+struct BloomFilter(Filter);
+
+#[pymethods]
+impl BloomFilter {
+    #[new]
+    fn new(n: usize, p: f64) -> PyResult<Self> {
+        Self::optimal_new(n, p)
+    }
+
+    /// Creates a filter sized for `n` items with a false positive rate of `p`, with the optimal number of hashes.
+    #[staticmethod]
+    fn optimal_new(n: usize, p: f64) -> PyResult<Self> {
+        Filter::try_optimal_new(n, p)
+            .map(BloomFilter)
+            .map_err(value_error)
+    }
+
+    /// Creates a filter sized for `n` items with a false positive rate of `p`, performing `k` hashes.
+    #[staticmethod]
+    fn with_rate(n: usize, p: f64, k: usize) -> PyResult<Self> {
+        Filter::try_with_rate(n, p, ReHasher::new(k))
+            .map(BloomFilter)
+            .map_err(value_error)
+    }
+
+    /// Reads a filter serialized by `to_bytes`, or by `BloomFilter::to_bytes` in Rust.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        read(bytes).map(BloomFilter).map_err(value_error)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.to_bytes())
+    }
+
+    fn add(&mut self, item: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.0.insert(&Item::extract(item)?);
+        Ok(())
+    }
+
+    fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.0.contains(&Item::extract(item)?))
+    }
+
+    /// The estimated number of distinct items that have been added.
+    fn __len__(&self) -> PyResult<usize> {
+        estimated_len(self.0.estimated_count())
+    }
+
+    fn __or__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        let mut union = self.0.clone();
+        union.union(&other.0).map_err(value_error)?;
+        Ok(BloomFilter(union))
+    }
+
+    fn __and__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        let mut intersection = self.0.clone();
+        intersection.intersect(&other.0).map_err(value_error)?;
+        Ok(BloomFilter(intersection))
+    }
+
+    #[getter]
+    fn num_bits(&self) -> usize {
+        self.0.num_bits()
+    }
+
+    #[getter]
+    fn k(&self) -> usize {
+        self.0.k()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BloomFilter(num_bits={}, k={})",
+            self.0.num_bits(),
+            self.0.k()
+        )
+    }
+}
+
+/// A bloom filter of `str`, `bytes` and `int` items that counts the items added to it.
+///
+/// `CountingBloomFilter(n, p)` is sized for `n` items with a false positive rate of `p`, like `optimal_new`.
+#[pyclass(module = "bloom_filter")]
+struct CountingBloomFilter(CountingFilter);
+
+impl CountingBloomFilter {
+    /// Combines two filters' bits, with the count of the result.
+    fn combine(
+        &self,
+        other: &Self,
+        bits: impl FnOnce(&mut Filter, &Filter) -> Result<(), BloomError>,
+        count: usize,
+    ) -> PyResult<Self> {
+        let mut bloom_filter = self.0.bloom_filter.clone();
+        bits(&mut bloom_filter, &other.0.bloom_filter).map_err(value_error)?;
+        Ok(CountingBloomFilter(RustCountingBloomFilter {
+            bloom_filter,
+            count,
+        }))
+    }
+}
+
+#[pymethods]
+impl CountingBloomFilter {
+    #[new]
+    fn new(n: usize, p: f64) -> PyResult<Self> {
+        Self::optimal_new(n, p)
+    }
+
+    /// Creates a filter sized for `n` items with a false positive rate of `p`, with the optimal number of hashes.
+    #[staticmethod]
+    fn optimal_new(n: usize, p: f64) -> PyResult<Self> {
+        CountingFilter::try_optimal_new(n, p)
+            .map(CountingBloomFilter)
+            .map_err(value_error)
+    }
+
+    /// Creates a filter sized for `n` items with a false positive rate of `p`, performing `k` hashes.
+    #[staticmethod]
+    fn with_rate(n: usize, p: f64, k: usize) -> PyResult<Self> {
+        CountingFilter::try_with_rate(n, p, ReHasher::new(k))
+            .map(CountingBloomFilter)
+            .map_err(value_error)
+    }
+
+    /// Reads a filter serialized by `to_bytes`, or by `CountingBloomFilter::to_bytes` in Rust.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        crate::format::hashes(bytes)
+            .and_then(|k| CountingFilter::from_bytes(bytes, ReHasher::new(k)))
+            .map(CountingBloomFilter)
+            .map_err(value_error)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.0.to_bytes())
+    }
+
+    fn add(&mut self, item: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.0.insert(&Item::extract(item)?);
+        Ok(())
+    }
+
+    fn __contains__(&self, item: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.0.contains(&Item::extract(item)?))
+    }
+
+    /// The estimated number of distinct items that have been added.
+    fn __len__(&self) -> PyResult<usize> {
+        estimated_len(self.0.bloom_filter.estimated_count())
+    }
+
+    /// The union, counting the items added to either filter, including any added to both.
+    fn __or__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        self.combine(&other, Filter::union, self.0.count + other.0.count)
+    }
+
+    /// The intersection, counting the items added to the filter that had fewer added.
+    fn __and__(&self, other: PyRef<'_, Self>) -> PyResult<Self> {
+        self.combine(&other, Filter::intersect, self.0.count.min(other.0.count))
+    }
+
+    /// The number of items that have been added, including duplicates.
+    #[getter]
+    fn count(&self) -> usize {
+        self.0.count
+    }
+
+    /// The chance of a lookup being a false positive, computed from `count`.
+    fn false_positive_chance(&self) -> f64 {
+        self.0.false_positive_chance()
+    }
+
+    #[getter]
+    fn num_bits(&self) -> usize {
+        self.0.num_bits()
+    }
+
+    #[getter]
+    fn k(&self) -> usize {
+        self.0.k()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CountingBloomFilter(num_bits={}, k={}, count={})",
+            self.0.num_bits(),
+            self.0.k(),
+            self.0.count
+        )
+    }
+}
+
+#[pymodule]
+fn bloom_filter(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<BloomFilter>()?;
+    m.add_class::<CountingBloomFilter>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/fixtures/python/rust_strings.bf"
+    );
+
+    #[test]
+    fn items_hash_like_rust_types() {
+        let mut items = Filter::optimal_new(100, 0.01);
+        items.insert(&Item::Str("hello".to_string()));
+        let mut strs = RustBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(100, 0.01);
+        strs.insert(&"hello");
+        assert_eq!(items.to_bytes(), strs.to_bytes());
+
+        let mut items = Filter::optimal_new(100, 0.01);
+        items.insert(&Item::Bytes(b"hello".to_vec()));
+        let mut bytes = RustBloomFilter::<&[u8], ReHasher<MurmurHasher>>::optimal_new(100, 0.01);
+        bytes.insert(&&b"hello"[..]);
+        assert_eq!(items.to_bytes(), bytes.to_bytes());
+        assert_ne!(items.to_bytes(), strs.to_bytes());
+
+        let mut items = Filter::optimal_new(100, 0.01);
+        items.insert(&Item::Int(-42));
+        let mut ints = RustBloomFilter::<i64, ReHasher<MurmurHasher>>::optimal_new(100, 0.01);
+        ints.insert(&-42);
+        assert_eq!(items.to_bytes(), ints.to_bytes());
+    }
+
+    /// The fixture read by `python/tests/test_bloom_filter.py` is what a Rust filter of strings serializes to.
+    /// It was written by `seq -f 'item%g' 0 99 | bloom build --n 1000 --p 0.01`.
+    #[test]
+    fn python_fixture() {
+        let mut bf = RustBloomFilter::<String, ReHasher<MurmurHasher>>::optimal_new(1000, 0.01);
+        (0..100).for_each(|i| bf.insert(&format!("item{}", i)));
+        assert_eq!(std::fs::read(FIXTURE).unwrap(), bf.to_bytes());
+    }
+}
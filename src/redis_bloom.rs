@@ -0,0 +1,771 @@
+//! Compatibility with the scalable bloom filters of [RedisBloom](https://github.com/RedisBloom/RedisBloom),
+//! so that filters can be moved between Redis and this crate with `BF.SCANDUMP` and `BF.LOADCHUNK`.
+//!
+//! A RedisBloom filter is a chain of filters, each holding `growth` times as many elements as the one before it,
+//! with half its false positive rate.
+//! The first reply of `BF.SCANDUMP` is the header, and the rest are the bytes of the filters, in order.
+//! All integers in the header are little endian, and it is packed without any padding.
+//!
+//! | Offset | Size | Field                                         |
+//! |--------|------|-----------------------------------------------|
+//! | 0      | 8    | The number of inserted elements               |
+//! | 8      | 4    | The number of filters                         |
+//! | 12     | 4    | Options, a combination of the `*_OPTION`s     |
+//! | 16     | 4    | Growth                                        |
+//! | 20     | 53   | The first filter, followed by the rest        |
+//!
+//! Each filter is described by:
+//!
+//! | Offset | Size | Field                                                               |
+//! |--------|------|---------------------------------------------------------------------|
+//! | 0      | 8    | The number of bytes                                                 |
+//! | 8      | 8    | The number of bits (`m`)                                            |
+//! | 16     | 8    | The number of elements inserted into it                             |
+//! | 24     | 8    | Its false positive rate (`p`), as an `f64`                          |
+//! | 32     | 8    | The number of bits per element, as an `f64`                         |
+//! | 40     | 4    | The number of hashes (`k`)                                          |
+//! | 44     | 8    | The number of elements it holds before the next is added (`n`)      |
+//! | 52     | 1    | `log2(m)` if `m` was rounded up to a power of 2, otherwise 0        |
+//!
+//! The bits are laid out like the serialized format of this crate, with bit `i` being `bytes[i / 8] >> (i % 8) & 1`.
+
+use crate::bit_store::BitStore;
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use core::convert::TryFrom;
+use core::hash::Hash;
+use std::borrow::Cow;
+
+/// The filters' sizes weren't rounded up to a power of 2.
+pub const NO_ROUND_OPTION: u32 = 1;
+/// The elements are hashed to 64 bits, which RedisBloom has done for every filter created since version 2.0.
+pub const FORCE64_OPTION: u32 = 4;
+/// The chain doesn't grow, and inserting into it fails once it is full.
+pub const NO_SCALING_OPTION: u32 = 8;
+
+/// The number of elements each filter in a chain holds, relative to the one before it, unless another is asked for.
+pub const DEFAULT_GROWTH: u32 = 2;
+
+/// The most bytes returned by one call to `scan_dump`.
+/// `load_chunk` accepts chunks of any size, as long as they don't span filters.
+pub const MAX_CHUNK_LEN: usize = 16 * 1024 * 1024;
+
+const SEED: u64 = 0xc6a4_a793_5bd1_e995;
+const TIGHTENING: f64 = 0.5;
+// The constants RedisBloom sizes its filters with, which are slightly less precise than `core::f64::consts`.
+#[allow(clippy::approx_constant)]
+const LN2: f64 = 0.693_147_180_559_945;
+const LN2_SQUARED: f64 = 0.480_453_013_918_201;
+const HEADER_LEN: usize = 20;
+const LINK_LEN: usize = 53;
+
+/// `MurmurHash64A`, the hash RedisBloom uses.
+fn murmur64a(bytes: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (bytes.len() as u64).wrapping_mul(M);
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = read_u64(chunk).wrapping_mul(M);
+        k ^= k >> R;
+        h ^= k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut buf = [0; 8];
+        buf[..tail.len()].copy_from_slice(tail);
+        h ^= u64::from_le_bytes(buf);
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^ (h >> R)
+}
+
+/// Hashes values to indices the way RedisBloom does.
+///
+/// # Note
+/// Only the bytes a value passes to `Hasher::write`, and the integers it writes, in little endian, are hashed.
+/// This makes `str`, `String`, `[u8]` and `Vec<u8>` hash like RedisBloom hashes the same bytes,
+/// but means that single bytes, and lengths written with `write_usize`, don't contribute to the hash.
+#[derive(Debug, Clone)]
+pub struct RedisHasher {
+    hashes: usize,
+}
+
+impl RedisHasher {
+    /// Creates a new RedisHasher
+    ///
+    /// # Arguments
+    /// * `hashes` - The number of hashes (`k`) of the filter.
+    pub fn new(hashes: usize) -> Self {
+        RedisHasher { hashes }
+    }
+}
+
+impl Default for RedisHasher {
+    fn default() -> Self {
+        // RedisBloom's default false positive rate of 0.01 is halved for the first filter in a chain, which takes 8 hashes.
+        RedisHasher::new(8)
+    }
+}
+
+impl HashToIndices for RedisHasher {
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
+        let bytes = crate::item_bytes::item_bytes(value);
+        let a = murmur64a(&bytes, SEED);
+        let b = murmur64a(&bytes, a);
+        (0..self.hashes as u64)
+            .map(|i| (a.wrapping_add(i.wrapping_mul(b)) % modulus as u64) as usize)
+            .collect()
+    }
+}
+
+impl K for RedisHasher {
+    fn k(&self) -> usize {
+        self.hashes
+    }
+}
+
+/// One of the filters in a chain.
+#[derive(Debug)]
+struct Link<T> {
+    filter: BloomFilter<T, RedisHasher, Vec<u64>>,
+    /// The number of elements inserted into this filter.
+    size: u64,
+    /// The number of elements this filter holds before the next is added.
+    entries: u64,
+    error: f64,
+    bits_per_element: f64,
+}
+
+impl<T> Link<T> {
+    /// Sizes a filter the way RedisBloom does, with a number of bits rounded up to a multiple of 64.
+    fn new(entries: u64, error: f64) -> Self {
+        let bits_per_element = (error.ln() / LN2_SQUARED).abs();
+        let bits = ((entries as f64 * bits_per_element) as u64).max(1);
+        let hashes = (LN2 * bits_per_element).ceil() as usize;
+        Link {
+            filter: BloomFilter::from_parts(
+                vec![0; bits.div_ceil(64) as usize],
+                RedisHasher::new(hashes),
+            ),
+            size: 0,
+            entries,
+            error,
+            bits_per_element,
+        }
+    }
+
+    fn num_bytes(&self) -> usize {
+        self.filter.bits.len() * 8
+    }
+
+    fn write_header(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.num_bytes() as u64).to_le_bytes());
+        out.extend_from_slice(&(BitStore::len(&self.filter.bits) as u64).to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&self.error.to_le_bytes());
+        out.extend_from_slice(&self.bits_per_element.to_le_bytes());
+        out.extend_from_slice(&(self.filter.k.k() as u32).to_le_bytes());
+        out.extend_from_slice(&self.entries.to_le_bytes());
+        // The size was never rounded up to a power of 2.
+        out.push(0);
+    }
+
+    fn read_header(bytes: &[u8]) -> Result<Self, BloomError> {
+        let num_bytes = to_usize(read_u64(&bytes[0..8]))?;
+        let bits = read_u64(&bytes[8..16]);
+        let hashes = read_u32(&bytes[40..44]) as usize;
+        let n2 = bytes[52];
+        if num_bytes == 0
+            || !num_bytes.is_multiple_of(8)
+            || (num_bytes as u64).checked_mul(8) != Some(bits)
+        {
+            return Err(BloomError::InvalidFormat(
+                "the number of bits isn't a multiple of 64 matching the number of bytes",
+            ));
+        }
+        if n2 != 0 && (n2 >= 64 || 1 << n2 != bits) {
+            return Err(BloomError::InvalidFormat(
+                "the number of bits doesn't match its power of 2",
+            ));
+        }
+        Ok(Link {
+            filter: BloomFilter::from_parts(
+                vec![0; num_bytes / 8],
+                RedisHasher::new(crate::error::check_k(hashes)?),
+            ),
+            size: read_u64(&bytes[16..24]),
+            error: f64::from_bits(read_u64(&bytes[24..32])),
+            bits_per_element: f64::from_bits(read_u64(&bytes[32..40])),
+            entries: read_u64(&bytes[44..52]),
+        })
+    }
+
+    /// Copies a chunk of bytes into the bits, starting at byte `offset`.
+    fn write_bytes(&mut self, offset: usize, chunk: &[u8]) {
+        let words = &mut self.filter.bits;
+        chunk.iter().enumerate().for_each(|(i, &byte)| {
+            let shift = (offset + i) % 8 * 8;
+            let word = &mut words[(offset + i) / 8];
+            *word = *word & !(0xff << shift) | (byte as u64) << shift;
+        });
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn to_usize(n: u64) -> Result<usize, BloomError> {
+    usize::try_from(n).map_err(|_| BloomError::InvalidFormat("too large for this platform"))
+}
+
+/// A scalable bloom filter that is laid out, sized and hashed like a RedisBloom filter,
+/// so it can be loaded from `BF.SCANDUMP` replies, and dumped for `BF.LOADCHUNK`.
+///
+/// Once the last filter in the chain holds as many elements as it was sized for, another filter is added
+/// with `growth` times the capacity and half the false positive rate,
+/// which keeps the false positive rate of the whole chain below the one it was created with.
+#[derive(Debug)]
+pub struct RedisBloomChain<T> {
+    links: Vec<Link<T>>,
+    /// The number of inserted elements.
+    size: u64,
+    options: u32,
+    growth: u32,
+}
+
+impl<T> RedisBloomChain<T> {
+    /// Creates a chain like `BF.RESERVE` does, which grows with the default growth of 2.
+    ///
+    /// # Arguments
+    /// * `capacity` - The number of elements the first filter in the chain holds.
+    /// * `p` - The false positive rate of the chain.
+    ///
+    /// # Panics
+    /// If `capacity` is 0, or `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::RedisBloomChain;
+    /// let mut chain = RedisBloomChain::<&str>::new(100, 0.01);
+    /// assert_eq!(chain.insert(&"hello"), Ok(true));
+    /// assert_eq!(chain.insert(&"hello"), Ok(false));
+    /// assert!(chain.contains(&"hello"));
+    /// ```
+    pub fn new(capacity: usize, p: f64) -> Self {
+        Self::try_new(capacity, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates a chain like `BF.RESERVE` does,
+    /// returning an error instead of panicking if `capacity` is 0 or `p` is not within `(0, 1)`.
+    ///
+    /// # Errors
+    /// If `capacity` is 0, or `p` is not within `(0, 1)`.
+    pub fn try_new(capacity: usize, p: f64) -> Result<Self, BloomError> {
+        Self::try_with_options(capacity, p, NO_ROUND_OPTION | FORCE64_OPTION)
+    }
+
+    /// Creates a chain like `BF.RESERVE` with `NONSCALING` does, holding a single filter with the false positive rate `p`.
+    /// Inserting more than `capacity` elements into it fails.
+    ///
+    /// # Panics
+    /// If `capacity` is 0, or `p` is not within `(0, 1)`.
+    pub fn non_scaling(capacity: usize, p: f64) -> Self {
+        Self::try_with_options(
+            capacity,
+            p,
+            NO_ROUND_OPTION | FORCE64_OPTION | NO_SCALING_OPTION,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_with_options(capacity: usize, p: f64, options: u32) -> Result<Self, BloomError> {
+        let capacity = crate::error::check_n(capacity)?;
+        let p = crate::error::check_p(p)?;
+        let p = if options & NO_SCALING_OPTION == 0 {
+            p * TIGHTENING
+        } else {
+            p
+        };
+        Ok(RedisBloomChain {
+            links: vec![Link::new(capacity as u64, p)],
+            size: 0,
+            options,
+            growth: DEFAULT_GROWTH,
+        })
+    }
+
+    /// Gets the number of elements that were inserted.
+    pub fn count(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Gets the number of filters in the chain.
+    pub fn num_filters(&self) -> usize {
+        self.links.len()
+    }
+
+    /// Gets the options the chain was created with, a combination of the `*_OPTION`s.
+    pub fn options(&self) -> u32 {
+        self.options
+    }
+
+    /// Takes the filter out of a chain that hasn't grown past its first filter.
+    ///
+    /// # Errors
+    /// If the chain holds more than one filter, the chain is given back.
+    pub fn into_bloom_filter(mut self) -> Result<BloomFilter<T, RedisHasher, Vec<u64>>, Self> {
+        if self.links.len() == 1 {
+            Ok(self.links.remove(0).filter)
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Creates an empty chain from the first reply of `BF.SCANDUMP`.
+    /// The bits are then loaded with `load_chunk`.
+    ///
+    /// # Errors
+    /// If the header is malformed, or describes filters that weren't created with 64 bit hashes,
+    /// which only RedisBloom versions before 2.0 did.
+    ///
+    /// The filters' bits are allocated as large as the header says they are,
+    /// so a header that isn't trusted should be loaded with `from_scan_dump`, which checks them against the dumped bytes first.
+    pub fn from_header(header: &[u8]) -> Result<Self, BloomError> {
+        Self::from_header_within(header, usize::MAX)
+    }
+
+    /// Creates an empty chain from the first reply of `BF.SCANDUMP`, if its filters hold at most `max_bytes` bytes.
+    fn from_header_within(header: &[u8], max_bytes: usize) -> Result<Self, BloomError> {
+        if header.len() < HEADER_LEN {
+            return Err(BloomError::InvalidFormat("too short to hold a header"));
+        }
+        let num_links = read_u32(&header[8..12]) as usize;
+        if num_links == 0 {
+            return Err(BloomError::InvalidFormat("the chain holds no filters"));
+        }
+        if header.len() != HEADER_LEN + num_links * LINK_LEN {
+            return Err(BloomError::InvalidFormat(
+                "the length doesn't match the number of filters",
+            ));
+        }
+        let options = read_u32(&header[12..16]);
+        if options & FORCE64_OPTION == 0 {
+            return Err(BloomError::InvalidFormat(
+                "only filters hashed to 64 bits are supported",
+            ));
+        }
+        let num_bytes = header[HEADER_LEN..]
+            .chunks(LINK_LEN)
+            .try_fold(0usize, |total, link| {
+                total.checked_add(to_usize(read_u64(&link[0..8])).ok()?)
+            });
+        if num_bytes.is_none_or(|num_bytes| num_bytes > max_bytes) {
+            return Err(BloomError::InvalidFormat(
+                "the filters hold more bytes than were dumped",
+            ));
+        }
+        Ok(RedisBloomChain {
+            links: header[HEADER_LEN..]
+                .chunks(LINK_LEN)
+                .map(Link::read_header)
+                .collect::<Result<_, _>>()?,
+            size: read_u64(&header[0..8]),
+            options,
+            growth: read_u32(&header[16..20]),
+        })
+    }
+
+    /// Creates a chain from the replies of `BF.SCANDUMP`, in the order they were returned.
+    ///
+    /// # Arguments
+    /// * `dump` - The iterator and data of each reply, starting with the header and excluding the final empty reply.
+    ///
+    /// # Errors
+    /// If the replies are missing the header, any of them are malformed,
+    /// or the header describes filters with more bytes than the rest of the replies hold.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::RedisBloomChain;
+    /// let mut chain = RedisBloomChain::<&str>::new(100, 0.01);
+    /// chain.insert(&"hello").unwrap();
+    /// let mut dump = Vec::new();
+    /// let mut iter = 0;
+    /// loop {
+    ///     let (next, data) = chain.scan_dump(iter);
+    ///     if next == 0 {
+    ///         break;
+    ///     }
+    ///     dump.push((next, data.into_owned()));
+    ///     iter = next;
+    /// }
+    /// let loaded = RedisBloomChain::<&str>::from_scan_dump(dump).unwrap();
+    /// assert!(loaded.contains(&"hello"));
+    /// ```
+    pub fn from_scan_dump<I, B>(dump: I) -> Result<Self, BloomError>
+    where
+        I: IntoIterator<Item = (i64, B)>,
+        B: AsRef<[u8]>,
+    {
+        let mut dump = dump.into_iter();
+        let header = match dump.next() {
+            Some((1, header)) => header,
+            _ => {
+                return Err(BloomError::InvalidFormat(
+                    "the header isn't the first reply",
+                ))
+            }
+        };
+        let chunks: Vec<_> = dump.collect();
+        let num_bytes = chunks.iter().fold(0usize, |total, (_, chunk)| {
+            total.saturating_add(chunk.as_ref().len())
+        });
+        let mut chain = Self::from_header_within(header.as_ref(), num_bytes)?;
+        chunks
+            .iter()
+            .try_for_each(|(iter, chunk)| chain.load_chunk(*iter, chunk.as_ref()))?;
+        Ok(chain)
+    }
+
+    /// Finds the filter holding byte `pos` of the chain's bits, and the offset of the byte within it.
+    fn link_at(&self, pos: usize) -> Option<(usize, usize)> {
+        let mut start = 0;
+        self.links.iter().enumerate().find_map(|(i, link)| {
+            if pos < start + link.num_bytes() {
+                Some((i, pos - start))
+            } else {
+                start += link.num_bytes();
+                None
+            }
+        })
+    }
+
+    /// Loads a chunk of bits returned by `BF.SCANDUMP`, like `BF.LOADCHUNK` does.
+    ///
+    /// # Arguments
+    /// * `iter` - The iterator returned along with the chunk.
+    /// * `chunk` - The chunk.
+    ///
+    /// # Errors
+    /// If the chunk doesn't fit within one of the chain's filters.
+    pub fn load_chunk(&mut self, iter: i64, chunk: &[u8]) -> Result<(), BloomError> {
+        // The iterator is one past the offset of the end of the chunk.
+        let end = usize::try_from(iter)
+            .ok()
+            .filter(|&end| end > chunk.len())
+            .ok_or(BloomError::InvalidFormat(
+                "the iterator is before the chunk",
+            ))?;
+        let (i, offset) = self
+            .link_at(end - chunk.len() - 1)
+            .ok_or(BloomError::InvalidFormat(
+                "the chunk is past the end of the bits",
+            ))?;
+        let link = &mut self.links[i];
+        if chunk.len() > link.num_bytes() - offset {
+            return Err(BloomError::InvalidFormat("the chunk spans filters"));
+        }
+        link.write_bytes(offset, chunk);
+        Ok(())
+    }
+
+    /// Gets the next reply of `BF.SCANDUMP`, to be loaded into Redis with `BF.LOADCHUNK`.
+    ///
+    /// # Arguments
+    /// * `iter` - 0 to start, then the iterator returned by the previous call.
+    ///
+    /// # Returns
+    /// The iterator to continue from and the data, which is the header for the first call.
+    /// Once every chunk has been returned, the iterator is 0.
+    pub fn scan_dump(&self, iter: i64) -> (i64, Cow<'_, [u8]>) {
+        if iter == 0 {
+            return (1, Cow::Owned(self.header()));
+        }
+        let link = usize::try_from(iter - 1)
+            .ok()
+            .and_then(|pos| self.link_at(pos));
+        match link {
+            Some((i, offset)) => {
+                let bytes = self.links[i].filter.bits.as_bytes();
+                let len = MAX_CHUNK_LEN.min(bytes.len() - offset);
+                let chunk = match bytes {
+                    Cow::Borrowed(bytes) => Cow::Borrowed(&bytes[offset..offset + len]),
+                    Cow::Owned(bytes) => Cow::Owned(bytes[offset..offset + len].to_vec()),
+                };
+                (iter + len as i64, chunk)
+            }
+            None => (0, Cow::Borrowed(&[])),
+        }
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.links.len() * LINK_LEN);
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out.extend_from_slice(&(self.links.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.options.to_le_bytes());
+        out.extend_from_slice(&self.growth.to_le_bytes());
+        self.links
+            .iter()
+            .for_each(|link| link.write_header(&mut out));
+        out
+    }
+}
+
+impl<T: Hash> RedisBloomChain<T> {
+    /// Inserts the value, like `BF.ADD` does, if it isn't already in the chain.
+    ///
+    /// # Returns
+    /// If the value was inserted, which it isn't if it, or a false positive, was already in the chain.
+    ///
+    /// # Errors
+    /// If the chain is full and was created without scaling,
+    /// or the next filter would hold more elements than can be counted.
+    pub fn insert(&mut self, value: &T) -> Result<bool, BloomError> {
+        if self.contains(value) {
+            return Ok(false);
+        }
+        let last = &self.links[self.links.len() - 1];
+        if last.size >= last.entries {
+            if self.options & NO_SCALING_OPTION != 0 {
+                return Err(BloomError::Full);
+            }
+            let entries = last
+                .entries
+                .checked_mul(self.growth as u64)
+                .ok_or(BloomError::Full)?;
+            let link = Link::new(entries, last.error * TIGHTENING);
+            self.links.push(link);
+        }
+        let last = self.links.len() - 1;
+        let link = &mut self.links[last];
+        link.filter.insert(value);
+        link.size += 1;
+        self.size += 1;
+        Ok(true)
+    }
+
+    /// Tests to see if the provided value is in any of the chain's filters.
+    /// This will return false positives if the bits that are the result of hashing the value are already set.
+    pub fn contains(&self, value: &T) -> bool {
+        self.links.iter().any(|link| link.filter.contains(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a fixture written by `fixtures/redis_bloom/capture.py`, skipping the `#` lines that record its origin.
+    fn fixture(text: &str) -> Vec<(i64, Vec<u8>)> {
+        text.lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                let (iter, hex) = line.split_once(' ').unwrap();
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                    .collect();
+                (iter.parse().unwrap(), bytes)
+            })
+            .collect()
+    }
+
+    fn dump<T>(chain: &RedisBloomChain<T>) -> Vec<(i64, Vec<u8>)> {
+        let mut replies = Vec::new();
+        let mut iter = 0;
+        loop {
+            let (next, data) = chain.scan_dump(iter);
+            if next == 0 {
+                return replies;
+            }
+            replies.push((next, data.into_owned()));
+            iter = next;
+        }
+    }
+
+    fn items(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("item{}", i)).collect()
+    }
+
+    #[test]
+    fn murmur64a_vectors() {
+        let seed = 0x1234_5678_9747_b28c;
+        assert_eq!(murmur64a(b"", seed), 4971528555162033992);
+        assert_eq!(murmur64a(b"murmur2", seed), 443646041180781888);
+        let bytes: Vec<u8> = (1..255).collect();
+        assert_eq!(murmur64a(&bytes, seed), 528696902255405805);
+    }
+
+    #[test]
+    fn hashes_strings_and_bytes_alike() {
+        let hasher = RedisHasher::new(8);
+        let indices = hasher.hash_to_indices(&"item1", 1024);
+        assert_eq!(hasher.hash_to_indices(&"item1".to_string(), 1024), indices);
+        assert_eq!(hasher.hash_to_indices(&&b"item1"[..], 1024), indices);
+        assert_eq!(hasher.hash_to_indices(&b"item1".to_vec(), 1024), indices);
+    }
+
+    #[test]
+    fn loads_single() {
+        let replies = fixture(include_str!("../fixtures/redis_bloom/single.txt"));
+        let chain = RedisBloomChain::<String>::from_scan_dump(replies.clone()).unwrap();
+        assert_eq!(chain.num_filters(), 1);
+        assert_eq!(chain.count(), 100);
+        items(100)
+            .iter()
+            .for_each(|item| assert!(chain.contains(item)));
+        assert_eq!(dump(&chain), replies);
+        let bf = chain.into_bloom_filter().unwrap();
+        items(100)
+            .iter()
+            .for_each(|item| assert!(bf.contains(item)));
+    }
+
+    #[test]
+    fn loads_scaled() {
+        let replies = fixture(include_str!("../fixtures/redis_bloom/scaled.txt"));
+        let chain = RedisBloomChain::<String>::from_scan_dump(replies.clone()).unwrap();
+        assert_eq!(chain.num_filters(), 2);
+        assert_eq!(chain.count(), 248);
+        items(250)
+            .iter()
+            .for_each(|item| assert!(chain.contains(item)));
+        assert_eq!(dump(&chain), replies);
+        assert!(chain.into_bloom_filter().is_err());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Miri makes `ln` imprecise, which changes the bits per entry in the header.
+    fn dumps_like_redis_bloom() {
+        for &(text, capacity, n) in &[
+            (
+                include_str!("../fixtures/redis_bloom/single.txt"),
+                1000,
+                100,
+            ),
+            (include_str!("../fixtures/redis_bloom/scaled.txt"), 100, 250),
+        ] {
+            let mut chain = RedisBloomChain::new(capacity, 0.01);
+            items(n).iter().for_each(|item| {
+                chain.insert(item).unwrap();
+            });
+            assert_eq!(dump(&chain), fixture(text));
+        }
+    }
+
+    #[test]
+    fn loads_smaller_chunks() {
+        let replies = fixture(include_str!("../fixtures/redis_bloom/scaled.txt"));
+        let mut chain = RedisBloomChain::<String>::from_header(&replies[0].1).unwrap();
+        let mut start = 1;
+        replies[1..].iter().for_each(|(end, bytes)| {
+            bytes.chunks(50).for_each(|chunk| {
+                start += chunk.len() as i64;
+                chain.load_chunk(start, chunk).unwrap();
+            });
+            assert_eq!(start, *end);
+        });
+        assert_eq!(dump(&chain), replies);
+        assert_eq!(
+            chain.load_chunk(start + 1, &[0]),
+            Err(BloomError::InvalidFormat(
+                "the chunk is past the end of the bits"
+            ))
+        );
+        assert_eq!(
+            chain.load_chunk(replies[1].0 + 1, &[0, 0]),
+            Err(BloomError::InvalidFormat("the chunk spans filters"))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let replies = fixture(include_str!("../fixtures/redis_bloom/single.txt"));
+        let header = &replies[0].1;
+        assert_eq!(
+            RedisBloomChain::<String>::from_header(&header[..HEADER_LEN + 1]).err(),
+            Some(BloomError::InvalidFormat(
+                "the length doesn't match the number of filters"
+            ))
+        );
+        let mut bad = header.clone();
+        bad[12] = NO_ROUND_OPTION as u8;
+        assert_eq!(
+            RedisBloomChain::<String>::from_header(&bad).err(),
+            Some(BloomError::InvalidFormat(
+                "only filters hashed to 64 bits are supported"
+            ))
+        );
+        let mut bad = header.clone();
+        bad[HEADER_LEN] += 1;
+        assert!(RedisBloomChain::<String>::from_header(&bad).is_err());
+        let mut bad = header.clone();
+        bad[HEADER_LEN..HEADER_LEN + 8].copy_from_slice(&(u64::MAX - 7).to_le_bytes());
+        bad[HEADER_LEN + 8..HEADER_LEN + 16].copy_from_slice(&(u64::MAX - 63).to_le_bytes());
+        assert_eq!(
+            RedisBloomChain::<String>::from_header(&bad).err(),
+            Some(BloomError::InvalidFormat(
+                "the number of bits isn't a multiple of 64 matching the number of bytes"
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_filters_larger_than_the_dump() {
+        let mut replies = fixture(include_str!("../fixtures/redis_bloom/single.txt"));
+        let mut bad = replies.clone();
+        bad[0].1[HEADER_LEN..HEADER_LEN + 8].copy_from_slice(&(1u64 << 60).to_le_bytes());
+        bad[0].1[HEADER_LEN + 8..HEADER_LEN + 16].copy_from_slice(&(1u64 << 63).to_le_bytes());
+        assert_eq!(
+            RedisBloomChain::<String>::from_scan_dump(bad).err(),
+            Some(BloomError::InvalidFormat(
+                "the filters hold more bytes than were dumped"
+            ))
+        );
+        replies.pop();
+        assert_eq!(
+            RedisBloomChain::<String>::from_scan_dump(replies).err(),
+            Some(BloomError::InvalidFormat(
+                "the filters hold more bytes than were dumped"
+            ))
+        );
+    }
+
+    #[test]
+    fn growth_past_the_largest_count_fails() {
+        let mut replies = fixture(include_str!("../fixtures/redis_bloom/single.txt"));
+        let header = &mut replies[0].1;
+        header[16..20].copy_from_slice(&4u32.to_le_bytes());
+        // Fill the filter, which was sized for 2^62 elements.
+        header[HEADER_LEN + 16..HEADER_LEN + 24].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        header[HEADER_LEN + 44..HEADER_LEN + 52].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        let mut chain = RedisBloomChain::<String>::from_scan_dump(replies).unwrap();
+        assert_eq!(chain.insert(&"more".to_string()), Err(BloomError::Full));
+        assert_eq!(chain.num_filters(), 1);
+    }
+
+    #[test]
+    fn non_scaling_fills_up() {
+        let mut chain = RedisBloomChain::non_scaling(10, 0.01);
+        items(10).iter().for_each(|item| {
+            chain.insert(item).unwrap();
+        });
+        assert_eq!(chain.insert(&"more".to_string()), Err(BloomError::Full));
+        assert_eq!(chain.num_filters(), 1);
+    }
+}
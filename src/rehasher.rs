@@ -8,6 +8,12 @@ use core::hash::Hasher;
 /// A struct when made to hash a value to indices into the bloom filter,
 /// will reuse the same hashbuffer multiple times,
 /// seeding the each iteration with the last's buffer state.
+///
+/// # Note
+/// This relies on the hasher mixing its state well. With FNV, successive indices are correlated,
+/// and the false positive rate is several times worse than `false_positive_rate` predicts;
+/// `measure::measure_fpr` can be used to check a hasher.
+/// Hashers that produce 32 bit hashes can't address more than 2^32 bits.
 pub struct ReHasher<T> {
     k: usize,
     hasher: BuildHasherDefault<T>,
@@ -25,10 +31,16 @@ impl<T> ReHasher<T> {
     }
 }
 
+impl<T> Clone for ReHasher<T> {
+    fn clone(&self) -> Self {
+        ReHasher::new(self.k)
+    }
+}
+
 impl<T: Default> Default for ReHasher<T> {
     fn default() -> Self {
         ReHasher {
-            /// 4 is a good number, but default() isn't really how this should be constructed
+            // 4 is a good number, but default() isn't really how this should be constructed
             k: 4,
             hasher: BuildHasherDefault::default(),
         }
@@ -36,7 +48,7 @@ impl<T: Default> Default for ReHasher<T> {
 }
 
 impl<H: Hasher + Default> HashToIndices for ReHasher<H> {
-    fn hash_to_indices<T: Hash>(&self, value: &T, modulus: usize) -> Vec<usize> {
+    fn hash_to_indices<T: Hash + ?Sized>(&self, value: &T, modulus: usize) -> Vec<usize> {
         let mut h = self.hasher.build_hasher();
         (0..self.k)
             .map(|_| {
@@ -0,0 +1,477 @@
+use crate::bit_store::BitStore;
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use crate::rehasher::ReHasher;
+use bit_vec::BitVec;
+use core::hash::Hash;
+use core::marker::PhantomData;
+
+#[cfg(feature = "parking_lot")]
+use parking_lot::RwLock;
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::RwLock;
+
+/// The number of shards used by the constructors that don't take a shard count.
+pub const DEFAULT_SHARDS: usize = 16;
+
+/// A bloom filter that can be shared across threads, that splits its bits into a number of
+/// independently locked shards.
+///
+/// # Notes
+/// Every value is routed to exactly one shard by the first index it hashes to,
+/// and all of its `k` bits are set within that shard.
+/// Writers that land in different shards never contend with each other,
+/// unlike `WLockBloomFilter`, where every writer waits on the same lock.
+///
+/// The hashing is done before any lock is taken, so the locks are only held while bits are being set or read.
+///
+/// By default, the shards are guarded by `std::sync::RwLock`.
+/// Enabling the `parking_lot` feature swaps these out for `parking_lot::RwLock`.
+///
+/// Because every shard holds `m / shards` bits, `m` is rounded up to the nearest multiple of the shard count.
+#[derive(Debug)]
+pub struct ShardedBloomFilter<T, K> {
+    /// The backing bit vectors.
+    shards: Vec<RwLock<BitVec>>,
+    /// The number of bits in each shard.
+    shard_bits: usize,
+    /// The type information of what the bitvector will accept as input.
+    type_info: PhantomData<T>,
+    /// The generic hashing structure.
+    pub(crate) k: K,
+}
+
+impl<T, H> ShardedBloomFilter<T, ReHasher<H>> {
+    /// Constructs a new ShardedBloomFilter with an optimal ratio of m and k,
+    /// derived from n and p inputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    /// * `shards` - Number of independently locked shards.
+    ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001, 32);
+    /// ```
+    pub fn optimal_new(n: usize, p: f64, shards: usize) -> Self {
+        Self::try_optimal_new(n, p, shards).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new ShardedBloomFilter with an optimal ratio of m and k, derived from n and p inputs,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(10000, 0.001, 0);
+    /// assert_eq!(bf.err(), Some(BloomError::NoShards));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64, shards: usize) -> Result<Self, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        let k = crate::optimal_k(n, m)?;
+        Self::from_parts(m, shards, ReHasher::new(k))
+    }
+}
+
+impl<T, H> ShardedBloomFilter<T, H>
+where
+    H: HashToIndices + K,
+{
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    /// * `hashers` - Hashing to indicies struct. `k` can be acquired from this.
+    /// * `shards` - Number of independently locked shards.
+    ///
+    /// # Panics
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(1), 32);
+    /// ```
+    pub fn with_rate(n: usize, p: f64, hashers: H, shards: usize) -> Self {
+        Self::try_with_rate(n, p, hashers, shards).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_rate(10000, 1.5, ReHasher::new(1), 32);
+    /// assert_eq!(bf.err(), Some(BloomError::InvalidFalsePositiveRate(1.5)));
+    /// ```
+    pub fn try_with_rate(n: usize, p: f64, hashers: H, shards: usize) -> Result<Self, BloomError> {
+        let m = crate::m_from_knp(hashers.k(), n, p)?;
+        Self::from_parts(m, shards, hashers)
+    }
+}
+
+impl<T, K: crate::hash_to_indicies::K> ShardedBloomFilter<T, K> {
+    /// Splits `m` bits into `shards` bit vectors of equal size.
+    fn from_parts(m: usize, shards: usize, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::error::check_m(m)?;
+        crate::error::check_k(hashers.k())?;
+        if shards == 0 {
+            return Err(BloomError::NoShards);
+        }
+        let shard_bits = m.div_ceil(shards);
+        Ok(ShardedBloomFilter {
+            shards: (0..shards)
+                .map(|_| RwLock::new(BitVec::from_elem(shard_bits, false)))
+                .collect(),
+            shard_bits,
+            type_info: PhantomData,
+            k: hashers,
+        })
+    }
+}
+
+impl<T, K> ShardedBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+{
+    /// Creates the bloom filter with a given number of bits, split evenly across `DEFAULT_SHARDS` shards,
+    /// and with a multiple-hashing-to-index function.
+    ///
+    /// # Arguments
+    /// * `m` - Number of bits for the BloomFilter.
+    /// * `hashers` - Hashing to indices structure.
+    ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// ```
+    pub fn new(m: usize, hashers: K) -> Self {
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        Self::from_parts(m, DEFAULT_SHARDS, hashers)
+    }
+
+    /// Creates the bloom filter with a given number of bits, split evenly across `shards` shards,
+    /// and with a multiple-hashing-to-index function.
+    ///
+    /// # Arguments
+    /// * `m` - Number of bits for the BloomFilter.
+    /// * `hashers` - Hashing to indices structure.
+    /// * `shards` - Number of independently locked shards.
+    ///
+    /// # Panics
+    /// If `m`, the `k` of the hashers, or `shards` are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::with_shards(100000, ReHasher::new(1), 64);
+    /// assert_eq!(bf.num_shards(), 64);
+    /// ```
+    pub fn with_shards(m: usize, hashers: K, shards: usize) -> Self {
+        Self::try_with_shards(m, hashers, shards).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits, split evenly across `shards` shards,
+    /// returning an error instead of panicking if either is 0.
+    ///
+    /// # Errors
+    /// If `m`, the `k` of the hashers, or `shards` are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_shards(100000, ReHasher::new(1), 0);
+    /// assert_eq!(bf.err(), Some(BloomError::NoShards));
+    /// ```
+    pub fn try_with_shards(m: usize, hashers: K, shards: usize) -> Result<Self, BloomError> {
+        Self::from_parts(m, shards, hashers)
+    }
+}
+
+impl<T, K> ShardedBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices,
+{
+    /// Gets the number of bits in the used in the bloom filter.
+    pub fn num_bits(&self) -> usize {
+        self.shard_bits * self.shards.len()
+    }
+
+    /// Gets the number of shards the bits are split across.
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Hashes the value, and splits the resulting indices into the shard the value belongs to,
+    /// and the indices within that shard.
+    fn shard_and_indices(&self, value: &T) -> (usize, Vec<usize>) {
+        let mut indices = self.k.hash_to_indices(value, self.num_bits());
+        let shard = indices.first().map(|i| i / self.shard_bits).unwrap_or(0);
+        indices.iter_mut().for_each(|i| *i %= self.shard_bits);
+        (shard, indices)
+    }
+
+    /// Takes multiple hashes of the provided value, takes the hashes modulo the number of bits
+    /// (converting them to indexes) and sets those bits in the shard the value belongs to.
+    /// Only that shard is locked while the bits are set.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// ```
+    pub fn insert(&self, value: &T) {
+        let (shard, indices) = self.shard_and_indices(value);
+        let mut bit_vec = write(&self.shards[shard]);
+        indices.into_iter().for_each(|i| bit_vec.set(i, true));
+    }
+
+    /// Tests to see if the provided value is in the bloom filter.
+    /// This will return false positives if the bits that are the result of hashing the value are already set.
+    /// Likelihood of false positives will increase as the filter fills up.
+    /// This can be mitigated by allocating more bits to the bloom filter, and by increasing the number of hash functions used ('k').
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value to be hashed to create indices into the bloom filter.
+    ///   These indices will be used to see if the element has been added.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.insert(&"there");
+    /// assert!(bf.contains(&"hello"));
+    /// assert!(bf.contains(&"there"));
+    /// assert!(!bf.contains(&"not here"));
+    /// ```
+    pub fn contains(&self, value: &T) -> bool {
+        let (shard, indices) = self.shard_and_indices(value);
+        let bit_vec = read(&self.shards[shard]);
+        indices.into_iter().all(|i| bit_vec[i])
+    }
+}
+
+impl<T, K> ShardedBloomFilter<T, K> {
+    /// Gets the number of bits that are set.
+    /// Shards are locked and counted one at a time, so concurrent inserts may be partially counted.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(1000, ReHasher::new(1));
+    /// assert_eq!(bf.count_ones(), 0);
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.count_ones(), 1);
+    /// ```
+    pub fn count_ones(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| read(shard).count_ones())
+            .sum()
+    }
+
+    /// Gets the fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / (self.shard_bits * self.shards.len()) as f64
+    }
+
+    /// Checks if no bits are set, which means nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read(shard).none())
+    }
+
+    /// Checks if at least `threshold` of the bits are set.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.fill_ratio() >= threshold
+    }
+
+    /// Unsets every bit in place, so the filter can be reused without replacing it wherever it is shared.
+    ///
+    /// # Notes
+    /// Shards are locked and cleared one at a time,
+    /// so values inserted while this runs may or may not survive it, depending on their shard.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::ShardedBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = ShardedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(1000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// ```
+    pub fn clear(&self) {
+        self.shards.iter().for_each(|shard| write(shard).clear())
+    }
+}
+
+impl<T, U: K> ShardedBloomFilter<T, U> {
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of bits that are set.
+    pub fn current_false_positive_rate(&self) -> f64 {
+        crate::sizing::false_positive_rate_from_fill(self.fill_ratio(), self.k.k())
+    }
+}
+
+impl<T, U: K> K for ShardedBloomFilter<T, U> {
+    fn k(&self) -> usize {
+        self.k.k()
+    }
+}
+
+#[cfg(not(feature = "parking_lot"))]
+fn read(lock: &RwLock<BitVec>) -> std::sync::RwLockReadGuard<'_, BitVec> {
+    // A writer can't panic while only setting bits, so the data behind a poisoned lock is still usable.
+    lock.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(not(feature = "parking_lot"))]
+fn write(lock: &RwLock<BitVec>) -> std::sync::RwLockWriteGuard<'_, BitVec> {
+    lock.write().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(feature = "parking_lot")]
+fn read(lock: &RwLock<BitVec>) -> parking_lot::RwLockReadGuard<'_, BitVec> {
+    lock.read()
+}
+
+#[cfg(feature = "parking_lot")]
+fn write(lock: &RwLock<BitVec>) -> parking_lot::RwLockWriteGuard<'_, BitVec> {
+    lock.write()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom_filter::BloomFilter;
+    use murmur3::murmur3_32::MurmurHasher;
+    use std::sync::Arc;
+
+    #[test]
+    fn optimal_constructor() {
+        let bf: ShardedBloomFilter<&str, ReHasher<MurmurHasher>> =
+            ShardedBloomFilter::optimal_new(1000, 0.01, 8);
+        assert_eq!(bf.num_bits(), 9592, "m is rounded up to a multiple of the shard count");
+        assert_eq!(bf.num_shards(), 8);
+        assert_eq!(bf.k(), 7)
+    }
+
+    #[test]
+    fn agrees_with_a_single_bloom_filter_when_unsharded() {
+        let sharded: ShardedBloomFilter<i32, ReHasher<MurmurHasher>> =
+            ShardedBloomFilter::with_shards(1000, ReHasher::new(3), 1);
+        let mut normal: BloomFilter<i32, ReHasher<MurmurHasher>> =
+            BloomFilter::new(1000, ReHasher::new(3));
+        (0..100).for_each(|n| {
+            sharded.insert(&n);
+            normal.insert(&n);
+        });
+        (0..1000).for_each(|n| assert_eq!(sharded.contains(&n), normal.contains(&n)));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn concurrent_inserts_are_all_visible() {
+        let bf: Arc<ShardedBloomFilter<i32, ReHasher<MurmurHasher>>> =
+            Arc::new(ShardedBloomFilter::optimal_new(8000, 0.001, 16));
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let bf = bf.clone();
+                std::thread::spawn(move || (t * 1000..(t + 1) * 1000).for_each(|n| bf.insert(&n)))
+            })
+            .collect();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        (0..8000).for_each(|n| assert!(bf.contains(&n)));
+    }
+
+    #[test]
+    fn counts_ones_across_shards() {
+        let bf: ShardedBloomFilter<usize, ReHasher<MurmurHasher>> =
+            ShardedBloomFilter::with_shards(10_000, ReHasher::new(3), 4);
+        assert!(bf.is_empty());
+        (0..500).for_each(|n| bf.insert(&n));
+        let ones = bf.count_ones();
+        assert!(ones > 1000 && ones <= 1500);
+        assert_eq!(bf.fill_ratio(), ones as f64 / 10_000.0);
+        assert!(!bf.is_saturated(0.5));
+    }
+}
@@ -0,0 +1,218 @@
+//! Functions for sizing a bloom filter, complementing `optimal_m`, `optimal_k`, `m_from_knp`
+//! and `false_positive_rate` at the crate root.
+//!
+//! Throughout, `n` is the number of elements, `m` the number of bits, `k` the number of hashes
+//! and `p` the false positive rate.
+
+use crate::error;
+use crate::error::BloomError;
+use core::fmt;
+
+/// Gets the maximum number of elements (`n`) that can be inserted into a filter with `m` bits and `k` hashes
+/// before its false positive rate rises above `p`.
+///
+/// # Errors
+/// If `m` or `k` are 0, or if `p` is not within `(0, 1)`.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sizing::max_items;
+/// assert_eq!(max_items(10_000, 7, 0.01), Ok(1042));
+/// ```
+pub fn max_items(m: usize, k: usize, p: f64) -> Result<usize, BloomError> {
+    let m = error::check_m(m)?;
+    let k = error::check_k(k)?;
+    let p = error::check_p(p)?;
+    // Inverts p = (1 - e^(-kn/m))^k
+    Ok((-(m as f64) / k as f64 * (1.0 - p.powf(1.0 / k as f64)).ln()).floor() as usize)
+}
+
+/// Gets the false positive rate (`p`) a filter with `m` bits and `k` hashes will have
+/// once `inserted` elements have been inserted into it.
+///
+/// Unlike `false_positive_rate`, the parameters are checked, and an empty filter is allowed.
+///
+/// # Errors
+/// If `m` or `k` are 0.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sizing::false_positive_rate_after;
+/// assert_eq!(false_positive_rate_after(9586, 7, 0), Ok(0.0));
+/// assert!(false_positive_rate_after(9586, 7, 2000).unwrap() > 0.01);
+/// ```
+pub fn false_positive_rate_after(m: usize, k: usize, inserted: usize) -> Result<f64, BloomError> {
+    let m = error::check_m(m)?;
+    let k = error::check_k(k)?;
+    Ok((1.0 - (-(k as f64) * inserted as f64 / m as f64).exp()).powi(k as i32))
+}
+
+/// Gets the false positive rate of a filter with `k` hashes from the fraction of its bits that are set.
+///
+/// Unlike estimating it from the number of inserted elements,
+/// this reflects the bits that were actually set, so duplicate inserts and merged filters are accounted for.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sizing::false_positive_rate_from_fill;
+/// assert_eq!(false_positive_rate_from_fill(0.5, 3), 0.125);
+/// ```
+pub fn false_positive_rate_from_fill(fill_ratio: f64, k: usize) -> f64 {
+    fill_ratio.powi(k as i32)
+}
+
+/// Estimates how many distinct elements were inserted into a filter with `m` bits and `k` hashes
+/// from the fraction of its bits that are set, as `-m / k * ln(1 - fill_ratio)`.
+///
+/// A saturated filter, with every bit set, gives infinity.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sizing::items_from_fill;
+/// assert_eq!(items_from_fill(0.0, 1000, 3), 0.0);
+/// let items = items_from_fill(0.25, 1000, 3);
+/// assert!(items > 95.8 && items < 95.9);
+/// ```
+pub fn items_from_fill(fill_ratio: f64, m: usize, k: usize) -> f64 {
+    -(m as f64) / k as f64 * (1.0 - fill_ratio).ln()
+}
+
+/// Gets the number of bits needed per element to achieve a false positive rate of `p`,
+/// assuming an optimal `k`.
+///
+/// # Errors
+/// If `p` is not within `(0, 1)`.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sizing::bits_per_element;
+/// let bits = bits_per_element(0.01).unwrap();
+/// assert!(bits > 9.58 && bits < 9.59);
+/// ```
+pub fn bits_per_element(p: f64) -> Result<f64, BloomError> {
+    let p = error::check_p(p)?;
+    Ok(-p.ln() / (2f64.ln() * 2f64.ln()))
+}
+
+/// Gets the number of bytes needed to hold `m` bits.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sizing::bytes_needed;
+/// assert_eq!(bytes_needed(9586), 1199);
+/// ```
+pub fn bytes_needed(m: usize) -> usize {
+    m.div_ceil(8)
+}
+
+/// The parameters chosen for a filter, and what they are expected to achieve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plan {
+    /// The number of elements the filter was sized for.
+    pub n: usize,
+    /// The number of bits.
+    pub m: usize,
+    /// The number of hashes.
+    pub k: usize,
+    /// The false positive rate expected once `n` elements have been inserted.
+    pub false_positive_rate: f64,
+    /// The number of bytes needed to hold the bits.
+    pub bytes: usize,
+}
+
+impl Plan {
+    /// Plans a filter for `n` elements with a false positive rate of `p`, using the optimal `m` and `k`.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::sizing::Plan;
+    /// let plan = Plan::new(1000, 0.01).unwrap();
+    /// assert_eq!(plan.m, 9586);
+    /// assert_eq!(plan.k, 7);
+    /// assert_eq!(plan.bytes, 1199);
+    /// assert!(plan.false_positive_rate < 0.0101);
+    /// ```
+    pub fn new(n: usize, p: f64) -> Result<Plan, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        Plan::with_bits(n, m, crate::optimal_k(n, m)?)
+    }
+
+    /// Plans a filter for `n` elements with a false positive rate of `p`, performing `k` hashes.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, or if `p` is not within `(0, 1)`.
+    pub fn with_hashes(n: usize, p: f64, k: usize) -> Result<Plan, BloomError> {
+        Plan::with_bits(n, crate::m_from_knp(k, n, p)?, k)
+    }
+
+    /// Reports what a filter with `m` bits and `k` hashes is expected to achieve with `n` elements.
+    ///
+    /// # Errors
+    /// If `m` or `k` are 0.
+    pub fn with_bits(n: usize, m: usize, k: usize) -> Result<Plan, BloomError> {
+        Ok(Plan {
+            n,
+            m,
+            k,
+            false_positive_rate: false_positive_rate_after(m, k, n)?,
+            bytes: bytes_needed(m),
+        })
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "m = {} bits ({} bytes), k = {}, expected false positive rate = {:.6} at n = {}",
+            self.m, self.bytes, self.k, self.false_positive_rate, self.n
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_items_inverts_false_positive_rate() {
+        let p = false_positive_rate_after(10_000, 4, 1000).unwrap();
+        // Leave room for floating point error in the last digit.
+        assert_eq!(max_items(10_000, 4, p * 1.000_001), Ok(1000));
+        assert_eq!(max_items(10_000, 4, p * 0.999), Ok(999));
+    }
+
+    #[test]
+    fn matches_crate_root() {
+        assert_eq!(
+            false_positive_rate_after(100_000, 4, 10_000),
+            Ok(crate::false_positive_rate(4, 10_000, 100_000))
+        );
+    }
+
+    #[test]
+    fn plans_meet_their_rate() {
+        for &(n, p) in &[(1, 0.5), (100, 0.1), (1000, 0.01), (123_456, 0.0001)] {
+            // `optimal_m` assumes `k` can be fractional, so rounding it can cost a little.
+            let plan = Plan::new(n, p).unwrap();
+            assert!(plan.false_positive_rate <= p * 1.01, "{}", plan);
+            let plan = Plan::with_hashes(n, p, 3).unwrap();
+            assert!(plan.false_positive_rate <= p, "{}", plan);
+            assert!(max_items(plan.m, plan.k, p).unwrap() >= n, "{}", plan);
+        }
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(max_items(0, 1, 0.1), Err(BloomError::NoBits));
+        assert_eq!(max_items(1, 0, 0.1), Err(BloomError::NoHashes));
+        assert_eq!(
+            bits_per_element(1.0),
+            Err(BloomError::InvalidFalsePositiveRate(1.0))
+        );
+        assert_eq!(Plan::new(0, 0.1), Err(BloomError::NoElements));
+    }
+}
@@ -0,0 +1,50 @@
+//! The synchronization primitives used by the concurrent filters.
+//!
+//! When compiled with `RUSTFLAGS="--cfg loom"`, these are swapped out for loom's,
+//! so the model tests can explore every interleaving of the operations that use them,
+//! and can catch unsynchronized accesses to the data behind an `UnsafeCell`.
+
+#[cfg(loom)]
+pub(crate) use loom::cell::UnsafeCell;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::AtomicU32;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::AtomicU8;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::AtomicUsize;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::Ordering;
+/// Loom can only make progress past a spin loop if the spinning thread yields to it.
+#[cfg(loom)]
+pub(crate) use loom::thread::yield_now as spin_loop;
+
+#[cfg(not(loom))]
+pub(crate) use core::hint::spin_loop;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::AtomicU32;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::AtomicU8;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::AtomicUsize;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::Ordering;
+
+/// A `core::cell::UnsafeCell` with the closure based accessors of loom's `UnsafeCell`.
+#[cfg(not(loom))]
+#[derive(Debug)]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    pub(crate) fn new(data: T) -> UnsafeCell<T> {
+        UnsafeCell(core::cell::UnsafeCell::new(data))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+}
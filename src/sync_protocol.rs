@@ -0,0 +1,857 @@
+//! A small protocol for keeping `VersionedBloomFilter`s on different nodes in sync over TCP,
+//! with a tokio based `SyncServer` and `SyncClient`.
+//!
+//! A client pulls the server's filter, first as a snapshot, and afterwards as deltas of the words
+//! that changed since the version it last pulled, and pushes the words of its own filter that changed
+//! since it last pushed. Whatever is received is unioned into the receiver's filter with `merge`,
+//! so the filters on both ends only ever gain bits, and every node ends up with every other node's items.
+//! A node that should pass on what it learns runs a server over the same filter its clients sync.
+//!
+//! Versions only count the changes made to a filter since the process started, so every server and client
+//! picks a random epoch when it is created, and sends it along with its versions. A pull from an earlier
+//! epoch, such as one made before the server restarted, is answered with a snapshot, because the versions
+//! it was pulled at mean nothing to the new filter. Likewise, a client whose pushes are merged by a server of
+//! a different epoch than before pushes its whole filter again, since the earlier pushes may have been lost.
+//!
+//! Every message is framed as a tag byte and the length of its payload, as a little endian `u64`,
+//! followed by the payload, in which every integer is a little endian `u64`. Messages longer than
+//! `DEFAULT_MAX_MESSAGE_LEN`, or the limit a server or client was given, are refused.
+//!
+//! | Tag | Message    | Payload                                                                                     |
+//! |-----|------------|---------------------------------------------------------------------------------------------|
+//! | 1   | `Pull`     | The epoch of the last pull, then the version to send the changes after, or 0 for a snapshot |
+//! | 2   | `Snapshot` | The epoch, the version, then the filter serialized like `BloomFilter::to_bytes`             |
+//! | 3   | `Delta`    | The epoch, `since`, the version, `m`, `k`, then the index and value of each changed word    |
+//! | 4   | `Merged`   | The epoch, then the number of words the pushed update changed                               |
+//! | 5   | `Error`    | A UTF-8 description of why the request failed                                               |
+//!
+//! A client sends `Pull`, answered by `Snapshot` or `Delta`, and `Snapshot` or `Delta` to push,
+//! answered by `Merged`. Any request can be answered by `Error`.
+
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use crate::versioned_bloom_filter::Update;
+use crate::versioned_bloom_filter::VersionedBloomFilter;
+use core::convert::TryFrom;
+use core::hash::Hash;
+use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::SystemTime;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::net::ToSocketAddrs;
+
+const PULL: u8 = 1;
+const SNAPSHOT: u8 = 2;
+const DELTA: u8 = 3;
+const MERGED: u8 = 4;
+const ERROR: u8 = 5;
+
+/// The longest message a server or client reads unless given another limit, which fits the snapshot
+/// of a filter of up to 8 billion bits.
+pub const DEFAULT_MAX_MESSAGE_LEN: u64 = 1 << 30;
+
+/// A filter shared between a `SyncServer`, the `SyncClient`s syncing it with other nodes,
+/// and the code inserting into it.
+///
+/// The lock is only held while inserting, or while an update is taken or merged, never across an `await`.
+pub type SharedFilter<T, K> = Arc<Mutex<VersionedBloomFilter<T, K>>>;
+
+/// A message of the sync protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Asks for the words that changed after a version, or for a snapshot if the version is 0,
+    /// or the epoch isn't the receiver's.
+    Pull {
+        /// The epoch of the last update pulled from the receiver, or 0 if none has been.
+        epoch: u64,
+        /// The version of the last update pulled from the receiver, or 0 if none has been.
+        since: u64,
+    },
+    /// Bits for the receiver to merge, in answer to a `Pull`, or pushed.
+    Update {
+        /// The epoch of the sender, which its versions count in.
+        epoch: u64,
+        /// The bits.
+        update: Update,
+    },
+    /// The answer to a pushed update.
+    Merged {
+        /// The epoch of the receiver.
+        epoch: u64,
+        /// The number of the receiver's words that the update changed.
+        changed: u64,
+    },
+    /// The answer to a request that failed.
+    Error(String),
+}
+
+fn invalid_data(e: BloomError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn truncated() -> io::Error {
+    invalid_data(BloomError::InvalidFormat("a message was cut short"))
+}
+
+/// Picks an epoch for a new server or client, which is never 0.
+fn new_epoch() -> u64 {
+    use std::hash::BuildHasher;
+    use std::hash::Hasher;
+    // Every `RandomState` has different keys, and the time tells apart processes that happen to get the same ones.
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos());
+    hasher.write_u128(now);
+    hasher.finish().max(1)
+}
+
+fn to_usize(n: u64) -> io::Result<usize> {
+    usize::try_from(n)
+        .map_err(|_| invalid_data(BloomError::InvalidFormat("too large for this platform")))
+}
+
+/// Reads the little endian `u64`s at the start of a payload.
+fn read_u64s<const N: usize>(payload: &[u8]) -> io::Result<([u64; N], &[u8])> {
+    if payload.len() < N * 8 {
+        return Err(truncated());
+    }
+    let mut values = [0; N];
+    payload
+        .chunks_exact(8)
+        .zip(values.iter_mut())
+        .for_each(|(chunk, value)| {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(chunk);
+            *value = u64::from_le_bytes(buf);
+        });
+    Ok((values, &payload[N * 8..]))
+}
+
+impl Message {
+    /// Gets the message's tag and payload.
+    fn encode(&self) -> (u8, Vec<u8>) {
+        let u64s = |values: &[u64]| -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect()
+        };
+        match self {
+            Message::Pull { epoch, since } => (PULL, u64s(&[*epoch, *since])),
+            Message::Update {
+                epoch,
+                update: Update::Snapshot { version, bytes },
+            } => {
+                let mut payload = u64s(&[*epoch, *version]);
+                payload.extend_from_slice(bytes);
+                (SNAPSHOT, payload)
+            }
+            Message::Update {
+                epoch,
+                update:
+                    Update::Delta {
+                        since,
+                        version,
+                        num_bits,
+                        k,
+                        words,
+                    },
+            } => {
+                let mut payload = u64s(&[*epoch, *since, *version, *num_bits as u64, *k as u64]);
+                words.iter().for_each(|&(i, word)| {
+                    payload.extend_from_slice(&u64s(&[i as u64, word]));
+                });
+                (DELTA, payload)
+            }
+            Message::Merged { epoch, changed } => (MERGED, u64s(&[*epoch, *changed])),
+            Message::Error(description) => (ERROR, description.as_bytes().to_vec()),
+        }
+    }
+
+    /// Reads a message from its tag and payload.
+    fn decode(tag: u8, payload: &[u8]) -> io::Result<Self> {
+        let message = match tag {
+            PULL => {
+                let ([epoch, since], _) = read_u64s(payload)?;
+                Message::Pull { epoch, since }
+            }
+            SNAPSHOT => {
+                let ([epoch, version], bytes) = read_u64s(payload)?;
+                Message::Update {
+                    epoch,
+                    update: Update::Snapshot {
+                        version,
+                        bytes: bytes.to_vec(),
+                    },
+                }
+            }
+            DELTA => {
+                let ([epoch, since, version, num_bits, k], mut rest) = read_u64s(payload)?;
+                let mut words = Vec::with_capacity(rest.len() / 16);
+                while !rest.is_empty() {
+                    let ([i, word], next) = read_u64s(rest)?;
+                    words.push((to_usize(i)?, word));
+                    rest = next;
+                }
+                Message::Update {
+                    epoch,
+                    update: Update::Delta {
+                        since,
+                        version,
+                        num_bits: to_usize(num_bits)?,
+                        k: to_usize(k)?,
+                        words,
+                    },
+                }
+            }
+            MERGED => {
+                let ([epoch, changed], _) = read_u64s(payload)?;
+                Message::Merged { epoch, changed }
+            }
+            ERROR => Message::Error(String::from_utf8_lossy(payload).into_owned()),
+            _ => {
+                return Err(invalid_data(BloomError::InvalidFormat(
+                    "unknown message tag",
+                )))
+            }
+        };
+        Ok(message)
+    }
+}
+
+/// Writes a framed message.
+///
+/// # Errors
+/// If the bytes can't be written.
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+) -> io::Result<()> {
+    let (tag, payload) = message.encode();
+    let mut frame = Vec::with_capacity(9 + payload.len());
+    frame.push(tag);
+    frame.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Reads a framed message whose payload is at most `max_len` bytes, or `None` if the reader ended
+/// before one started.
+///
+/// # Errors
+/// If the bytes can't be read, or the reader ends partway through a message,
+/// or with `io::ErrorKind::InvalidData` if they aren't a message, or it is longer than `max_len`.
+pub async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max_len: u64,
+) -> io::Result<Option<Message>> {
+    let mut tag = [0];
+    if reader.read(&mut tag).await? == 0 {
+        return Ok(None);
+    }
+    let mut len = [0; 8];
+    reader.read_exact(&mut len).await?;
+    let len = u64::from_le_bytes(len);
+    if len > max_len {
+        return Err(invalid_data(BloomError::InvalidFormat(
+            "a message is longer than the limit",
+        )));
+    }
+    let mut payload = Vec::new();
+    // Read through `take`, so a corrupt length can't make this allocate more than the reader holds.
+    if reader.take(len).read_to_end(&mut payload).await? as u64 != len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "a message was cut short",
+        ));
+    }
+    Message::decode(tag[0], &payload).map(Some)
+}
+
+/// Locks the filter. Nothing panics while holding the lock partway through changing the filter,
+/// so the filter behind a poisoned lock is still usable.
+fn lock<T, K>(
+    filter: &Mutex<VersionedBloomFilter<T, K>>,
+) -> MutexGuard<'_, VersionedBloomFilter<T, K>> {
+    filter
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Merges an update into a shared filter. The update is checked against the filter's number of bits and hashes,
+/// and decoded, before the lock is taken to merge it, so inserts aren't held up by a large snapshot,
+/// and one that isn't for this filter is refused before its bits are decoded.
+fn merge<T, U: K>(
+    filter: &Mutex<VersionedBloomFilter<T, U>>,
+    update: &Update,
+) -> Result<usize, BloomError> {
+    let (num_bits, k) = {
+        let filter = lock(filter);
+        (filter.num_bits(), filter.k())
+    };
+    let decoded = update.decode(num_bits, k)?;
+    lock(filter).merge_decoded(&decoded)
+}
+
+/// Answers a request made to a server of the given epoch.
+fn answer<T, U: K>(
+    filter: &Mutex<VersionedBloomFilter<T, U>>,
+    epoch: u64,
+    request: Message,
+) -> Message {
+    match request {
+        Message::Pull {
+            epoch: pulled,
+            since,
+        } => {
+            let filter = lock(filter);
+            // A version pulled in another epoch, or one the filter hasn't reached yet, was pulled from some other filter,
+            // so everything is sent.
+            let update = if since == 0 || pulled != epoch || since > filter.version() {
+                filter.snapshot()
+            } else {
+                filter.delta_since(since)
+            };
+            Message::Update { epoch, update }
+        }
+        Message::Update { update, .. } => match merge(filter, &update) {
+            Ok(changed) => Message::Merged {
+                epoch,
+                changed: changed as u64,
+            },
+            Err(e) => Message::Error(e.to_string()),
+        },
+        Message::Merged { .. } | Message::Error(_) => {
+            Message::Error("only pulls and updates can be sent to a server".to_string())
+        }
+    }
+}
+
+/// Serves a `SharedFilter` to `SyncClient`s, answering their pulls and merging their pushes.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::sync_protocol::SyncClient;
+/// use bloom_filter::sync_protocol::SyncServer;
+/// use bloom_filter::VersionedBloomFilter;
+/// use bloom_filter::ReHasher;
+/// use murmur3::murmur3_32::MurmurHasher;
+/// use std::sync::Arc;
+/// use std::sync::Mutex;
+/// use tokio::net::TcpListener;
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let server = SyncServer::new(VersionedBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(1000, 0.01));
+/// server.filter().lock().unwrap().insert(&"hello");
+/// let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+/// let addr = listener.local_addr().unwrap();
+/// tokio::spawn(async move { server.serve(listener).await });
+///
+/// let local = Arc::new(Mutex::new(VersionedBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(1000, 0.01)));
+/// let mut client = SyncClient::connect(addr).await.unwrap();
+/// client.pull(&local).await.unwrap();
+/// assert!(local.lock().unwrap().contains(&"hello"));
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct SyncServer<T, K> {
+    filter: SharedFilter<T, K>,
+    /// The epoch the filter's versions count in.
+    epoch: u64,
+    /// The longest request that is read.
+    max_message_len: u64,
+}
+
+impl<T, K> SyncServer<T, K> {
+    /// Creates a server of the filter.
+    pub fn new(filter: VersionedBloomFilter<T, K>) -> Self {
+        SyncServer::from_shared(Arc::new(Mutex::new(filter)))
+    }
+
+    /// Creates a server of a filter that is already shared, such as with the node's `SyncClient`s.
+    pub fn from_shared(filter: SharedFilter<T, K>) -> Self {
+        SyncServer {
+            filter,
+            epoch: new_epoch(),
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+        }
+    }
+
+    /// Sets the longest request that is read, instead of `DEFAULT_MAX_MESSAGE_LEN`.
+    /// Connections that send longer ones are dropped.
+    pub fn with_max_message_len(mut self, max_message_len: u64) -> Self {
+        self.max_message_len = max_message_len;
+        self
+    }
+
+    /// Gets the served filter, to insert into it, or sync it with other nodes.
+    pub fn filter(&self) -> SharedFilter<T, K> {
+        self.filter.clone()
+    }
+}
+
+impl<T, U> SyncServer<T, U>
+where
+    T: Hash + Send + 'static,
+    U: HashToIndices + K + Send + 'static,
+{
+    /// Accepts connections from `listener` until accepting one fails, serving each on its own task.
+    /// Connections that send something other than requests are dropped.
+    ///
+    /// # Errors
+    /// If a connection can't be accepted.
+    pub async fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let filter = self.filter.clone();
+            let (epoch, max_message_len) = (self.epoch, self.max_message_len);
+            tokio::spawn(async move {
+                // The connection is dropped on any error, and the client will see it closed.
+                let _ = serve_connection(stream, &filter, epoch, max_message_len).await;
+            });
+        }
+    }
+}
+
+/// Answers requests from one client until it disconnects.
+async fn serve_connection<T, U: K>(
+    mut stream: TcpStream,
+    filter: &Mutex<VersionedBloomFilter<T, U>>,
+    epoch: u64,
+    max_message_len: u64,
+) -> io::Result<()> {
+    while let Some(request) = read_message(&mut stream, max_message_len).await? {
+        let response = answer(filter, epoch, request);
+        write_message(&mut stream, &response).await?;
+    }
+    Ok(())
+}
+
+/// A connection to a `SyncServer`, which pulls the server's filter into a local one,
+/// and pushes the local one to the server.
+///
+/// The first pull gets a snapshot, and the first push sends every word that has changed.
+/// After that, only the words that changed since the last pull or push are sent,
+/// unless the server has restarted since, when everything is sent again.
+#[derive(Debug)]
+pub struct SyncClient {
+    stream: TcpStream,
+    /// The epoch the local filter's versions are sent in.
+    epoch: u64,
+    /// The epoch of the server that was last pulled from, or 0.
+    pulled_epoch: u64,
+    /// The version of the server's filter that was last pulled, or 0.
+    pulled: u64,
+    /// The epoch of the server that last merged a push, or 0.
+    pushed_epoch: u64,
+    /// The version of the local filter that was last pushed, or 0.
+    pushed: u64,
+    /// The longest answer that is read.
+    max_message_len: u64,
+}
+
+impl SyncClient {
+    /// Connects to a server.
+    ///
+    /// # Errors
+    /// If the connection can't be made.
+    pub async fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(SyncClient {
+            stream: TcpStream::connect(addr).await?,
+            epoch: new_epoch(),
+            pulled_epoch: 0,
+            pulled: 0,
+            pushed_epoch: 0,
+            pushed: 0,
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+        })
+    }
+
+    /// Connects to a server again, such as after it restarted, keeping what has been pulled and pushed,
+    /// so only what changed since is sent if it is the same server.
+    ///
+    /// # Errors
+    /// If the connection can't be made.
+    pub async fn reconnect<A: ToSocketAddrs>(&mut self, addr: A) -> io::Result<()> {
+        self.stream = TcpStream::connect(addr).await?;
+        Ok(())
+    }
+
+    /// Sets the longest answer that is read, instead of `DEFAULT_MAX_MESSAGE_LEN`.
+    pub fn with_max_message_len(mut self, max_message_len: u64) -> Self {
+        self.max_message_len = max_message_len;
+        self
+    }
+
+    /// Sends a request, and reads the answer.
+    async fn request(&mut self, request: &Message) -> io::Result<Message> {
+        write_message(&mut self.stream, request).await?;
+        match read_message(&mut self.stream, self.max_message_len).await? {
+            Some(Message::Error(description)) => Err(io::Error::other(description)),
+            Some(response) => Ok(response),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            )),
+        }
+    }
+
+    /// Merges the changes made to the server's filter since the last pull into `filter`,
+    /// and returns how many of its words changed.
+    ///
+    /// # Errors
+    /// If the server can't be reached, or with `io::ErrorKind::InvalidData` wrapping a `BloomError`
+    /// if its filter has a different number of bits or hashes.
+    pub async fn pull<T, U: K>(
+        &mut self,
+        filter: &Mutex<VersionedBloomFilter<T, U>>,
+    ) -> io::Result<usize> {
+        let request = Message::Pull {
+            epoch: self.pulled_epoch,
+            since: self.pulled,
+        };
+        match self.request(&request).await? {
+            // Merging a delta from another epoch could miss words, so it is refused.
+            Message::Update {
+                epoch,
+                update: Update::Delta { .. },
+            } if epoch != self.pulled_epoch => Err(invalid_data(BloomError::InvalidFormat(
+                "a delta was pulled from a different epoch",
+            ))),
+            Message::Update { epoch, update } => {
+                let changed = merge(filter, &update).map_err(invalid_data)?;
+                self.pulled_epoch = epoch;
+                self.pulled = update.version();
+                Ok(changed)
+            }
+            _ => Err(invalid_data(BloomError::InvalidFormat(
+                "a pull wasn't answered with an update",
+            ))),
+        }
+    }
+
+    /// Sends the changes made to `filter` since the last push to the server,
+    /// and returns how many of the server's words changed.
+    /// If the server isn't of the epoch that merged the last push, it may not have the earlier ones,
+    /// so every word that has changed is sent again.
+    ///
+    /// # Errors
+    /// If the server can't be reached, or fails to merge the changes,
+    /// because its filter has a different number of bits or hashes.
+    pub async fn push<T, U: K>(
+        &mut self,
+        filter: &Mutex<VersionedBloomFilter<T, U>>,
+    ) -> io::Result<usize> {
+        let previous = self.pushed_epoch;
+        let mut changed = self.push_since(filter, self.pushed).await?;
+        if previous != 0 && self.pushed_epoch != previous {
+            changed += self.push_since(filter, 0).await?;
+        }
+        Ok(changed)
+    }
+
+    /// Sends the changes made to `filter` after a version, recording the version and the server's epoch.
+    async fn push_since<T, U: K>(
+        &mut self,
+        filter: &Mutex<VersionedBloomFilter<T, U>>,
+        since: u64,
+    ) -> io::Result<usize> {
+        let update = lock(filter).delta_since(since);
+        let version = update.version();
+        let request = Message::Update {
+            epoch: self.epoch,
+            update,
+        };
+        match self.request(&request).await? {
+            Message::Merged { epoch, changed } => {
+                self.pushed_epoch = epoch;
+                self.pushed = version;
+                to_usize(changed)
+            }
+            _ => Err(invalid_data(BloomError::InvalidFormat(
+                "a push wasn't answered with a merge",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rehasher::ReHasher;
+    use murmur3::murmur3_32::MurmurHasher;
+    use std::net::SocketAddr;
+
+    type Filter = VersionedBloomFilter<u32, ReHasher<MurmurHasher>>;
+
+    fn shared() -> SharedFilter<u32, ReHasher<MurmurHasher>> {
+        Arc::new(Mutex::new(Filter::optimal_new(10_000, 0.01)))
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_io()
+            .build()
+            .unwrap()
+    }
+
+    /// Serves the filter on a localhost port, and returns its address.
+    async fn serve(filter: SharedFilter<u32, ReHasher<MurmurHasher>>) -> SocketAddr {
+        serve_server(SyncServer::from_shared(filter)).await
+    }
+
+    async fn serve_server(server: SyncServer<u32, ReHasher<MurmurHasher>>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { server.serve(listener).await });
+        addr
+    }
+
+    /// Pulls from a server without a `SyncClient`, and returns the epoch and the update it answered with.
+    async fn pull(stream: &mut TcpStream, epoch: u64, since: u64) -> (u64, Update) {
+        write_message(stream, &Message::Pull { epoch, since })
+            .await
+            .unwrap();
+        match read_message(stream, DEFAULT_MAX_MESSAGE_LEN).await.unwrap() {
+            Some(Message::Update { epoch, update }) => (epoch, update),
+            response => panic!("{:?}", response),
+        }
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        runtime().block_on(async {
+            let mut filter = Filter::new(1024, ReHasher::new(2));
+            filter.insert(&1);
+            let messages = vec![
+                Message::Pull { epoch: 7, since: 3 },
+                Message::Update {
+                    epoch: 7,
+                    update: filter.snapshot(),
+                },
+                Message::Update {
+                    epoch: 7,
+                    update: filter.delta_since(0),
+                },
+                Message::Merged {
+                    epoch: 7,
+                    changed: 2,
+                },
+                Message::Error("nope".to_string()),
+            ];
+            let mut bytes = Vec::new();
+            for message in &messages {
+                write_message(&mut bytes, message).await.unwrap();
+            }
+            let mut reader = &bytes[..];
+            for message in messages {
+                assert_eq!(
+                    read_message(&mut reader, DEFAULT_MAX_MESSAGE_LEN)
+                        .await
+                        .unwrap(),
+                    Some(message)
+                );
+            }
+            assert_eq!(
+                read_message(&mut reader, DEFAULT_MAX_MESSAGE_LEN)
+                    .await
+                    .unwrap(),
+                None
+            );
+
+            let mut frame = Vec::new();
+            write_message(&mut frame, &Message::Pull { epoch: 7, since: 3 })
+                .await
+                .unwrap();
+            let cut = read_message(&mut &frame[..frame.len() - 1], DEFAULT_MAX_MESSAGE_LEN)
+                .await
+                .unwrap_err();
+            assert_eq!(cut.kind(), io::ErrorKind::UnexpectedEof);
+            let long = read_message(&mut &frame[..], 15).await.unwrap_err();
+            assert_eq!(long.kind(), io::ErrorKind::InvalidData);
+            assert!(read_message(&mut &frame[..], 16).await.is_ok());
+            frame[0] = 9;
+            let unknown = read_message(&mut &frame[..], DEFAULT_MAX_MESSAGE_LEN)
+                .await
+                .unwrap_err();
+            assert_eq!(unknown.kind(), io::ErrorKind::InvalidData);
+
+            // A length past the limit is refused before anything is allocated for it.
+            let mut huge = vec![PULL];
+            huge.extend_from_slice(&u64::MAX.to_le_bytes());
+            let huge = read_message(&mut &huge[..], DEFAULT_MAX_MESSAGE_LEN)
+                .await
+                .unwrap_err();
+            assert_eq!(huge.kind(), io::ErrorKind::InvalidData);
+        });
+    }
+
+    #[test]
+    fn nodes_sync_through_a_server() {
+        runtime().block_on(async {
+            let hub = shared();
+            let addr = serve(hub.clone()).await;
+            let a = shared();
+            let b = shared();
+            (0..1000).for_each(|n| a.lock().unwrap().insert(&n));
+            (1000..2000).for_each(|n| b.lock().unwrap().insert(&n));
+
+            let mut a_client = SyncClient::connect(addr).await.unwrap();
+            let mut b_client = SyncClient::connect(addr).await.unwrap();
+            assert!(a_client.push(&a).await.unwrap() > 0);
+            assert!(b_client.push(&b).await.unwrap() > 0);
+            a_client.pull(&a).await.unwrap();
+            b_client.pull(&b).await.unwrap();
+            for filter in [&a, &b, &hub] {
+                let filter = filter.lock().unwrap();
+                (0..2000).for_each(|n| assert!(filter.contains(&n)));
+            }
+
+            // Nothing changed since the last push and pull.
+            assert_eq!(a_client.push(&a).await.unwrap(), 0);
+            assert_eq!(b_client.pull(&b).await.unwrap(), 0);
+
+            a.lock().unwrap().insert(&5000);
+            assert!(a_client.push(&a).await.unwrap() > 0);
+            assert!(b_client.pull(&b).await.unwrap() > 0);
+            assert!(b.lock().unwrap().contains(&5000));
+        });
+    }
+
+    #[test]
+    fn pulls_after_the_first_are_deltas() {
+        runtime().block_on(async {
+            let hub = shared();
+            (0..1000).for_each(|n| hub.lock().unwrap().insert(&n));
+            let addr = serve(hub.clone()).await;
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+
+            let (epoch, update) = pull(&mut stream, 0, 0).await;
+            assert!(matches!(update, Update::Snapshot { .. }));
+            let version = update.version();
+            hub.lock().unwrap().insert(&1000);
+            match pull(&mut stream, epoch, version).await {
+                (_, Update::Delta { words, .. }) => {
+                    assert!(!words.is_empty() && words.len() <= 7, "{:?}", words)
+                }
+                response => panic!("{:?}", response),
+            }
+            // A version pulled in another epoch means nothing to this filter.
+            assert!(matches!(
+                pull(&mut stream, epoch + 1, version).await,
+                (_, Update::Snapshot { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn restarted_servers_are_synced_in_full() {
+        runtime().block_on(async {
+            let old = shared();
+            (0..1000).for_each(|n| old.lock().unwrap().insert(&n));
+            let local = shared();
+            (10_000..11_000).for_each(|n| local.lock().unwrap().insert(&n));
+            let mut client = SyncClient::connect(serve(old).await).await.unwrap();
+            client.pull(&local).await.unwrap();
+            client.push(&local).await.unwrap();
+
+            // The restarted server's version has passed the one pulled before,
+            // and it lost what was pushed before.
+            let restarted = shared();
+            (1000..4000).for_each(|n| restarted.lock().unwrap().insert(&n));
+            assert!(restarted.lock().unwrap().version() > client.pulled);
+            client
+                .reconnect(serve(restarted.clone()).await)
+                .await
+                .unwrap();
+            client.pull(&local).await.unwrap();
+            client.push(&local).await.unwrap();
+            (1000..4000).for_each(|n| assert!(local.lock().unwrap().contains(&n)));
+            (10_000..11_000).for_each(|n| assert!(restarted.lock().unwrap().contains(&n)));
+
+            // Against the same server, only what changed is sent.
+            assert_eq!(client.pull(&local).await.unwrap(), 0);
+            assert_eq!(client.push(&local).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn long_messages_are_refused() {
+        runtime().block_on(async {
+            let server = SyncServer::from_shared(shared()).with_max_message_len(256);
+            let addr = serve_server(server).await;
+            let local = shared();
+            local.lock().unwrap().insert(&1);
+            let mut client = SyncClient::connect(addr).await.unwrap();
+            // A push of the few words one insert sets fits, but the server hangs up on a longer one.
+            assert!(client.push(&local).await.unwrap() > 0);
+            (0..1000).for_each(|n| local.lock().unwrap().insert(&n));
+            assert!(client.push(&local).await.is_err());
+
+            let addr = serve(shared()).await;
+            let mut client = SyncClient::connect(addr)
+                .await
+                .unwrap()
+                .with_max_message_len(256);
+            let refused = client.pull(&local).await.unwrap_err();
+            assert_eq!(refused.kind(), io::ErrorKind::InvalidData);
+        });
+    }
+
+    #[test]
+    fn forged_snapshots_are_refused_without_decoding() {
+        runtime().block_on(async {
+            let addr = serve(shared()).await;
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let mut bytes = Filter::new(64, ReHasher::new(7))
+                .filter()
+                .to_compact_bytes();
+            bytes[16..24].copy_from_slice(&(1u64 << 62).to_le_bytes());
+            let forged = Message::Update {
+                epoch: 1,
+                update: Update::Snapshot { version: 1, bytes },
+            };
+            write_message(&mut stream, &forged).await.unwrap();
+            match read_message(&mut stream, DEFAULT_MAX_MESSAGE_LEN)
+                .await
+                .unwrap()
+            {
+                Some(Message::Error(description)) => {
+                    assert!(description.contains("can't be combined"), "{}", description)
+                }
+                response => panic!("{:?}", response),
+            }
+            // The server is still serving.
+            let (_, update) = pull(&mut stream, 0, 0).await;
+            assert!(matches!(update, Update::Snapshot { .. }));
+        });
+    }
+
+    #[test]
+    fn mismatched_filters_are_refused() {
+        runtime().block_on(async {
+            let addr = serve(shared()).await;
+            let small = Arc::new(Mutex::new(Filter::new(64, ReHasher::new(7))));
+            small.lock().unwrap().insert(&1);
+            let mut client = SyncClient::connect(addr).await.unwrap();
+            let refused = client.push(&small).await.unwrap_err();
+            assert!(
+                refused.to_string().contains("can't be combined"),
+                "{}",
+                refused
+            );
+            let refused = client.pull(&small).await.unwrap_err();
+            assert_eq!(refused.kind(), io::ErrorKind::InvalidData);
+        });
+    }
+}
@@ -0,0 +1,443 @@
+//! A bloom filter that keeps track of which of its words changed when,
+//! so that filters on different nodes can be kept in sync by sending each other only what changed.
+
+use crate::bit_store::BitStore;
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
+use crate::hash_to_indicies::HashToIndices;
+use crate::hash_to_indicies::K;
+use crate::rehasher::ReHasher;
+use core::hash::Hash;
+
+/// The bits one filter sends to another, to be merged into it with `VersionedBloomFilter::merge`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Update {
+    /// Every bit of the filter.
+    Snapshot {
+        /// The version of the filter the bits were taken at.
+        version: u64,
+        /// The filter, serialized like `BloomFilter::to_bytes`.
+        bytes: Vec<u8>,
+    },
+    /// The words of the filter that changed after a version.
+    Delta {
+        /// The version the changes were made after.
+        since: u64,
+        /// The version of the filter the words were taken at.
+        version: u64,
+        /// The number of bits of the filter, which the receiving filter must have too.
+        num_bits: usize,
+        /// The number of hashes of the filter, which the receiving filter must perform too.
+        k: usize,
+        /// The indexes and values of the changed words. Bit `i` is bit `i % 64` of word `i / 64`.
+        words: Vec<(usize, u64)>,
+    },
+}
+
+impl Update {
+    /// Gets the version of the filter the update was taken at.
+    /// Passing it to `delta_since` the next time gets the changes made after this update.
+    pub fn version(&self) -> u64 {
+        match self {
+            Update::Snapshot { version, .. } | Update::Delta { version, .. } => *version,
+        }
+    }
+
+    /// Checks that the update is from a filter with `num_bits` bits, and hashers that perform `k` hashes,
+    /// and gets the words it sets, decoding a snapshot's bits only once its header has been checked.
+    /// Decoding is the slow part of a merge, so a filter behind a lock can be merged into by decoding the update
+    /// before taking the lock, and only holding it for `VersionedBloomFilter::merge_decoded`.
+    ///
+    /// # Errors
+    /// If the update is from a filter with a different number of bits, or hashers that perform a different
+    /// number of hashes, or a snapshot isn't a serialized filter, or a delta holds words past the end.
+    pub fn decode(&self, num_bits: usize, k: usize) -> Result<DecodedUpdate, BloomError> {
+        let (other_bits, other_k) = match self {
+            Update::Snapshot { bytes, .. } => (
+                crate::format::num_bits(bytes)?,
+                crate::format::hashes(bytes)?,
+            ),
+            Update::Delta { num_bits, k, .. } => (*num_bits, *k),
+        };
+        if other_bits != num_bits {
+            return Err(BloomError::MismatchedBits {
+                bits: num_bits,
+                other: other_bits,
+            });
+        }
+        if other_k != k {
+            return Err(BloomError::ConflictingHashes {
+                requested: other_k,
+                hashers: k,
+            });
+        }
+        let words = match self {
+            Update::Snapshot { bytes, .. } => {
+                let (bits, _) = crate::format::decode(bytes, k)?;
+                let bytes = BitStore::as_bytes(&bits);
+                let words = bytes.chunks(8).map(|chunk| {
+                    let mut buf = [0; 8];
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    u64::from_le_bytes(buf)
+                });
+                // Unset words wouldn't change anything, so they aren't kept.
+                words.enumerate().filter(|&(_, word)| word != 0).collect()
+            }
+            Update::Delta { words, .. } => {
+                if words.iter().any(|&(i, _)| i >= num_bits.div_ceil(64)) {
+                    return Err(BloomError::InvalidFormat("a changed word is out of range"));
+                }
+                words.clone()
+            }
+        };
+        Ok(DecodedUpdate { num_bits, k, words })
+    }
+}
+
+/// The words an update sets, checked to fit a filter with some number of bits and hashes,
+/// made by `Update::decode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedUpdate {
+    num_bits: usize,
+    k: usize,
+    /// The indexes and values of the words, which are all in range.
+    words: Vec<(usize, u64)>,
+}
+
+/// A bloom filter backed by 64 bit words, which records the version at which each word last changed.
+///
+/// The version starts at 0, and goes up by one with every `insert` or `merge` that sets any bits.
+/// `delta_since` gets the words that changed after a version, so a peer that has already merged everything
+/// up to that version only needs to be sent those.
+/// Bits are only ever set, never unset, so merging is a union, and updates can be merged in any order,
+/// any number of times.
+///
+/// # Examples
+/// ```
+/// use bloom_filter::versioned_bloom_filter::VersionedBloomFilter;
+/// use bloom_filter::ReHasher;
+/// use murmur3::murmur3_32::MurmurHasher;
+/// let mut a = VersionedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(1024, ReHasher::new(2));
+/// let mut b = VersionedBloomFilter::<&str, ReHasher<MurmurHasher>>::new(1024, ReHasher::new(2));
+/// a.insert(&"hello");
+/// b.merge(&a.snapshot()).unwrap();
+/// let synced = a.version();
+/// a.insert(&"there");
+/// b.merge(&a.delta_since(synced)).unwrap();
+/// assert!(b.contains(&"hello"));
+/// assert!(b.contains(&"there"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct VersionedBloomFilter<T, K> {
+    filter: BloomFilter<T, K, Vec<u64>>,
+    /// The version at which each word was last changed.
+    word_versions: Vec<u64>,
+    version: u64,
+}
+
+impl<T, H> VersionedBloomFilter<T, ReHasher<H>> {
+    /// Constructs a new VersionedBloomFilter with an optimal ratio of m and k,
+    /// derived from n and p inputs. The number of bits is rounded up to a whole number of words.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Number of expected elements to be inserted into the set.
+    /// * `p` - False positive rate.
+    ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    pub fn optimal_new(n: usize, p: f64) -> Self {
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new VersionedBloomFilter with an optimal ratio of m and k,
+    /// returning an error instead of panicking if the parameters are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        let k = crate::optimal_k(n, m)?;
+        Ok(Self::from_parts(m, ReHasher::new(k)))
+    }
+}
+
+impl<T, K> VersionedBloomFilter<T, K> {
+    fn from_parts(m: usize, hashers: K) -> Self {
+        let words = m.div_ceil(64);
+        VersionedBloomFilter {
+            filter: BloomFilter::from_parts(vec![0; words], hashers),
+            word_versions: vec![0; words],
+            version: 0,
+        }
+    }
+
+    /// Gets the current version, which is the number of inserts and merges that have set any bits.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Gets the number of bits used in the bloom filter, which is a multiple of 64.
+    pub fn num_bits(&self) -> usize {
+        BitStore::len(&self.filter.bits)
+    }
+
+    /// Gets the filter, to look at its bits, or serialize it.
+    pub fn filter(&self) -> &BloomFilter<T, K, Vec<u64>> {
+        &self.filter
+    }
+
+    /// ORs words into the filter, recording the ones that changed at the next version,
+    /// and returns how many did.
+    fn merge_words(&mut self, words: impl Iterator<Item = (usize, u64)>) -> usize {
+        let next = self.version + 1;
+        let mut changed = 0;
+        for (i, word) in words {
+            let old = self.filter.bits[i];
+            if old | word != old {
+                self.filter.bits[i] = old | word;
+                self.word_versions[i] = next;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.version = next;
+        }
+        changed
+    }
+}
+
+impl<T, U: K> VersionedBloomFilter<T, U> {
+    /// Gets every bit of the filter, to be sent to a peer that has none of them yet.
+    pub fn snapshot(&self) -> Update {
+        Update::Snapshot {
+            version: self.version,
+            bytes: self.filter.to_bytes(),
+        }
+    }
+
+    /// Gets the words that changed after `since`, to be sent to a peer that has already merged
+    /// everything up to that version.
+    pub fn delta_since(&self, since: u64) -> Update {
+        let words = self
+            .word_versions
+            .iter()
+            .enumerate()
+            .filter(|(_, &version)| version > since)
+            .map(|(i, _)| (i, self.filter.bits[i]))
+            .collect();
+        Update::Delta {
+            since,
+            version: self.version,
+            num_bits: self.num_bits(),
+            k: self.filter.k.k(),
+            words,
+        }
+    }
+
+    /// Sets every bit that is set in the update, and returns the number of words that changed.
+    /// If any did, the version goes up by one, so the merged bits are passed on by the next `delta_since`.
+    ///
+    /// # Errors
+    /// If the update is from a filter with a different number of bits, or hashers that perform a different
+    /// number of hashes, or a snapshot isn't a serialized filter, or a delta holds words past the end.
+    /// The filter is left as it was.
+    pub fn merge(&mut self, update: &Update) -> Result<usize, BloomError> {
+        let decoded = update.decode(self.num_bits(), self.k())?;
+        self.merge_decoded(&decoded)
+    }
+
+    /// Like `merge`, with an update that has already been decoded.
+    ///
+    /// # Errors
+    /// If the update was decoded for a filter with a different number of bits or hashes.
+    /// The filter is left as it was.
+    pub fn merge_decoded(&mut self, update: &DecodedUpdate) -> Result<usize, BloomError> {
+        if update.num_bits != self.num_bits() {
+            return Err(BloomError::MismatchedBits {
+                bits: self.num_bits(),
+                other: update.num_bits,
+            });
+        }
+        if update.k != self.k() {
+            return Err(BloomError::ConflictingHashes {
+                requested: update.k,
+                hashers: self.k(),
+            });
+        }
+        Ok(self.merge_words(update.words.iter().copied()))
+    }
+}
+
+impl<T, K> VersionedBloomFilter<T, K>
+where
+    T: Hash,
+    K: HashToIndices + crate::hash_to_indicies::K,
+{
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate.
+    /// The number of bits is rounded up to a whole number of words.
+    ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    pub fn with_rate(n: usize, p: f64, hashers: K) -> Self {
+        Self::try_with_rate(n, p, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `with_rate`, returning an error instead of panicking if the parameters are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    pub fn try_with_rate(n: usize, p: f64, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::m_from_knp(hashers.k(), n, p)?;
+        Ok(Self::from_parts(m, hashers))
+    }
+
+    /// Creates the bloom filter with at least `m` bits, rounded up to a whole number of words,
+    /// and with a multiple-hashing-to-index function.
+    ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    pub fn new(m: usize, hashers: K) -> Self {
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `new`, returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::error::check_m(m)?;
+        crate::error::check_k(hashers.k())?;
+        Ok(Self::from_parts(m, hashers))
+    }
+
+    /// Inserts the value, recording the words it changed at the next version.
+    pub fn insert(&mut self, value: &T) {
+        let m = self.num_bits();
+        let indices = self.filter.k.hash_to_indices(value, m);
+        self.merge_words(indices.into_iter().map(|i| (i / 64, 1 << (i % 64))));
+    }
+
+    /// Tests to see if the provided value is in the bloom filter.
+    /// This will return false positives, but never false negatives.
+    pub fn contains(&self, value: &T) -> bool {
+        self.filter.contains(value)
+    }
+}
+
+impl<T, U: K> K for VersionedBloomFilter<T, U> {
+    fn k(&self) -> usize {
+        self.filter.k.k()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    type Filter = VersionedBloomFilter<u32, ReHasher<MurmurHasher>>;
+
+    #[test]
+    fn versions_count_changing_inserts() {
+        let mut bf = Filter::new(1000, ReHasher::new(3));
+        assert_eq!(bf.num_bits(), 1024);
+        bf.insert(&1);
+        bf.insert(&1);
+        assert_eq!(bf.version(), 1);
+        bf.insert(&2);
+        assert_eq!(bf.version(), 2);
+        match bf.delta_since(1) {
+            Update::Delta { words, .. } => {
+                assert!(!words.is_empty() && words.len() <= 3);
+                assert!(words.iter().all(|&(i, _)| bf.word_versions[i] == 2));
+            }
+            update => panic!("{:?}", update),
+        }
+    }
+
+    #[test]
+    fn deltas_bring_peers_up_to_date() {
+        let mut a = Filter::optimal_new(1000, 0.01);
+        let mut b = Filter::optimal_new(1000, 0.01);
+        (0..100).for_each(|n| a.insert(&n));
+        assert!(b.merge(&a.snapshot()).unwrap() > 0);
+        let synced = a.version();
+        (100..110).for_each(|n| a.insert(&n));
+        let delta = a.delta_since(synced);
+        assert!(b.merge(&delta).unwrap() > 0);
+        assert_eq!(b.merge(&delta), Ok(0), "merging is idempotent");
+        assert_eq!(b.filter().to_bytes(), a.filter().to_bytes());
+        match a.delta_since(a.version()) {
+            Update::Delta { words, .. } => assert!(words.is_empty()),
+            update => panic!("{:?}", update),
+        }
+    }
+
+    #[test]
+    fn merged_words_are_passed_on() {
+        let mut a = Filter::optimal_new(1000, 0.01);
+        let mut b = Filter::optimal_new(1000, 0.01);
+        let mut c = Filter::optimal_new(1000, 0.01);
+        b.insert(&7);
+        let since = a.version();
+        a.merge(&b.delta_since(0)).unwrap();
+        c.merge(&a.delta_since(since)).unwrap();
+        assert!(c.contains(&7));
+    }
+
+    #[test]
+    fn rejects_mismatched_updates() {
+        let mut bf = Filter::new(1024, ReHasher::new(3));
+        let other = Filter::new(2048, ReHasher::new(3));
+        assert_eq!(
+            bf.merge(&other.snapshot()),
+            Err(BloomError::MismatchedBits {
+                bits: 1024,
+                other: 2048
+            })
+        );
+        let other = Filter::new(1024, ReHasher::new(2));
+        assert!(bf.merge(&other.delta_since(0)).is_err());
+        let out_of_range = Update::Delta {
+            since: 0,
+            version: 1,
+            num_bits: 1024,
+            k: 3,
+            words: vec![(16, 1)],
+        };
+        assert!(bf.merge(&out_of_range).is_err());
+        assert_eq!(bf.version(), 0);
+    }
+
+    #[test]
+    fn snapshot_headers_are_checked_before_decoding() {
+        // A snapshot claiming far more bits than it holds is refused for its size,
+        // without its bits being decoded.
+        let mut forged = Filter::new(1024, ReHasher::new(3))
+            .filter()
+            .to_compact_bytes();
+        forged[16..24].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        let update = Update::Snapshot {
+            version: 1,
+            bytes: forged,
+        };
+        assert_eq!(
+            update.decode(1024, 3),
+            Err(BloomError::MismatchedBits {
+                bits: 1024,
+                other: 1 << 62
+            })
+        );
+
+        let mut a = Filter::new(1024, ReHasher::new(3));
+        a.insert(&1);
+        let decoded = a.snapshot().decode(1024, 3).unwrap();
+        let mut b = Filter::new(1024, ReHasher::new(3));
+        assert!(b.merge_decoded(&decoded).unwrap() > 0);
+        assert!(b.contains(&1));
+        let mut other = Filter::new(2048, ReHasher::new(3));
+        assert!(other.merge_decoded(&decoded).is_err());
+    }
+}
@@ -1,54 +1,66 @@
+use crate::bit_store::BitStore;
+use crate::bloom_filter::BloomFilter;
+use crate::error::BloomError;
 use crate::hash_to_indicies::HashToIndices;
 use crate::hash_to_indicies::K;
 use crate::rehasher::ReHasher;
+use crate::sync::spin_loop;
+use crate::sync::AtomicUsize;
+use crate::sync::Ordering;
+use crate::sync::UnsafeCell;
 use bit_vec::BitVec;
+use core::fmt;
 use core::hash::Hash;
 use core::marker::PhantomData;
-use core::sync::atomic::AtomicBool;
-use core::sync::atomic::Ordering;
 
 /// A variant of a bloom filter with the insert method taking &self, so no mutable reference to the
 /// datastructure is needed.
 ///
 /// # Notes
 /// This is thread safe because:
-/// 1. The size of the backing bitvec is fixed size, and nothing takes any references to it anyway,
-/// so the concern about growing+reallocating does not exist.
+/// 1. The size of the backing bitvec is fixed size, and its length is recorded at construction,
+///    so nothing needs to look at the bitvec outside of setting and reading bits.
 /// 2. The k values are immutable and act as factories for producing default hashers.
-/// If they don't produce hashers in the same state on every invocation, the implementation is broken anyway.
+///    If they don't produce hashers in the same state on every invocation, the implementation is broken anyway.
 /// 3. Because there is a spinlock on the critical section of the insert operation,
-/// no two threads can race and clobber the setting of bits.
+///    no two threads can race and clobber the setting of bits.
+/// 4. Lookups share the read side of the same spinlock, so bits are never read while they are being set.
 ///
-/// # Warning
-/// Do note, that this is a write only lock.
-/// That means that if one thread is reading and another is writing,
-/// there is no guarantee that the write will finish before the read occurs assuming the
-/// operations are dispatched at approximately the same time.
-/// This should be Ok for most workloads, although for that brief moment before the write is committed,
-/// a false negative is possible.
+/// Values are hashed before the lock is taken, and the lock is only held while the `k` bits are set or read,
+/// which is why this spins instead of using a `RwLock<BloomFilter>`,
+/// whose lock would persist while the hashing takes place, which is where the majority of time is spent.
+/// Once a writer is waiting, new lookups wait behind it, so a steady stream of lookups can't starve inserts.
 ///
-/// If guaranteed absolute ordering is needed, a RwLock<BloomFilter> could be used instead,
-/// although that comes with a significant performance cost because the lock would persist
-/// while the hashing takes place, which is where the majority of time is spent.
-#[derive(Debug, Clone)]
-pub struct WLockBloomFilter<T, K> {
-    pub(crate) bit_vec: *mut BitVec,
-    is_writing: AtomicBool,
+/// A lookup that is dispatched at approximately the same time as an insert of the same value
+/// may happen before it, and not find the value.
+///
+/// Because every writer contends for the same lock, insert throughput drops off sharply past a handful of
+/// writing threads. For heavily contended workloads, `ShardedBloomFilter` should be preferred.
+/// Inserts and lookups spin while another thread holds the lock, so in async code, where a spinning task stalls its executor
+/// thread, `AtomicBloomFilter` should be used instead.
+pub struct WLockBloomFilter<T, K, S = BitVec> {
+    bits: UnsafeCell<S>,
+    /// The number of bits, so it can be known without taking the lock.
+    num_bits: usize,
+    /// `WRITER` while a writer holds the lock, or is waiting for the readers to release it,
+    /// plus the number of readers holding it.
+    lock: AtomicUsize,
     type_info: PhantomData<T>,
     pub(crate) k: K,
 }
 
-
-unsafe impl<T, K> Send for WLockBloomFilter<T, K>
+unsafe impl<T, K, S> Send for WLockBloomFilter<T, K, S>
 where
     T: Send,
     K: Sync,
+    S: Send,
 {
 }
-unsafe impl<T, K> Sync for WLockBloomFilter<T, K>
+unsafe impl<T, K, S> Sync for WLockBloomFilter<T, K, S>
 where
     T: Sync,
     K: Sync,
+    S: Send + Sync,
 {
 }
 
@@ -61,6 +73,9 @@ impl<T, H> WLockBloomFilter<T, ReHasher<H>> {
     /// * `n` - Number of expected elements to be inserted into the set.
     /// * `p` - False positive rate.
     ///
+    /// # Panics
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::WLockBloomFilter;
@@ -69,14 +84,31 @@ impl<T, H> WLockBloomFilter<T, ReHasher<H>> {
     /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::optimal_new(10000, 0.001);
     /// ```
     pub fn optimal_new(n: usize, p: f64) -> Self {
-        let m = crate::optimal_m(n, p);
-        let k = crate::optimal_k(n, m);
-        WLockBloomFilter {
-            bit_vec: Box::into_raw(Box::new(BitVec::from_elem(m, false))),
-            is_writing: AtomicBool::new(false),
-            type_info: PhantomData,
-            k: ReHasher::new(k),
-        }
+        Self::try_optimal_new(n, p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Constructs a new WLockBloomFilter with an optimal ratio of m and k, derived from n and p inputs,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `n` is 0, or if `p` is not within `(0, 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::try_optimal_new(0, 0.001);
+    /// assert_eq!(bf.err(), Some(BloomError::NoElements));
+    /// ```
+    pub fn try_optimal_new(n: usize, p: f64) -> Result<Self, BloomError> {
+        let m = crate::optimal_m(n, p)?;
+        let k = crate::optimal_k(n, m)?;
+        Ok(Self::from_parts(
+            BitVec::from_elem(m, false),
+            ReHasher::new(k),
+        ))
     }
 }
 
@@ -101,6 +133,10 @@ where
     /// Because the insert and checking time scales with `k`, not with `m`,
     /// `m` can be increased to trade space efficiency for speed.
     ///
+    /// # Panics
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::WLockBloomFilter;
@@ -109,20 +145,36 @@ where
     /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::with_rate(10000, 0.001, ReHasher::new(1));
     /// ```
     pub fn with_rate(n: usize, p: f64, hashers: H) -> Self {
-        let m = crate::m_from_knp(hashers.k(), n, p);
-        WLockBloomFilter {
-            bit_vec: Box::into_raw(Box::new(BitVec::from_elem(m, false))),
-            is_writing: AtomicBool::new(false),
-            type_info: PhantomData,
-            k: hashers,
-        }
+        Self::try_with_rate(n, p, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Given a fixed size `k`, and an expected number of elements (`n`),
+    /// initialize the bloom filter with a computed `m` value to achieve the required error rate,
+    /// returning an error instead of panicking if they are invalid.
+    ///
+    /// # Errors
+    /// If `k` or `n` are 0, if `p` is not within `(0, 1)`,
+    /// or if `p` is so close to 1 that no bits would be needed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::try_with_rate(10000, 1.5, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::InvalidFalsePositiveRate(1.5)));
+    /// ```
+    pub fn try_with_rate(n: usize, p: f64, hashers: H) -> Result<Self, BloomError> {
+        let m = crate::m_from_knp(hashers.k(), n, p)?;
+        Ok(Self::from_parts(BitVec::from_elem(m, false), hashers))
     }
 }
 
 impl<T, K> WLockBloomFilter<T, K>
 where
     T: Hash,
-    K: HashToIndices,
+    K: HashToIndices + crate::hash_to_indicies::K,
 {
     /// Creates the bloom filter with a given number of bits
     /// and with a multiple-hashing-to-index function.
@@ -130,6 +182,9 @@ where
     /// * `m` - Number of bits for the BloomFilter.
     /// * `hashers` - Hashing to indices structure.
     ///
+    /// # Panics
+    /// If `m` or the `k` of the hashers are 0.
+    ///
     /// # Examples
     /// ```
     /// use bloom_filter::WLockBloomFilter;
@@ -138,17 +193,40 @@ where
     /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
     /// ```
     pub fn new(m: usize, hashers: K) -> Self {
-        WLockBloomFilter {
-            bit_vec: Box::into_raw(Box::new(BitVec::from_elem(m, false))),
-            is_writing: AtomicBool::new(false),
-            type_info: PhantomData,
-            k: hashers,
-        }
+        Self::try_new(m, hashers).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates the bloom filter with a given number of bits and with a multiple-hashing-to-index function,
+    /// returning an error instead of panicking if `m` or `k` are 0.
+    ///
+    /// # Errors
+    /// If `m` or the `k` of the hashers are 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomError;
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::try_new(0, ReHasher::new(1));
+    /// assert_eq!(bf.err(), Some(BloomError::NoBits));
+    /// ```
+    pub fn try_new(m: usize, hashers: K) -> Result<Self, BloomError> {
+        let m = crate::error::check_m(m)?;
+        crate::error::check_k(hashers.k())?;
+        Ok(Self::from_parts(BitVec::from_elem(m, false), hashers))
     }
+}
 
+impl<T, K, S> WLockBloomFilter<T, K, S>
+where
+    T: Hash,
+    K: HashToIndices,
+    S: BitStore,
+{
     /// Gets the number of bits in the used in the bloom filter.
     pub fn num_bits(&self) -> usize {
-        unsafe { self.bit_vec.as_ref().unwrap().len() }
+        self.num_bits
     }
 
     /// Takes multiple hashes of the provided value, takes the hashes modulo the number of bits
@@ -172,19 +250,7 @@ where
     /// ```
     pub fn insert(&self, value: &T) {
         let indices = self.k.hash_to_indices(value, self.num_bits());
-        while self
-            .is_writing
-            .compare_and_swap(false, true, Ordering::Acquire)
-        {
-            #[cfg(std)]
-            std::thread::yield_now() // TODO check if this is faster or slower than just spinning. do for various thread counts.
-        }
-        indices
-            .into_iter()
-            .for_each(|i| unsafe { self.bit_vec.as_mut().unwrap().set(i, true) });
-
-        // release the lock
-        self.is_writing.store(false, Ordering::Release);
+        self.with_lock(|bits| indices.into_iter().for_each(|i| bits.set(i, true)));
     }
 
     /// Tests to see if the provided value is in the bloom filter.
@@ -197,7 +263,7 @@ where
     /// # Arguments
     ///
     /// * `value` - The value to be hashed to create indices into the bloom filter.
-    /// These indices will be used to see if the element has been added.
+    ///   These indices will be used to see if the element has been added.
     ///
     /// # Examples
     /// ```
@@ -212,31 +278,212 @@ where
     /// assert!(!bf.contains(&"not here"));
     /// ```
     pub fn contains(&self, value: &T) -> bool {
-        self.k
-            .hash_to_indices(value, self.num_bits())
-            .into_iter()
-            .all(|i| unsafe { self.bit_vec.as_ref().unwrap()[i] })
+        let indices = self.k.hash_to_indices(value, self.num_bits());
+        self.with_read_lock(|bits| indices.into_iter().all(|i| bits.get(i)))
+    }
+}
+
+impl<T, K, S: BitStore> WLockBloomFilter<T, K, S> {
+    /// Gets the number of bits that are set.
+    /// The read lock is held while they are counted, so concurrent inserts wait for it.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// assert_eq!(bf.count_ones(), 0);
+    /// bf.insert(&"hello");
+    /// assert_eq!(bf.count_ones(), 1);
+    /// ```
+    pub fn count_ones(&self) -> usize {
+        self.with_read_lock(|bits| bits.count_ones())
+    }
+
+    /// Gets the fraction of bits that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.num_bits as f64
+    }
+
+    /// Checks if no bits are set, which means nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.with_read_lock(|bits| bits.count_ones() == 0)
+    }
+
+    /// Checks if at least `threshold` of the bits are set.
+    /// Past around half, the false positive rate climbs quickly, until every lookup returns true.
+    ///
+    /// # Arguments
+    /// * `threshold` - The fill ratio, between 0 and 1, at which the filter is considered saturated.
+    pub fn is_saturated(&self, threshold: f64) -> bool {
+        self.fill_ratio() >= threshold
+    }
+
+    /// Unsets every bit in place, so the filter can be reused without replacing it wherever it is shared.
+    ///
+    /// # Notes
+    /// The write lock is held while the bits are cleared,
+    /// so an insert or a lookup happens either entirely before or entirely after it.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// bf.clear();
+    /// assert!(!bf.contains(&"hello"));
+    /// ```
+    pub fn clear(&self) {
+        self.with_lock(|bits| bits.clear())
+    }
+}
+
+impl<T, U: K, S: BitStore> WLockBloomFilter<T, U, S> {
+    /// Returns the chance that any given lookup will return a false positive,
+    /// computed from the fraction of bits that are set.
+    pub fn current_false_positive_rate(&self) -> f64 {
+        crate::sizing::false_positive_rate_from_fill(self.fill_ratio(), self.k.k())
+    }
+}
+
+/// The bit of `WLockBloomFilter::lock` that is set by a writer.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+impl<T, K, S: BitStore> WLockBloomFilter<T, K, S> {
+    fn from_parts(bits: S, k: K) -> Self {
+        WLockBloomFilter {
+            num_bits: bits.len(),
+            bits: UnsafeCell::new(bits),
+            lock: AtomicUsize::new(0),
+            type_info: PhantomData,
+            k,
+        }
+    }
+
+    /// Acquires the write lock, runs the provided function against the backing bits, and releases the lock.
+    pub(crate) fn with_lock<R, F: FnOnce(&mut S) -> R>(&self, f: F) -> R {
+        // The critical section only sets `k` bits, so the lock is spun on instead of yielding to the scheduler.
+        // Contention across many threads is addressed by `ShardedBloomFilter` instead, see the `threaded` benches.
+        loop {
+            let state = self.lock.load(Ordering::Relaxed);
+            if state & WRITER == 0
+                && self
+                    .lock
+                    .compare_exchange_weak(state, state | WRITER, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                break;
+            }
+            spin_loop()
+        }
+        // No new readers can get in now, so wait for the ones that already have.
+        while self.lock.load(Ordering::Acquire) != WRITER {
+            spin_loop()
+        }
+        let ret = self.bits.with_mut(|bits| f(unsafe { &mut *bits }));
+
+        // release the lock
+        self.lock.store(0, Ordering::Release);
+        ret
+    }
+
+    /// Acquires the lock alongside any other readers, runs the provided function against the backing bits,
+    /// and releases the lock.
+    fn with_read_lock<R, F: FnOnce(&S) -> R>(&self, f: F) -> R {
+        loop {
+            let state = self.lock.load(Ordering::Relaxed);
+            if state & WRITER == 0
+                && self
+                    .lock
+                    .compare_exchange_weak(state, state + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                break;
+            }
+            spin_loop()
+        }
+        let ret = self.bits.with(|bits| f(unsafe { &*bits }));
+
+        self.lock.fetch_sub(1, Ordering::Release);
+        ret
+    }
+
+    /// Creates a filter that can be shared across threads from a snapshot.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let mut snapshot = BloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// snapshot.insert(&"hello");
+    /// let bf = WLockBloomFilter::from_snapshot(snapshot);
+    /// assert!(bf.contains(&"hello"));
+    /// ```
+    pub fn from_snapshot(snapshot: BloomFilter<T, K, S>) -> Self {
+        Self::from_parts(snapshot.bits, snapshot.k)
+    }
+}
+
+impl<T, K: Clone, S: BitStore + Clone> WLockBloomFilter<T, K, S> {
+    /// Copies the filter into an owned `BloomFilter`.
+    ///
+    /// # Notes
+    /// The write lock is held while the bits are copied,
+    /// so every insert is either entirely present in the snapshot, or entirely absent from it.
+    /// Writers are only blocked for the duration of the copy.
+    ///
+    /// # Examples
+    /// ```
+    /// use bloom_filter::WLockBloomFilter;
+    /// use bloom_filter::ReHasher;
+    /// use murmur3::murmur3_32::MurmurHasher;
+    /// let bf = WLockBloomFilter::<&str, ReHasher<MurmurHasher>>::new(100000, ReHasher::new(1));
+    /// bf.insert(&"hello");
+    /// let snapshot = bf.snapshot();
+    /// bf.insert(&"there");
+    /// assert!(snapshot.contains(&"hello"));
+    /// assert!(!snapshot.contains(&"there"));
+    /// ```
+    pub fn snapshot(&self) -> BloomFilter<T, K, S> {
+        let bits = self.with_lock(|bits| bits.clone());
+        BloomFilter::from_parts(bits, self.k.clone())
     }
 }
 
-impl<T, U: K> K for WLockBloomFilter<T, U> {
+// Copies through a snapshot, so an insert running at the same time is either entirely copied or not at all.
+impl<T, K: Clone, S: BitStore + Clone> Clone for WLockBloomFilter<T, K, S> {
+    fn clone(&self) -> Self {
+        Self::from_snapshot(self.snapshot())
+    }
+}
+
+impl<T, U: K, S> K for WLockBloomFilter<T, U, S> {
     fn k(&self) -> usize {
         self.k.k()
     }
 }
 
-impl<T, K> Drop for WLockBloomFilter<T, K> {
-    fn drop(&mut self) {
-        unsafe {
-            let _ = Box::from_raw(self.bit_vec);
-        }
+impl<T, K: fmt::Debug, S: BitStore + fmt::Debug> fmt::Debug for WLockBloomFilter<T, K, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.with_read_lock(|bits| {
+            f.debug_struct("WLockBloomFilter")
+                .field("bits", bits)
+                .field("k", &self.k)
+                .finish()
+        })
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(loom)))]
 mod tests {
     use super::*;
     use murmur3::murmur3_32::MurmurHasher;
+    use std::sync::Arc;
 
     #[test]
     fn optimal_constructor() {
@@ -246,4 +493,165 @@ mod tests {
         assert_eq!(bf.k(), 7)
     }
 
+    #[test]
+    fn other_store() {
+        let bf: WLockBloomFilter<i32, ReHasher<MurmurHasher>, Box<[u64]>> =
+            WLockBloomFilter::from_snapshot(BloomFilter::with_store(
+                vec![0; 16].into_boxed_slice(),
+                ReHasher::new(3),
+            ));
+        (0..100).for_each(|n| bf.insert(&n));
+        assert_eq!(bf.num_bits(), 1024);
+        (0..100).for_each(|n| assert!(bf.contains(&n)));
+        let snapshot = bf.snapshot();
+        assert_eq!(snapshot.count_ones(), bf.count_ones());
+        bf.clear();
+        assert!(bf.is_empty());
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let bf: WLockBloomFilter<i32, ReHasher<MurmurHasher>> =
+            WLockBloomFilter::optimal_new(1000, 0.01);
+        (0..500).for_each(|n| bf.insert(&n));
+        let restored = WLockBloomFilter::from_snapshot(bf.snapshot());
+        assert_eq!(restored.num_bits(), bf.num_bits());
+        (0..1000).for_each(|n| assert_eq!(restored.contains(&n), bf.contains(&n)));
+    }
+
+    #[test]
+    fn clone_is_independent() {
+        let bf: WLockBloomFilter<&str, ReHasher<MurmurHasher>> =
+            WLockBloomFilter::optimal_new(1000, 0.01);
+        bf.insert(&"hello");
+        let cloned = bf.clone();
+        bf.insert(&"there");
+        assert!(cloned.contains(&"hello"));
+        assert!(!cloned.contains(&"there"));
+        assert_eq!(cloned.num_bits(), bf.num_bits());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Too slow to interpret.
+    fn snapshot_while_writing() {
+        let bf: Arc<WLockBloomFilter<i32, ReHasher<MurmurHasher>>> =
+            Arc::new(WLockBloomFilter::optimal_new(10_000, 0.01));
+        let writer = {
+            let bf = bf.clone();
+            std::thread::spawn(move || (0..10_000).for_each(|n| bf.insert(&n)))
+        };
+        let snapshots: Vec<_> = (0..10).map(|_| bf.snapshot()).collect();
+        writer.join().unwrap();
+        snapshots.windows(2).for_each(|w| {
+            (0..10_000)
+                .filter(|n| w[0].contains(n))
+                .for_each(|n| assert!(w[1].contains(&n)))
+        });
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+    use murmur3::murmur3_32::MurmurHasher;
+
+    type Filter = WLockBloomFilter<&'static str, ReHasher<MurmurHasher>>;
+
+    fn set_bits(bit_vec: &BitVec) -> usize {
+        bit_vec.iter().filter(|b| *b).count()
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        loom::model(|| {
+            let bf: Arc<Filter> = Arc::new(WLockBloomFilter::new(16, ReHasher::new(2)));
+            let handles: Vec<_> = ["hello", "there"]
+                .iter()
+                .map(|value| {
+                    let bf = bf.clone();
+                    thread::spawn(move || bf.insert(value))
+                })
+                .collect();
+            handles.into_iter().for_each(|h| h.join().unwrap());
+            assert!(bf.contains(&"hello"));
+            assert!(bf.contains(&"there"));
+        });
+    }
+
+    #[test]
+    fn concurrent_contains() {
+        loom::model(|| {
+            let bf: Arc<Filter> = Arc::new(WLockBloomFilter::new(16, ReHasher::new(2)));
+            bf.insert(&"hello");
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let bf = bf.clone();
+                    thread::spawn(move || assert!(bf.contains(&"hello")))
+                })
+                .collect();
+            handles.into_iter().for_each(|h| h.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn contains_during_insert() {
+        loom::model(|| {
+            let bf: Arc<Filter> = Arc::new(WLockBloomFilter::new(16, ReHasher::new(2)));
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"hello"))
+            };
+            // Either outcome is fine, as long as the bits aren't read while they are being set.
+            bf.contains(&"hello");
+            writer.join().unwrap();
+            assert!(bf.contains(&"hello"));
+        });
+    }
+
+    #[test]
+    fn contains_during_clear() {
+        loom::model(|| {
+            let bf: Arc<Filter> = Arc::new(WLockBloomFilter::new(16, ReHasher::new(2)));
+            bf.insert(&"hello");
+            let clearer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.clear())
+            };
+            bf.contains(&"hello");
+            clearer.join().unwrap();
+            assert!(!bf.contains(&"hello"));
+        });
+    }
+
+    #[test]
+    fn snapshot_during_insert() {
+        loom::model(|| {
+            let bf: Arc<Filter> = Arc::new(WLockBloomFilter::new(16, ReHasher::new(2)));
+            let mut reference = BloomFilter::new(16, ReHasher::<MurmurHasher>::new(2));
+            reference.insert(&"hello");
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"hello"))
+            };
+            let snapshot = bf.snapshot();
+            writer.join().unwrap();
+            let seen = set_bits(&snapshot.bits);
+            assert!(seen == 0 || seen == set_bits(&reference.bits));
+        });
+    }
+
+    #[test]
+    fn drop_on_another_thread() {
+        loom::model(|| {
+            let bf: Arc<Filter> = Arc::new(WLockBloomFilter::new(16, ReHasher::new(2)));
+            let writer = {
+                let bf = bf.clone();
+                thread::spawn(move || bf.insert(&"hello"))
+            };
+            drop(bf);
+            writer.join().unwrap();
+        });
+    }
 }
@@ -0,0 +1,46 @@
+//! Builds the static library, compiles `capi/bloom_filter_test.c` against it and the checked in header,
+//! the way a C program would use the crate, and runs it.
+#![cfg(all(feature = "capi", unix))]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_program() {
+    // The library is built like the README says to, with only the `capi` feature, into a target directory of its own,
+    // so the other features of this build, like `python`, don't leave it with symbols a C program can't resolve.
+    let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "capi",
+            "--crate-type",
+            "staticlib",
+            "--target-dir",
+        ])
+        .arg(&target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "the static library didn't build");
+    let exe = target_dir.join("bloom_filter_test");
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .args(["-Wall", "-Werror", "-Icapi", "capi/bloom_filter_test.c", "-o"])
+        .arg(&exe)
+        .arg(target_dir.join("debug").join("libbloom_filter.a"))
+        .args(["-lpthread", "-ldl", "-lm"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .expect("a C compiler is needed, set CC if it isn't `cc`");
+    assert!(status.success(), "capi/bloom_filter_test.c didn't compile");
+    let output = Command::new(&exe).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}